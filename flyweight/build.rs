@@ -3,4 +3,3 @@ extern crate lalrpop;
 fn main() {
     lalrpop::process_root().unwrap();
 }
-
@@ -0,0 +1,234 @@
+//! What each bound variable of a constraint block does across every
+//! solution: whether it's stuck at one value, always equal or opposite to
+//! another bound variable, or just a copy of one input.
+//!
+//! A bound variable stuck at a value is an error, reported by
+//! `Constraints::assert_bound_used`; a signal that can never show clear
+//! isn't doing anything. The copies are warnings, since an aspect that only
+//! repeats another can be replaced by it.
+//!
+//! Candidates are found by simulation first: a few dozen solutions, one
+//! for each of a set of pseudo-random free assignments, rule out most
+//! pairs without asking the solver. Each query that then fails to prove a
+//! relation adds its solution to the set.
+
+use crate::ast::nodes::{Constraints, Ident};
+use crate::ast::symbols::{SymbolTable, VarId};
+use crate::bl::sat::{Lit, Solver};
+use crate::diagnostics::Report;
+use std::collections::HashMap;
+
+/// How many pseudo-random free assignments are tried for solutions to
+/// simulate.
+const SAMPLES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    /// Has this value in every solution.
+    Constant(bool),
+    /// Equals `other`, an earlier bound variable, in every solution, or its
+    /// negation if `negated`.
+    Equal { other: Ident, negated: bool },
+    /// Equals the free variable `input` in every solution, or its negation
+    /// if `negated`.
+    Input { input: VarId, negated: bool },
+    /// None of the above.
+    Varies,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundBehavior {
+    pub var: Ident,
+    pub behavior: Behavior,
+}
+
+impl BoundBehavior {
+    /// A warning for a bound variable that copies another or an input.
+    /// Constants are errors, which `assert_bound_used` reports.
+    pub fn warning(&self, symbols: &SymbolTable) -> Option<Report> {
+        let name = symbols.name(self.var.id);
+        let relation = |negated: bool| match negated {
+            false => "the same value as",
+            true => "the opposite value of",
+        };
+        let not = |negated: bool| if negated { "~" } else { "" };
+        match self.behavior {
+            Behavior::Equal { other, negated } => Some(
+                Report::warning(format!(
+                    "bound variable `{}` always has {} `{}`",
+                    name,
+                    relation(negated),
+                    symbols.name(other.id)
+                ))
+                .with_label(self.var.span, "")
+                .with_secondary_label(other.span, "")
+                .with_note(format!(
+                    "it can be replaced by `{}{}`",
+                    not(negated),
+                    symbols.name(other.id)
+                )),
+            ),
+            Behavior::Input { input, negated } => Some(
+                Report::warning(format!(
+                    "bound variable `{}` always has {} the input `{}`",
+                    name,
+                    relation(negated),
+                    symbols.name(input)
+                ))
+                .with_label(self.var.span, "")
+                .with_note(format!(
+                    "it can be replaced by `{}{}`",
+                    not(negated),
+                    symbols.name(input)
+                )),
+            ),
+            Behavior::Constant(_) | Behavior::Varies => None,
+        }
+    }
+}
+
+/// The clauses in a solver, and solutions seen so far, each as the values
+/// of the free variables then the bound ones.
+struct Sweep {
+    solver: Solver,
+    lits: Vec<Lit>,
+    all: Lit,
+    models: Vec<Vec<bool>>,
+}
+
+impl Sweep {
+    fn model(&self) -> Vec<bool> {
+        self.lits
+            .iter()
+            .map(|l| self.solver.model_value(*l))
+            .collect()
+    }
+
+    /// Solutions for pseudo-random assignments of the first `free`
+    /// variables, or any one solution if none of those have one.
+    fn sample(&mut self, free: usize) {
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        for _ in 0..if free == 0 { 1 } else { SAMPLES } {
+            let mut assumptions = vec![self.all];
+            for l in &self.lits[..free] {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                assumptions.push(if seed & 1 == 1 { *l } else { !*l });
+            }
+            if self.solver.solve_with(&assumptions) {
+                self.models.push(self.model());
+            }
+        }
+        if self.models.is_empty() && self.solver.solve_with(&[self.all]) {
+            self.models.push(self.model());
+        }
+    }
+
+    /// Whether `lits[i]` is `value` in every solution.
+    fn constant(&mut self, i: usize, value: bool) -> bool {
+        if self.models.iter().any(|m| m[i] != value) {
+            return false;
+        }
+        let other = if value { !self.lits[i] } else { self.lits[i] };
+        self.never(other)
+    }
+
+    /// Whether `lits[i]` equals `lits[j]`, or its negation if `negated`, in
+    /// every solution.
+    fn equal(&mut self, i: usize, j: usize, negated: bool) -> bool {
+        if self.models.iter().any(|m| (m[i] != m[j]) != negated) {
+            return false;
+        }
+        let differ = self.solver.xor(self.lits[i], self.lits[j]);
+        self.never(if negated { !differ } else { differ })
+    }
+
+    /// Whether no solution has `lit`; one that does joins the samples.
+    fn never(&mut self, lit: Lit) -> bool {
+        if self.solver.solve_with(&[self.all, lit]) {
+            self.models.push(self.model());
+            return false;
+        }
+        true
+    }
+}
+
+impl Constraints {
+    /// What each bound variable does across every solution, in declaration
+    /// order. A block without solutions has no behavior to speak of, so its
+    /// variables all vary.
+    pub fn bound_behavior(&self) -> Vec<BoundBehavior> {
+        self.behaviors(true)
+    }
+
+    /// As `bound_behavior`, but only looking for constants unless
+    /// `related`.
+    pub(crate) fn behaviors(&self, related: bool) -> Vec<BoundBehavior> {
+        let free = self.free_vars();
+        let mut solver = Solver::new();
+        let ids: Vec<VarId> = free
+            .iter()
+            .copied()
+            .chain(self.bound.iter().map(|b| b.id))
+            .collect();
+        let lits: Vec<Lit> = ids.iter().map(|_| solver.new_var().lit(true)).collect();
+        let mut vars: HashMap<VarId, Lit> = ids.iter().copied().zip(lits.clone()).collect();
+        let clauses: Vec<Lit> = self
+            .exprs
+            .iter()
+            .map(|e| solver.expr(e, &mut vars))
+            .collect();
+        let all = solver.and(&clauses);
+        let mut sweep = Sweep {
+            solver,
+            lits,
+            all,
+            models: vec![],
+        };
+        sweep.sample(free.len());
+
+        let mut found: Vec<BoundBehavior> = vec![];
+        for (k, var) in self.bound.iter().enumerate() {
+            let i = free.len() + k;
+            let behavior = match sweep.models.first().map(|m| m[i]) {
+                None => Behavior::Varies,
+                Some(value) if sweep.constant(i, value) => Behavior::Constant(value),
+                Some(_) if !related => Behavior::Varies,
+                Some(_) => {
+                    let first = sweep.models[0].clone();
+                    // Only compare with earlier variables that aren't
+                    // themselves constants or copies; those were compared
+                    // with what they copy.
+                    let other = found
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, b)| {
+                            matches!(b.behavior, Behavior::Varies | Behavior::Input { .. })
+                        })
+                        .map(|(j, b)| (free.len() + j, b.var))
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                        .find(|(j, _)| sweep.equal(i, *j, first[i] != first[*j]));
+                    match other {
+                        Some((j, other)) => Behavior::Equal {
+                            other,
+                            negated: first[i] != first[j],
+                        },
+                        None => (0..free.len())
+                            .find(|j| sweep.equal(i, *j, first[i] != first[*j]))
+                            .map_or(Behavior::Varies, |j| Behavior::Input {
+                                input: free[j],
+                                negated: first[i] != first[j],
+                            }),
+                    }
+                }
+            };
+            found.push(BoundBehavior {
+                var: *var,
+                behavior,
+            });
+        }
+        found
+    }
+}
@@ -0,0 +1,191 @@
+//! Condensing each subexpression of a constraint block that mentions no
+//! bound variable into a variable of its own.
+//!
+//! Such a subexpression only depends on the free variables, so at solving
+//! time it's just another input, and the solver only needs to know its
+//! value, not how it's computed. The generated code computes each one once,
+//! as the `inputs` say, and looks the rest up as before.
+//!
+//! The new variables are named `$0`, `$1`, ..., which no source name can
+//! clash with.
+
+use crate::ast::nodes::{Constraints, Expr, ExprKind, Ident, VarMap};
+use crate::ast::symbols::{SymbolTable, VarId};
+use crate::bl::sat::Solver;
+use std::collections::{HashMap, HashSet};
+
+/// A constraint block with its free-only subexpressions condensed.
+#[derive(Debug)]
+pub struct Condensed {
+    /// The block, each clause keeping its index and span. Its free list
+    /// is the new variables, then the free variables still used directly.
+    pub constraints: Constraints,
+    /// What each new variable stands for, in order of first use. Identical
+    /// subexpressions share a variable.
+    pub inputs: Vec<Input>,
+}
+
+/// A variable standing for `expr`, a subexpression of only free variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Input {
+    pub var: Ident,
+    pub expr: Expr,
+}
+
+impl Constraints {
+    /// Replaces every maximal subexpression that mentions free variables
+    /// but no bound one with a new variable. Variables are left alone, as
+    /// there's nothing to gain, and negations are kept outside.
+    pub fn condense(&self, symbols: &mut SymbolTable) -> Condensed {
+        let mut inputs = vec![];
+        let exprs: Vec<Expr> = self
+            .exprs
+            .iter()
+            .map(|e| self.condense_expr(e, symbols, &mut inputs))
+            .collect();
+        let condensed: HashSet<VarId> = inputs.iter().map(|i: &Input| i.var.id).collect();
+        let used: HashSet<VarId> = exprs.iter().flat_map(|e| e.variables()).collect();
+        let free = inputs
+            .iter()
+            .map(|i| i.var)
+            .chain(
+                self.free
+                    .iter()
+                    .filter(|f| used.contains(&f.id) && !condensed.contains(&f.id))
+                    .copied(),
+            )
+            .collect();
+        Condensed {
+            constraints: Constraints {
+                free,
+                bound: self.bound.clone(),
+                exprs,
+                span: self.span,
+            },
+            inputs,
+        }
+    }
+
+    fn condense_expr(&self, e: &Expr, symbols: &mut SymbolTable, inputs: &mut Vec<Input>) -> Expr {
+        let vars = e.variables();
+        let free_only = !vars.is_empty() && vars.iter().all(|v| !self.is_bound(*v));
+        match &e.kind {
+            ExprKind::Term(_) | ExprKind::True | ExprKind::False => return e.clone(),
+            // Condensing under the negation lets `~(a | b)` share with `a | b`.
+            ExprKind::Not(_) => {}
+            _ if free_only => {
+                let id = match inputs.iter().find(|i| i.expr == *e) {
+                    Some(i) => i.var.id,
+                    None => {
+                        let id = fresh(symbols);
+                        inputs.push(Input {
+                            var: Ident { id, span: e.span },
+                            expr: e.clone(),
+                        });
+                        id
+                    }
+                };
+                return Expr::new(ExprKind::Term(id), e.span);
+            }
+            _ => {}
+        }
+        let mut go = |a: &Expr| Box::new(self.condense_expr(a, symbols, inputs));
+        let kind = match &e.kind {
+            ExprKind::Term(_) | ExprKind::True | ExprKind::False => unreachable!(),
+            ExprKind::Not(a) => ExprKind::Not(go(a)),
+            ExprKind::And(a, b) => ExprKind::And(go(a), go(b)),
+            ExprKind::Or(a, b) => ExprKind::Or(go(a), go(b)),
+            ExprKind::Implication(a, b) => ExprKind::Implication(go(a), go(b)),
+            ExprKind::Biconditional(a, b) => ExprKind::Biconditional(go(a), go(b)),
+            ExprKind::Xor(a, b) => ExprKind::Xor(go(a), go(b)),
+        };
+        Expr::new(kind, e.span)
+    }
+}
+
+/// The first of `$0`, `$1`, ... not yet in `symbols`.
+fn fresh(symbols: &mut SymbolTable) -> VarId {
+    let name = (0..)
+        .map(|n| format!("${}", n))
+        .find(|n| symbols.get(n).is_none())
+        .unwrap();
+    symbols.intern(&name)
+}
+
+impl Condensed {
+    pub fn input(&self, v: VarId) -> Option<&Input> {
+        self.inputs.iter().find(|i| i.var.id == v)
+    }
+
+    /// `e` with each new variable replaced by what it stands for.
+    pub fn expand(&self, e: &Expr) -> Expr {
+        let go = |a: &Expr| Box::new(self.expand(a));
+        let kind = match &e.kind {
+            ExprKind::Term(v) => match self.input(*v) {
+                Some(i) => i.expr.kind.clone(),
+                None => ExprKind::Term(*v),
+            },
+            ExprKind::True => ExprKind::True,
+            ExprKind::False => ExprKind::False,
+            ExprKind::Not(a) => ExprKind::Not(go(a)),
+            ExprKind::And(a, b) => ExprKind::And(go(a), go(b)),
+            ExprKind::Or(a, b) => ExprKind::Or(go(a), go(b)),
+            ExprKind::Implication(a, b) => ExprKind::Implication(go(a), go(b)),
+            ExprKind::Biconditional(a, b) => ExprKind::Biconditional(go(a), go(b)),
+            ExprKind::Xor(a, b) => ExprKind::Xor(go(a), go(b)),
+        };
+        Expr::new(kind, e.span)
+    }
+
+    /// Whether every assignment of the condensed block's free variables
+    /// comes from some assignment of the original's, so that both have the
+    /// same solutions for the same question. That holds when no two inputs,
+    /// nor an input and a free variable still used directly, share a
+    /// variable, and no input is constant.
+    pub fn exact(&self) -> bool {
+        let mut seen: HashSet<VarId> = self
+            .constraints
+            .free_vars()
+            .into_iter()
+            .filter(|v| self.input(*v).is_none())
+            .collect();
+        for i in &self.inputs {
+            let vars = i.expr.variables();
+            if vars.iter().any(|v| seen.contains(v)) {
+                return false;
+            }
+            seen.extend(vars);
+
+            let mut solver = Solver::new();
+            let l = solver.expr(&i.expr, &mut HashMap::new());
+            if !solver.solve_with(&[l]) || !solver.solve_with(&[!l]) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// An assignment of the original free variables that gives the
+    /// condensed ones the values in `m`; other variables are kept. A value
+    /// an input can't have is dropped, which can only happen if the
+    /// condensing isn't `exact`.
+    pub fn lift(&self, m: &VarMap) -> VarMap {
+        let mut lifted = HashMap::new();
+        for (v, value) in m.assignment() {
+            let input = match self.input(v) {
+                Some(i) => i,
+                None => {
+                    lifted.insert(v, value);
+                    continue;
+                }
+            };
+            let mut solver = Solver::new();
+            let mut vars = HashMap::new();
+            let l = solver.expr(&input.expr, &mut vars);
+            if solver.solve_with(&[if value { l } else { !l }]) {
+                lifted.extend(vars.iter().map(|(v, l)| (*v, solver.model_value(*l))));
+            }
+        }
+        lifted.into()
+    }
+}
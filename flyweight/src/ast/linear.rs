@@ -0,0 +1,50 @@
+//! Solving a constraint block by its linear part.
+//!
+//! Aspect encodings are mostly `xor`s: as Zhegalkin polynomials (see
+//! `bl::anf`) most of their clauses are linear in the bound variables,
+//! however the free variables enter. Gaussian elimination over GF(2) on
+//! those gives each bound variable directly when they have full rank, and
+//! the other clauses only have to hold with those values substituted.
+//! `Constraints::assert_bound_used` tries this before the SAT solver, which
+//! is still what finds a witness when it fails.
+
+use crate::ast::nodes::{Constraints, Ident};
+use crate::ast::symbols::VarId;
+use crate::bl::anf::{Anf, XorSystem};
+use crate::bl::astToLogic;
+use std::collections::HashMap;
+
+impl Constraints {
+    /// Each bound variable as a polynomial in the free variables, in
+    /// declaration order, if the clauses linear in the bound variables
+    /// determine all of them for every assignment of the free ones, and the
+    /// rest then always hold. `None` says nothing either way.
+    pub fn linear_solution(&self) -> Option<Vec<(Ident, Anf)>> {
+        let bound: Vec<VarId> = self.bound_ids().collect();
+        let mut system = XorSystem::new(&bound);
+        let mut rest = vec![];
+        for e in &self.exprs {
+            let p = Anf::new(&astToLogic(e))?;
+            if !system.assert(&p) {
+                rest.push(p);
+            }
+        }
+
+        let solved = system.eliminate();
+        if !solved.unconstrained.is_empty() || !solved.conditions.is_empty() {
+            return None;
+        }
+        let values: HashMap<VarId, Anf> = solved.determined.into_iter().collect();
+        for p in rest {
+            if p.substitute(&values)?.as_constant() != Some(true) {
+                return None;
+            }
+        }
+        Some(
+            self.bound
+                .iter()
+                .map(|b| (*b, values[&b.id].clone()))
+                .collect(),
+        )
+    }
+}
@@ -1 +1,10 @@
+pub mod behavior;
+pub mod condense;
+pub mod linear;
+pub mod names;
 pub mod nodes;
+pub mod redundancy;
+pub mod skolem;
+pub mod solvability;
+pub mod span;
+pub mod symbols;
@@ -0,0 +1,213 @@
+//! Name resolution: every variable a program mentions must be declared at
+//! the top level (`A_occ <- false`), every declared variable must be used
+//! somewhere, and no variable may be declared or bound twice, or listed
+//! twice in one block.
+
+use crate::ast::nodes::{
+    Constraints, Declaration, Ident, Program, Statement, StatementKind, Value,
+};
+use crate::ast::symbols::{SymbolTable, VarId};
+use crate::diagnostics::Report;
+use std::collections::HashMap;
+
+/// The top-level variable declarations of a program, by variable.
+pub struct Declarations<'p> {
+    by_id: HashMap<VarId, &'p Declaration>,
+}
+
+impl<'p> Declarations<'p> {
+    /// Builds the table, reporting any variable declared more than once.
+    pub fn new(program: &'p Program, symbols: &SymbolTable) -> (Declarations<'p>, Vec<Report>) {
+        let mut by_id: HashMap<VarId, &'p Declaration> = HashMap::new();
+        let mut errs = vec![];
+        for d in program.declarations() {
+            if let Some(first) = by_id.get(&d.name.id) {
+                errs.push(
+                    Report::error(format!(
+                        "`{}` is declared more than once",
+                        symbols.name(d.name.id)
+                    ))
+                    .with_label(d.name.span, "declared again here")
+                    .with_secondary_label(first.name.span, "first declared here"),
+                );
+            } else {
+                by_id.insert(d.name.id, d);
+            }
+        }
+        (Declarations { by_id }, errs)
+    }
+
+    pub fn get(&self, id: VarId) -> Option<&'p Declaration> {
+        self.by_id.get(&id).copied()
+    }
+
+    pub fn contains(&self, id: VarId) -> bool {
+        self.by_id.contains_key(&id)
+    }
+}
+
+impl Program {
+    /// Checks that every name is declared, that no constraint block lists a
+    /// name as both free and bound, and that every declaration is used.
+    pub fn check_names(&self, symbols: &SymbolTable) -> Result<(), Vec<Report>> {
+        self.name_errors(symbols, true)
+    }
+
+    /// `check_names` for a program recovered from syntax errors. Unused
+    /// declarations aren't reported, since the lines using them may be among
+    /// those that failed to parse.
+    pub fn check_names_partial(&self, symbols: &SymbolTable) -> Result<(), Vec<Report>> {
+        self.name_errors(symbols, false)
+    }
+
+    fn name_errors(&self, symbols: &SymbolTable, report_unused: bool) -> Result<(), Vec<Report>> {
+        let (decls, mut errs) = Declarations::new(self, symbols);
+
+        let mut uses: Vec<Ident> = vec![];
+        let mut bound_by: HashMap<VarId, Ident> = HashMap::new();
+        for c in self.constraints() {
+            errs.append(&mut c.check_names(symbols));
+            uses.extend(c.free.iter().chain(c.bound.iter()).copied());
+            uses.extend(c.exprs.iter().flat_map(|e| e.idents()));
+            // A variable is set by exactly one block; two blocks binding it
+            // (typically from different files) would fight over its value.
+            for (k, b) in c.bound.iter().enumerate() {
+                // Repeats within the block are its own `check_names` error.
+                if c.bound[..k].iter().any(|p| p.id == b.id) {
+                    continue;
+                }
+                if let Some(first) = bound_by.get(&b.id) {
+                    errs.push(
+                        Report::error(format!(
+                            "`{}` is bound by more than one constraint block",
+                            symbols.name(b.id)
+                        ))
+                        .with_label(b.span, "bound again here")
+                        .with_secondary_label(first.span, "first bound here"),
+                    );
+                } else {
+                    bound_by.insert(b.id, *b);
+                }
+            }
+        }
+        for r in self.runs() {
+            for s in &r.statements {
+                statement_idents(s, &mut uses);
+            }
+        }
+
+        // One report per undeclared name, labelling every use of it.
+        let mut undeclared: Vec<(VarId, Vec<Ident>)> = vec![];
+        for u in uses.iter().filter(|u| !decls.contains(u.id)) {
+            match undeclared.iter_mut().find(|(id, _)| *id == u.id) {
+                Some((_, v)) => v.push(*u),
+                None => undeclared.push((u.id, vec![*u])),
+            }
+        }
+        for (id, idents) in undeclared {
+            let name = symbols.name(id);
+            let report = Report::error(format!("`{}` is not declared", name)).with_note(format!(
+                "declare it at the top level, e.g. `{} <- false`",
+                name
+            ));
+            errs.push(
+                idents
+                    .iter()
+                    .fold(report, |r, i| r.with_label(i.span, "not declared")),
+            );
+        }
+
+        for d in self.declarations().filter(|_| report_unused) {
+            if !uses.iter().any(|u| u.id == d.name.id) {
+                errs.push(
+                    Report::error(format!(
+                        "`{}` is declared but never used",
+                        symbols.name(d.name.id)
+                    ))
+                    .with_label(d.name.span, ""),
+                );
+            }
+        }
+
+        if !errs.is_empty() {
+            errs.sort_by_key(|e| e.span());
+            return Err(errs);
+        }
+        Ok(())
+    }
+}
+
+impl Constraints {
+    /// Checks the `free=` and `bound=` lists against each other and against
+    /// the clauses. Without a `free=` list every name that isn't bound is
+    /// free; with one, every name used must be listed.
+    pub fn check_names(&self, symbols: &SymbolTable) -> Vec<Report> {
+        let mut errs = vec![];
+        for (list, kind) in &[(&self.free, "free"), (&self.bound, "bound")] {
+            for (k, i) in list.iter().enumerate() {
+                if let Some(first) = list[..k].iter().find(|p| p.id == i.id) {
+                    errs.push(
+                        Report::error(format!(
+                            "`{}` is listed twice in `{}=`",
+                            symbols.name(i.id),
+                            kind
+                        ))
+                        .with_label(i.span, "listed again here")
+                        .with_secondary_label(first.span, "first listed here"),
+                    );
+                }
+            }
+        }
+        for (k, b) in self.bound.iter().enumerate() {
+            if self.bound[..k].iter().any(|p| p.id == b.id) {
+                continue;
+            }
+            if let Some(f) = self.free.iter().find(|f| f.id == b.id) {
+                errs.push(
+                    Report::error(format!(
+                        "`{}` is listed as both free and bound",
+                        symbols.name(b.id)
+                    ))
+                    .with_label(b.span, "bound here")
+                    .with_secondary_label(f.span, "free here"),
+                );
+            }
+        }
+        if !self.free.is_empty() {
+            let mut reported = vec![];
+            for i in self.exprs.iter().flat_map(|e| e.idents()) {
+                let listed = self.is_bound(i.id) || self.free.iter().any(|f| f.id == i.id);
+                if !listed && !reported.contains(&i.id) {
+                    reported.push(i.id);
+                    errs.push(
+                        Report::error(format!(
+                            "`{}` is neither free nor bound here",
+                            symbols.name(i.id)
+                        ))
+                        .with_label(i.span, "")
+                        .with_note("add it to this block's `free=` or `bound=` list"),
+                    );
+                }
+            }
+        }
+        errs
+    }
+}
+
+fn statement_idents(s: &Statement, uses: &mut Vec<Ident>) {
+    match &s.kind {
+        StatementKind::Assign { targets, value } => {
+            uses.extend(targets.iter().copied());
+            match value {
+                Value::Input(_) => (),
+                Value::Expr(e) => uses.extend(e.idents()),
+                Value::Transaction(body, _) => {
+                    for s in body {
+                        statement_idents(s, uses);
+                    }
+                }
+            }
+        }
+        StatementKind::Output(e) => uses.extend(e.idents()),
+    }
+}
@@ -1,72 +1,229 @@
+use crate::ast::span::Span;
+use crate::ast::symbols::{Names, SymbolTable, VarId};
+use crate::bl::bdd::Bdd;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
-type TermName<'a> = &'a str;
+type TermName = VarId;
 
-#[derive(PartialEq, Eq, Clone)]
-pub enum Expr<'a> {
-    Term(TermName<'a>),
-    And(Box<Expr<'a>>, Box<Expr<'a>>),
-    Or(Box<Expr<'a>>, Box<Expr<'a>>),
-    Implication(Box<Expr<'a>>, Box<Expr<'a>>),
-    Biconditional(Box<Expr<'a>>, Box<Expr<'a>>),
-    Xor(Box<Expr<'a>>, Box<Expr<'a>>),
-    Not(Box<Expr<'a>>),
+/// The most variables a truth table is enumerated over, for 2^16 rows.
+pub const MAX_TABLE_VARS: usize = 16;
+
+/// A variable, un-negated (`true`) or negated (`false`), in a CNF clause.
+pub type Literal = (TermName, bool);
+
+/// An expression and the span of source it was parsed from. Expressions
+/// built while evaluating or simplifying keep the span of the node they were
+/// derived from. Spans are ignored when comparing expressions.
+#[derive(Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum ExprKind {
+    Term(TermName),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Implication(Box<Expr>, Box<Expr>),
+    Biconditional(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
     True,
     False,
 }
 
-impl<'a> fmt::Display for Expr<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.pp(f)
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
     }
 }
-impl<'a> fmt::Debug for Expr<'a> {
+impl Eq for Expr {}
+
+/// Without a symbol table, variables print as their IDs, e.g. `(#0 & ~#1)`.
+impl fmt::Debug for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.pp(f)
+        self.pp(None, f)
     }
 }
 
-impl<'a> Hash for Expr<'a> {
+impl Names for Expr {
+    fn fmt_names(&self, symbols: &SymbolTable, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pp(Some(symbols), f)
+    }
+}
+
+impl Hash for Expr {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.ppf().hash(state);
+        self.kind.hash(state);
     }
 }
 
+/// A name as written in the source, e.g. in a `bound=` list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ident {
+    pub id: VarId,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Constraints {
+    pub free: Vec<Ident>,
+    pub bound: Vec<Ident>,
+    pub exprs: Vec<Expr>,
+    pub span: Span,
+}
+
+/// A whole Flyweight source file, with its items in source order.
+#[derive(Debug)]
+pub struct Program {
+    pub items: Vec<Item>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum Item {
+    Import(Import),
+    Declaration(Declaration),
+    Constraints(Constraints),
+    Run(Run),
+}
+
+/// `import "stationA.fw"`, with the quotes stripped from `path`.
 #[derive(Debug)]
-pub struct Constraints<'a> {
-    pub bound: Vec<&'a str>,
-    pub exprs: Vec<Expr<'a>>,
+pub struct Import {
+    pub path: String,
+    pub span: Span,
+}
+
+/// `A_occ <- false`: a variable and its initial value.
+#[derive(Debug)]
+pub struct Declaration {
+    pub name: Ident,
+    pub value: bool,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Run {
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum StatementKind {
+    /// `x <- IN`, `x <- expr` or `x, y <- transaction { ... }`. Only a
+    /// transaction may have more than one target.
+    Assign { targets: Vec<Ident>, value: Value },
+    /// `OUT <- expr`
+    Output(Expr),
+}
+
+#[derive(Debug)]
+pub enum Value {
+    Input(Span),
+    Expr(Expr),
+    Transaction(Vec<Statement>, Span),
+}
+
+impl Program {
+    pub fn imports(&self) -> impl Iterator<Item = &Import> {
+        self.items.iter().filter_map(|i| match i {
+            Item::Import(i) => Some(i),
+            _ => None,
+        })
+    }
+
+    pub fn declarations(&self) -> impl Iterator<Item = &Declaration> {
+        self.items.iter().filter_map(|i| match i {
+            Item::Declaration(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    pub fn constraints(&self) -> impl Iterator<Item = &Constraints> {
+        self.items.iter().filter_map(|i| match i {
+            Item::Constraints(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    pub fn runs(&self) -> impl Iterator<Item = &Run> {
+        self.items.iter().filter_map(|i| match i {
+            Item::Run(r) => Some(r),
+            _ => None,
+        })
+    }
 }
 
 #[derive(PartialEq, Eq, Clone)]
-pub struct VarMap<'a>(HashMap<&'a str, bool>);
+pub struct VarMap(HashMap<VarId, bool>);
 
-impl<'a> fmt::Display for VarMap<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.0)
+impl Names for VarMap {
+    fn fmt_names(&self, symbols: &SymbolTable, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut vars = self
+            .0
+            .iter()
+            .map(|(k, v)| (symbols.name(*k), *v))
+            .collect::<Vec<(&str, bool)>>();
+        vars.sort();
+        let vars = vars
+            .iter()
+            .map(|(k, v)| format!("{} = {}", k, v))
+            .collect::<Vec<String>>();
+        write!(f, "{}", vars.join(", "))
     }
 }
-impl<'a> fmt::Debug for VarMap<'a> {
+impl fmt::Debug for VarMap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self.0)
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
-pub struct TruthTable<'a> {
-    pub free_map: VarMap<'a>,
-    pub result: Expr<'a>,
+pub struct TruthTable {
+    pub free_map: VarMap,
+    pub result: Expr,
+}
+
+impl VarMap {
+    pub fn get(&self, v: VarId) -> Option<bool> {
+        self.0.get(&v).copied()
+    }
+
+    pub fn values(&self) -> &HashMap<VarId, bool> {
+        &self.0
+    }
+
+    /// The variables and their values, in ID order.
+    pub fn assignment(&self) -> Vec<(VarId, bool)> {
+        let mut vars: Vec<(VarId, bool)> = self.0.iter().map(|(k, v)| (*k, *v)).collect();
+        vars.sort();
+        vars
+    }
+}
+
+impl From<HashMap<VarId, bool>> for VarMap {
+    fn from(map: HashMap<VarId, bool>) -> VarMap {
+        VarMap(map)
+    }
 }
 
-impl<'a> Hash for VarMap<'a> {
+impl Hash for VarMap {
     fn hash<H: Hasher>(&self, state: &mut H) {
         let mut vars = self
             .0
             .iter()
             .map(|(k, v)| (*k, *v))
-            .collect::<Vec<(&str, bool)>>();
+            .collect::<Vec<(VarId, bool)>>();
         vars.sort();
 
         vars.iter().for_each(|(k, v)| {
@@ -76,255 +233,460 @@ impl<'a> Hash for VarMap<'a> {
     }
 }
 
-impl<'a> Constraints<'a> {
-    pub fn assert_bound_used(&self) -> Result<(), Vec<String>> {
-        let mut errs = vec![];
-
-        let ftts = self.free_truth_tables();
-        let mut bound_solvable_exprs = HashMap::new();
-        for (i, ftt) in ftts.iter().enumerate() {
-            for tt in ftt {
-                let btt = self.bound_truth_table(&tt.result);
-                let btt_true: Vec<&TruthTable> =
-                    btt.iter().filter(|tt| tt.result != Expr::False).collect();
-                if btt_true.len() == 0 {
-                    errs.push(format!(
-                        "{:?} has no solution when {:?}",
-                        tt.result, tt.free_map
-                    ))
-                } else {
-                    bound_solvable_exprs
-                        .entry(&tt.free_map)
-                        .or_insert(HashSet::new())
-                        .insert(&tt.result);
-                }
-            }
-            for (free_map, bse) in &bound_solvable_exprs {
-                let all_solvable_expr = bse
-                    .iter()
-                    .map(|a| (*a).clone())
-                    .collect::<HashSet<Expr>>()
-                    .iter()
-                    .map(|a| (*a).clone())
-                    .fold_first(|a, b| Expr::And(Box::new(a), Box::new(b)).simplify())
-                    .unwrap();
-                let all_btt = self.bound_truth_table(&all_solvable_expr);
-                println!();
-                println!();
-                println!();
-                println!("free_map: {:?}", free_map);
-                println!("bse: {:?}", bse);
-                println!("all_solvable_expr: {:?}", all_solvable_expr);
-                println!("all_btt: {:?}", all_btt);
-                let bound_solutions: Vec<&TruthTable> = all_btt
-                    .iter()
-                    .filter(|tt| tt.result == Expr::True)
-                    .collect();
-                if bound_solutions.len() == 0 {
-                    let msg = format!(
-                        "{:?} has no solution when {:?}",
-                        all_solvable_expr, free_map
-                    );
-                    println!("{}", msg);
-                    errs.push(msg);
-                } else if bound_solutions.len() != 1 {
-                    let msg = format!(
-                        "{:?}/{:?} has multiple solutions: {:?}",
-                        all_solvable_expr,
-                        free_map,
-                        bound_solutions.iter().map(|tt| &tt.free_map).collect(): Vec<&VarMap>
-                    );
-                    println!("{}", msg);
-                    errs.push(msg);
-                }
-                let bound = self.bound_vars_in_expr(&all_solvable_expr);
+impl Constraints {
+    pub fn bound_ids(&self) -> impl Iterator<Item = VarId> + '_ {
+        self.bound.iter().map(|b| b.id)
+    }
 
-                let unused_bound: Vec<&str> = self
-                    .bound
-                    .iter()
-                    .filter(|b| !bound.contains(b))
-                    .map(|b| *b)
-                    .collect();
-                if unused_bound.len() != 0 {
-                    errs.push(format!(
-                        "Bound variables {:?} are never used in any constraint when {:?}",
-                        unused_bound, free_map,
-                    ));
-                }
-            }
-        }
-        let all_expr = self
+    pub fn is_bound(&self, id: VarId) -> bool {
+        self.bound.iter().any(|b| b.id == id)
+    }
+
+    /// The variables the clauses use that aren't bound, in ID order.
+    pub fn free_vars(&self) -> Vec<VarId> {
+        let mut vars: Vec<VarId> = self
             .exprs
             .iter()
-            .map(|a| (*a).clone())
-            .fold_first(|a, b| Expr::And(Box::new(a), Box::new(b)).simplify())
-            .unwrap();
-        let all_ftt = self.free_truth_table(&all_expr);
-        let bound = self.bound_vars_in_expr(&all_expr);
-
-        for tt in all_ftt {
-            if tt.result == Expr::True || tt.result == Expr::False {
-                errs.push(format!(
-                    "When {:?} then {} is {}, hence bound vars {:?} have no solution",
-                    tt.free_map, all_expr, tt.result, bound
-                ))
-            }
-        }
+            .flat_map(|e| e.variables())
+            .filter(|v| !self.is_bound(*v))
+            .collect();
+        vars.sort();
+        vars.dedup();
+        vars
+    }
 
-        let unused_bound: Vec<&str> = self
-            .bound
+    /// The clauses `and`ed together.
+    pub fn conjunction(&self) -> Expr {
+        self.exprs
             .iter()
-            .filter(|b| !bound.contains(b))
-            .map(|b| *b)
-            .collect();
+            .cloned()
+            .reduce(|a, b| Expr::binary(ExprKind::And, a, b))
+            .unwrap_or_else(|| Expr::new(ExprKind::True, self.span))
+    }
 
-        if unused_bound.len() != 0 {
-            errs.push(format!(
-                "Bound variables {:?} are never used in any constraint",
-                unused_bound,
-            ));
-        }
+    pub fn bound_vars_in_expr(&self, e: &Expr) -> Vec<VarId> {
+        let vars = e.variables();
+        self.bound_ids().filter(|v| vars.contains(v)).collect()
+    }
 
-        if errs.len() != 0 {
-            return Err(errs);
-        }
+    /// `e` under each assignment to the bound variables it depends on, or
+    /// `None` if there are more than `MAX_TABLE_VARS` of them.
+    pub fn bound_truth_table(&self, e: &Expr) -> Option<Vec<TruthTable>> {
+        self.truth_table(e, |v| self.is_bound(v))
+    }
 
-        Ok(())
+    /// `e` under each assignment to the free variables it depends on, or
+    /// `None` if there are more than `MAX_TABLE_VARS` of them.
+    pub fn free_truth_table(&self, e: &Expr) -> Option<Vec<TruthTable>> {
+        self.truth_table(e, |v| !self.is_bound(v))
     }
-    pub fn bound_vars_in_expr(&self, e: &Expr<'a>) -> Vec<&'a str> {
-        let vars = e.variables();
-        self.bound
+
+    pub fn free_truth_tables(&self) -> Vec<Option<Vec<TruthTable>>> {
+        self.exprs
             .iter()
-            .filter(|v| vars.contains(*v))
-            .map(|v| *v)
+            .map(|e| self.free_truth_table(e))
             .collect()
     }
 
-    pub fn bound_truth_table(&self, e: &Expr<'a>) -> Vec<TruthTable> {
-        let vars = self.bound_vars_in_expr(e);
-        (0..(1 << vars.len()))
+    /// Rows for the variables passing `keep` that `e`'s BDD tests, so ones
+    /// it mentions but doesn't depend on (`x | ~x`) don't double the table.
+    fn truth_table(&self, e: &Expr, keep: impl Fn(VarId) -> bool) -> Option<Vec<TruthTable>> {
+        let mut bdd = Bdd::new();
+        let f = bdd.expr(e);
+        let mut vars: Vec<VarId> = bdd.support(f).into_iter().filter(|v| keep(*v)).collect();
+        if vars.len() > MAX_TABLE_VARS {
+            return None;
+        }
+        vars.sort();
+        let rows = (0..1u32 << vars.len())
             .map(|n| {
-                let mut run = HashMap::<&'a str, bool>::new();
-                for (i, v) in vars.iter().enumerate() {
-                    let p = 1 << i;
-                    run.insert(v, p & n != 0);
-                }
+                let run: HashMap<VarId, bool> = vars
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (*v, n >> i & 1 == 1))
+                    .collect();
                 TruthTable {
-                    free_map: VarMap(run.clone()),
                     result: e.evaluate(&run),
+                    free_map: VarMap(run),
                 }
             })
-            .collect()
-    }
-    pub fn free_truth_table(&self, e: &Expr<'a>) -> Vec<TruthTable> {
-        let vars: Vec<&str> = self
-            .exprs
-            .iter()
-            .map(|e| e.variables())
-            .flatten()
-            .filter(|v| !self.bound.contains(v))
             .collect();
+        Some(rows)
+    }
+}
 
-        (0..(1 << vars.len()))
-            .map(|n| {
-                let mut run = HashMap::<&'a str, bool>::new();
-                for (i, v) in vars.iter().enumerate() {
-                    let p = 1 << i;
-                    run.insert(v, p & n != 0);
-                }
-                TruthTable {
-                    free_map: VarMap(run.clone()),
-                    result: e.evaluate(&run),
-                }
-            })
-            .collect()
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Expr {
+        Expr { kind, span }
     }
 
-    pub fn free_truth_tables(&self) -> Vec<Vec<TruthTable>> {
-        self.exprs
-            .iter()
-            .map(|e| self.free_truth_table(e))
-            .collect()
+    /// `op(l, r)` spanning both operands, e.g. `Expr::binary(ExprKind::And, l, r)`.
+    pub fn binary(op: fn(Box<Expr>, Box<Expr>) -> ExprKind, l: Expr, r: Expr) -> Expr {
+        let span = l.span.to(r.span);
+        Expr::new(op(Box::new(l), Box::new(r)), span)
+    }
+
+    pub fn is_true(&self) -> bool {
+        self.kind == ExprKind::True
+    }
+
+    pub fn is_false(&self) -> bool {
+        self.kind == ExprKind::False
+    }
+
+    /// A node of `kind` carrying this expression's span.
+    fn with_kind(&self, kind: ExprKind) -> Expr {
+        Expr::new(kind, self.span)
+    }
+
+    /// The number of nodes.
+    pub fn size(&self) -> usize {
+        match &self.kind {
+            ExprKind::Term(_) | ExprKind::True | ExprKind::False => 1,
+            ExprKind::Not(a) => 1 + a.size(),
+            ExprKind::And(a, b)
+            | ExprKind::Or(a, b)
+            | ExprKind::Implication(a, b)
+            | ExprKind::Biconditional(a, b)
+            | ExprKind::Xor(a, b) => 1 + a.size() + b.size(),
+        }
     }
-}
 
-impl<'a> Expr<'a> {
-    fn simplify(self) -> Expr<'a> {
-        match self {
-            Expr::Not(box a) => match a {
-                Expr::Not(b) => b.simplify(),
-                Expr::True => Expr::False,
-                Expr::False => Expr::True,
-                _ => Expr::Not(Box::new(a.simplify())),
+    /// `~self`, without stacking negations or negating constants.
+    fn negated(self) -> Expr {
+        let span = self.span;
+        match self.kind {
+            ExprKind::Not(box a) => a,
+            ExprKind::True => Expr::new(ExprKind::False, span),
+            ExprKind::False => Expr::new(ExprKind::True, span),
+            kind => Expr::new(ExprKind::Not(Box::new(Expr::new(kind, span))), span),
+        }
+    }
+
+    /// Rewrites by identity, annulment, complement, absorption, idempotence,
+    /// De Morgan and constant folding until no rule applies anywhere. Every
+    /// rule makes the expression strictly smaller, which is what guarantees
+    /// this stops; so De Morgan only ever pulls negations out, as in
+    /// `~a & ~b` to `~(a | b)`.
+    pub fn simplify(self) -> Expr {
+        let span = self.span;
+        let simplified = |a: Box<Expr>| Box::new(a.simplify());
+        let kind = match self.kind {
+            ExprKind::Not(a) => ExprKind::Not(simplified(a)),
+            ExprKind::And(a, b) => ExprKind::And(simplified(a), simplified(b)),
+            ExprKind::Or(a, b) => ExprKind::Or(simplified(a), simplified(b)),
+            ExprKind::Implication(a, b) => ExprKind::Implication(simplified(a), simplified(b)),
+            ExprKind::Biconditional(a, b) => ExprKind::Biconditional(simplified(a), simplified(b)),
+            ExprKind::Xor(a, b) => ExprKind::Xor(simplified(a), simplified(b)),
+            kind => kind,
+        };
+        let e = Expr::new(kind, span);
+        match e.rewrite() {
+            Some(r) => {
+                debug_assert!(r.size() < e.size(), "{:?} grew to {:?}", e, r);
+                r.simplify()
+            }
+            None => e,
+        }
+    }
+
+    /// The first rule that applies at the root, whose operands are already
+    /// simplified.
+    fn rewrite(&self) -> Option<Expr> {
+        let inner = |e: &Expr| match &e.kind {
+            ExprKind::Not(a) => Some((**a).clone()),
+            _ => None,
+        };
+        // Whether `a` is `~b` or `b` is `~a`.
+        let complements =
+            |a: &Expr, b: &Expr| inner(a).as_ref() == Some(b) || inner(b).as_ref() == Some(a);
+        let node = |kind: ExprKind| Some(self.with_kind(kind));
+        let boxed = |e: Expr| Box::new(e);
+
+        match &self.kind {
+            ExprKind::Term(_) | ExprKind::True | ExprKind::False => None,
+            ExprKind::Not(a) => match &a.kind {
+                ExprKind::Not(_) | ExprKind::True | ExprKind::False => {
+                    Some((**a).clone().negated())
+                }
+                ExprKind::Xor(b, c) => node(ExprKind::Biconditional(b.clone(), c.clone())),
+                ExprKind::Biconditional(b, c) => node(ExprKind::Xor(b.clone(), c.clone())),
+                // ~(~b & c) is b | ~c, and so on.
+                ExprKind::And(b, c) | ExprKind::Or(b, c)
+                    if inner(b).is_some() || inner(c).is_some() =>
+                {
+                    let (b, c) = (
+                        boxed((**b).clone().negated()),
+                        boxed((**c).clone().negated()),
+                    );
+                    node(match a.kind {
+                        ExprKind::And(..) => ExprKind::Or(b, c),
+                        _ => ExprKind::And(b, c),
+                    })
+                }
+                _ => None,
             },
-            Expr::And(box a, box b) => match (a, b) {
-                (Expr::True, c) | (c, Expr::True) => c,
-                (Expr::False, _) | (_, Expr::False) => Expr::False,
-                (c, d) => Expr::And(Box::new(c), Box::new(d)),
+            ExprKind::And(a, b) | ExprKind::Or(a, b) => {
+                let and = matches!(self.kind, ExprKind::And(..));
+                // The value that decides the operation, and the one that
+                // leaves the other operand as it is.
+                let (zero, unit) = match and {
+                    true => (ExprKind::False, ExprKind::True),
+                    false => (ExprKind::True, ExprKind::False),
+                };
+                // `a` absorbs `b` if `b` is `a` in the dual operation, as in
+                // `a & (a | c)`.
+                let absorbs = |a: &Expr, b: &Expr| match (&b.kind, and) {
+                    (ExprKind::Or(c, d), true) | (ExprKind::And(c, d), false) => {
+                        **c == *a || **d == *a
+                    }
+                    _ => false,
+                };
+                // `a & (~a | c)` is `a & c`.
+                let without_complement = |a: &Expr, b: &Expr| match (&b.kind, and) {
+                    (ExprKind::Or(c, d), true) | (ExprKind::And(c, d), false) => {
+                        if complements(a, c) {
+                            Some((**d).clone())
+                        } else if complements(a, d) {
+                            Some((**c).clone())
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None,
+                };
+                let rebuilt = |a: &Expr, b: Expr| {
+                    let (a, b) = (boxed(a.clone()), boxed(b));
+                    node(if and {
+                        ExprKind::And(a, b)
+                    } else {
+                        ExprKind::Or(a, b)
+                    })
+                };
+                if a.kind == zero || b.kind == zero || complements(a, b) {
+                    node(zero)
+                } else if a.kind == unit || absorbs(b, a) {
+                    Some((**b).clone())
+                } else if b.kind == unit || a == b || absorbs(a, b) {
+                    Some((**a).clone())
+                } else if let Some(c) = without_complement(a, b) {
+                    rebuilt(a, c)
+                } else if let Some(c) = without_complement(b, a) {
+                    rebuilt(&c, (**b).clone())
+                } else if let (Some(c), Some(d)) = (inner(a), inner(b)) {
+                    let (c, d) = (boxed(c), boxed(d));
+                    let dual = if and {
+                        ExprKind::Or(c, d)
+                    } else {
+                        ExprKind::And(c, d)
+                    };
+                    node(ExprKind::Not(boxed(self.with_kind(dual))))
+                } else {
+                    None
+                }
+            }
+            ExprKind::Implication(a, b) => match (&a.kind, &b.kind) {
+                (ExprKind::False, _) | (_, ExprKind::True) => node(ExprKind::True),
+                (ExprKind::True, _) => Some((**b).clone()),
+                (_, ExprKind::False) => Some((**a).clone().negated()),
+                _ if a == b => node(ExprKind::True),
+                // ~b -> b and a -> ~a are b and ~a.
+                _ if complements(a, b) => Some((**b).clone()),
+                _ => match (inner(a), inner(b)) {
+                    (Some(c), Some(d)) => node(ExprKind::Implication(boxed(d), boxed(c))),
+                    _ => None,
+                },
             },
-            _ => self,
+            ExprKind::Biconditional(a, b) | ExprKind::Xor(a, b) => {
+                let equal = matches!(self.kind, ExprKind::Biconditional(..));
+                // `a <-> b` is `~(a + b)`: they differ in which constant
+                // leaves the other operand as it is.
+                let (unit, flips) = match equal {
+                    true => (ExprKind::True, ExprKind::False),
+                    false => (ExprKind::False, ExprKind::True),
+                };
+                let rebuilt = |equal: bool, a: Expr, b: Expr| {
+                    let (a, b) = (boxed(a), boxed(b));
+                    node(match equal {
+                        true => ExprKind::Biconditional(a, b),
+                        false => ExprKind::Xor(a, b),
+                    })
+                };
+                if a.kind == unit {
+                    Some((**b).clone())
+                } else if b.kind == unit {
+                    Some((**a).clone())
+                } else if a.kind == flips {
+                    Some((**b).clone().negated())
+                } else if b.kind == flips {
+                    Some((**a).clone().negated())
+                } else if a == b {
+                    node(if equal {
+                        ExprKind::True
+                    } else {
+                        ExprKind::False
+                    })
+                } else if complements(a, b) {
+                    node(if equal {
+                        ExprKind::False
+                    } else {
+                        ExprKind::True
+                    })
+                } else {
+                    match (inner(a), inner(b)) {
+                        (Some(c), Some(d)) => rebuilt(equal, c, d),
+                        (Some(c), None) => rebuilt(!equal, c, (**b).clone()),
+                        (None, Some(d)) => rebuilt(!equal, (**a).clone(), d),
+                        (None, None) => None,
+                    }
+                }
+            }
         }
     }
 
-    fn pp(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Expr::Term(x) => write!(f, "{}", x),
-            Expr::False => write!(f, "𝔽"),
-            Expr::True => write!(f, "𝕋"),
-            Expr::And(a, b) => write!(f, "({} & {})", a, b),
-            Expr::Or(a, b) => write!(f, "({} | {})", a, b),
-            Expr::Implication(a, b) => write!(f, "({} -> {})", a, b),
-            Expr::Biconditional(a, b) => write!(f, "({} <-> {})", a, b),
-            Expr::Xor(a, b) => write!(f, "({} + {})", a, b),
-            Expr::Not(a) => write!(f, "~{}", a),
+    /// Conjunctive normal form by distribution, as a list of clauses. An
+    /// empty list is `true`; an empty clause is `false`. Clauses are
+    /// de-duplicated and tautologies (`x | ~x`) dropped.
+    pub fn cnf(&self) -> Vec<Vec<Literal>> {
+        let mut clauses: Vec<Vec<Literal>> = vec![];
+        for c in self.cnf_clauses(true) {
+            if !clauses.contains(&c) {
+                clauses.push(c);
+            }
         }
+        clauses
     }
-    fn ppf(&self) -> String {
-        match self {
-            Expr::Term(x) => format!("{}", x),
-            Expr::False => format!("𝔽"),
-            Expr::True => format!("𝕋"),
-            Expr::And(a, b) => format!("({} & {})", a, b),
-            Expr::Or(a, b) => format!("({} | {})", a, b),
-            Expr::Implication(a, b) => format!("({} -> {})", a, b),
-            Expr::Biconditional(a, b) => format!("({} <-> {})", a, b),
-            Expr::Xor(a, b) => format!("({} + {})", a, b),
-            Expr::Not(a) => format!("~{}", a),
+
+    /// The CNF of `self` (or of `~self` when `!positive`).
+    fn cnf_clauses(&self, positive: bool) -> Vec<Vec<Literal>> {
+        fn product(a: Vec<Vec<Literal>>, b: Vec<Vec<Literal>>) -> Vec<Vec<Literal>> {
+            let mut out = vec![];
+            for x in &a {
+                for y in &b {
+                    let mut c = x.clone();
+                    for l in y {
+                        if !c.contains(l) {
+                            c.push(*l);
+                        }
+                    }
+                    if !c.iter().any(|(n, p)| c.contains(&(*n, !p))) {
+                        out.push(c);
+                    }
+                }
+            }
+            out
+        }
+        fn concat(mut a: Vec<Vec<Literal>>, mut b: Vec<Vec<Literal>>) -> Vec<Vec<Literal>> {
+            a.append(&mut b);
+            a
+        }
+
+        match &self.kind {
+            ExprKind::Term(x) => vec![vec![(*x, positive)]],
+            ExprKind::True if positive => vec![],
+            ExprKind::True => vec![vec![]],
+            ExprKind::False if positive => vec![vec![]],
+            ExprKind::False => vec![],
+            ExprKind::Not(a) => a.cnf_clauses(!positive),
+            ExprKind::And(a, b) if positive => concat(a.cnf_clauses(true), b.cnf_clauses(true)),
+            ExprKind::And(a, b) => product(a.cnf_clauses(false), b.cnf_clauses(false)),
+            ExprKind::Or(a, b) if positive => product(a.cnf_clauses(true), b.cnf_clauses(true)),
+            ExprKind::Or(a, b) => concat(a.cnf_clauses(false), b.cnf_clauses(false)),
+            ExprKind::Implication(a, b) if positive => {
+                product(a.cnf_clauses(false), b.cnf_clauses(true))
+            }
+            ExprKind::Implication(a, b) => concat(a.cnf_clauses(true), b.cnf_clauses(false)),
+            // a <-> b is (~a | b) & (a | ~b); a + b is its negation.
+            ExprKind::Biconditional(a, b) | ExprKind::Xor(a, b) => {
+                let equal = positive == matches!(self.kind, ExprKind::Biconditional(..));
+                concat(
+                    product(a.cnf_clauses(false), b.cnf_clauses(equal)),
+                    product(a.cnf_clauses(true), b.cnf_clauses(!equal)),
+                )
+            }
         }
     }
-    pub fn contains(&self, t: TermName<'a>) -> bool {
-        match self {
-            Expr::Term(x) => *x == t,
-            Expr::False => false,
-            Expr::True => false,
-            Expr::And(a, b)
-            | Expr::Or(a, b)
-            | Expr::Implication(a, b)
-            | Expr::Biconditional(a, b)
-            | Expr::Xor(a, b) => a.contains(t) | b.contains(t),
-            Expr::Not(a) => a.contains(t),
+
+    fn pp(&self, symbols: Option<&SymbolTable>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let binary = |f: &mut fmt::Formatter<'_>, a: &Expr, op: &str, b: &Expr| {
+            write!(f, "(")?;
+            a.pp(symbols, f)?;
+            write!(f, " {} ", op)?;
+            b.pp(symbols, f)?;
+            write!(f, ")")
+        };
+        match &self.kind {
+            ExprKind::Term(x) => match symbols {
+                Some(symbols) => write!(f, "{}", symbols.name(*x)),
+                None => write!(f, "{:?}", x),
+            },
+            ExprKind::False => write!(f, "𝔽"),
+            ExprKind::True => write!(f, "𝕋"),
+            ExprKind::And(a, b) => binary(f, a, "&", b),
+            ExprKind::Or(a, b) => binary(f, a, "|", b),
+            ExprKind::Implication(a, b) => binary(f, a, "->", b),
+            ExprKind::Biconditional(a, b) => binary(f, a, "<->", b),
+            ExprKind::Xor(a, b) => binary(f, a, "+", b),
+            ExprKind::Not(a) => {
+                write!(f, "~")?;
+                a.pp(symbols, f)
+            }
+        }
+    }
+    pub fn contains(&self, t: TermName) -> bool {
+        match &self.kind {
+            ExprKind::Term(x) => *x == t,
+            ExprKind::False => false,
+            ExprKind::True => false,
+            ExprKind::And(a, b)
+            | ExprKind::Or(a, b)
+            | ExprKind::Implication(a, b)
+            | ExprKind::Biconditional(a, b)
+            | ExprKind::Xor(a, b) => a.contains(t) | b.contains(t),
+            ExprKind::Not(a) => a.contains(t),
         }
     }
 
-    fn _variables(&self) -> Vec<&'a str> {
-        match self {
-            Expr::Term(a) => vec![a],
-            Expr::False => vec![],
-            Expr::True => vec![],
-            Expr::And(a, b)
-            | Expr::Or(a, b)
-            | Expr::Implication(a, b)
-            | Expr::Biconditional(a, b)
-            | Expr::Xor(a, b) => {
+    fn _variables(&self) -> Vec<VarId> {
+        match &self.kind {
+            ExprKind::Term(a) => vec![*a],
+            ExprKind::False => vec![],
+            ExprKind::True => vec![],
+            ExprKind::And(a, b)
+            | ExprKind::Or(a, b)
+            | ExprKind::Implication(a, b)
+            | ExprKind::Biconditional(a, b)
+            | ExprKind::Xor(a, b) => {
                 let mut x = a._variables();
                 x.append(&mut b._variables());
                 x
             }
-            Expr::Not(a) => a._variables(),
+            ExprKind::Not(a) => a._variables(),
+        }
+    }
+
+    /// Every variable reference, in source order, with its span.
+    pub fn idents(&self) -> Vec<Ident> {
+        match &self.kind {
+            ExprKind::Term(id) => vec![Ident {
+                id: *id,
+                span: self.span,
+            }],
+            ExprKind::False | ExprKind::True => vec![],
+            ExprKind::And(a, b)
+            | ExprKind::Or(a, b)
+            | ExprKind::Implication(a, b)
+            | ExprKind::Biconditional(a, b)
+            | ExprKind::Xor(a, b) => {
+                let mut x = a.idents();
+                x.append(&mut b.idents());
+                x
+            }
+            ExprKind::Not(a) => a.idents(),
         }
     }
 
-    pub fn variables(&self) -> HashSet<&'a str> {
+    pub fn variables(&self) -> HashSet<VarId> {
         let mut x = HashSet::new();
         self._variables().iter().for_each(|v| {
             x.insert(*v);
@@ -332,76 +694,82 @@ impl<'a> Expr<'a> {
         x
     }
 
-    pub fn evaluate(&self, m: &HashMap<TermName<'a>, bool>) -> Expr<'a> {
-        match self {
-            Expr::False => Expr::False,
-            Expr::True => Expr::True,
-            Expr::Term(x) => match m.get(x) {
+    pub fn evaluate(&self, m: &HashMap<TermName, bool>) -> Expr {
+        match &self.kind {
+            ExprKind::False => self.with_kind(ExprKind::False),
+            ExprKind::True => self.with_kind(ExprKind::True),
+            ExprKind::Term(x) => match m.get(x) {
                 Some(v) => match v {
-                    true => Expr::True,
-                    false => Expr::False,
+                    true => self.with_kind(ExprKind::True),
+                    false => self.with_kind(ExprKind::False),
                 },
-                None => Expr::Term(x),
+                None => self.with_kind(ExprKind::Term(*x)),
             },
-            Expr::And(a, b) => {
+            ExprKind::And(a, b) => {
                 let a = a.evaluate(m);
                 let b = b.evaluate(m);
 
-                match (a, b) {
-                    (Expr::False, _) | (_, Expr::False) => Expr::False,
-                    (Expr::True, Expr::True) => Expr::True,
-                    (Expr::True, x) | (x, Expr::True) => x,
-                    (x, y) => Expr::And(Box::new(x), Box::new(y)),
+                match (&a.kind, &b.kind) {
+                    (ExprKind::False, _) | (_, ExprKind::False) => self.with_kind(ExprKind::False),
+                    (ExprKind::True, ExprKind::True) => self.with_kind(ExprKind::True),
+                    (ExprKind::True, _) => b,
+                    (_, ExprKind::True) => a,
+                    _ => self.with_kind(ExprKind::And(Box::new(a), Box::new(b))),
                 }
             }
-            Expr::Or(a, b) => {
+            ExprKind::Or(a, b) => {
                 let a = a.evaluate(m);
                 let b = b.evaluate(m);
 
-                match (a, b) {
-                    (Expr::True, _) | (_, Expr::True) => Expr::True,
-                    (Expr::False, x) | (x, Expr::False) => x,
-                    (x, y) => Expr::Or(Box::new(x), Box::new(y)),
+                match (&a.kind, &b.kind) {
+                    (ExprKind::True, _) | (_, ExprKind::True) => self.with_kind(ExprKind::True),
+                    (ExprKind::False, _) => b,
+                    (_, ExprKind::False) => a,
+                    _ => self.with_kind(ExprKind::Or(Box::new(a), Box::new(b))),
                 }
             }
-            Expr::Xor(a, b) => {
+            ExprKind::Xor(a, b) => {
                 let a = a.evaluate(m);
                 let b = b.evaluate(m);
 
-                match (a, b) {
-                    (Expr::True, Expr::True) => Expr::False,
-                    (Expr::False, x) | (x, Expr::False) => x,
-                    (Expr::True, x) | (x, Expr::True) => Expr::Not(Box::new(x)).simplify(),
-                    (x, y) => Expr::Xor(Box::new(x), Box::new(y)),
+                match (&a.kind, &b.kind) {
+                    (ExprKind::True, ExprKind::True) => self.with_kind(ExprKind::False),
+                    (ExprKind::False, _) => b,
+                    (_, ExprKind::False) => a,
+                    (ExprKind::True, _) => b.negated(),
+                    (_, ExprKind::True) => a.negated(),
+                    _ => self.with_kind(ExprKind::Xor(Box::new(a), Box::new(b))),
                 }
             }
-            Expr::Implication(a, b) => {
+            ExprKind::Implication(a, b) => {
                 let a = a.evaluate(m);
                 let b = b.evaluate(m);
 
-                match (a, b) {
-                    (Expr::False, _) | (_, Expr::True) => Expr::True,
-                    (Expr::True, a) => a,
-                    (x, y) => Expr::Implication(Box::new(x), Box::new(y)),
+                match (&a.kind, &b.kind) {
+                    (ExprKind::False, _) | (_, ExprKind::True) => self.with_kind(ExprKind::True),
+                    (ExprKind::True, _) => b,
+                    _ => self.with_kind(ExprKind::Implication(Box::new(a), Box::new(b))),
                 }
             }
-            Expr::Biconditional(a, b) => {
+            ExprKind::Biconditional(a, b) => {
                 let a = a.evaluate(m);
                 let b = b.evaluate(m);
 
-                match (a, b) {
-                    (Expr::True, x) | (x, Expr::True) => x,
-                    (Expr::False, x) | (x, Expr::False) => Expr::Not(Box::new(x)).simplify(),
-                    (x, y) => Expr::Biconditional(Box::new(x), Box::new(y)),
+                match (&a.kind, &b.kind) {
+                    (ExprKind::True, _) => b,
+                    (_, ExprKind::True) => a,
+                    (ExprKind::False, _) => b.negated(),
+                    (_, ExprKind::False) => a.negated(),
+                    _ => self.with_kind(ExprKind::Biconditional(Box::new(a), Box::new(b))),
                 }
             }
-            Expr::Not(a) => {
+            ExprKind::Not(a) => {
                 let a = a.evaluate(m);
 
-                match a {
-                    Expr::True => Expr::False,
-                    Expr::False => Expr::True,
-                    x => Expr::Not(Box::new(x)).simplify(),
+                match a.kind {
+                    ExprKind::True => self.with_kind(ExprKind::False),
+                    ExprKind::False => self.with_kind(ExprKind::True),
+                    _ => a.negated(),
                 }
             }
         }
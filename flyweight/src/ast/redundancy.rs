@@ -0,0 +1,132 @@
+//! Clauses a constraint block could do without: those implied by the rest
+//! of the block, and those that say the same as another.
+//!
+//! Both are warnings. A block that grew by copying rules around can hide
+//! which of them actually constrain anything, and a clause that follows
+//! from the others is one that can be deleted, or one whose intended
+//! meaning got lost.
+
+use crate::ast::nodes::{Constraints, Expr};
+use crate::ast::span::Span;
+use crate::ast::symbols::SymbolTable;
+use crate::bl::sat::{Lit, Solver};
+use crate::diagnostics::{Report, Severity};
+use crate::format::format_expr;
+use std::collections::HashMap;
+
+/// A clause, by its index in `Constraints::exprs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    pub index: usize,
+    pub span: Span,
+    pub expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redundancy {
+    /// `clause` holds whenever all of `by` do; a minimal set of other
+    /// clauses. If `by` is empty it always holds.
+    Implied { clause: Clause, by: Vec<Clause> },
+    /// `second` holds exactly when `first`, an earlier clause, does.
+    Equivalent { first: Clause, second: Clause },
+}
+
+impl Redundancy {
+    pub fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// The clause that could be removed.
+    pub fn span(&self) -> Span {
+        match self {
+            Redundancy::Implied { clause, .. } => clause.span,
+            Redundancy::Equivalent { second, .. } => second.span,
+        }
+    }
+
+    pub fn report(&self, symbols: &SymbolTable) -> Report {
+        let quoted = |c: &Clause| format!("`{}`", format_expr(&c.expr, symbols));
+        let report = match self {
+            Redundancy::Implied { clause, by } if by.is_empty() => {
+                Report::warning("constraint is always true").with_label(clause.span, "")
+            }
+            Redundancy::Implied { clause, by } => {
+                let names: Vec<String> = by.iter().map(quoted).collect();
+                by.iter()
+                    .fold(
+                        Report::warning("constraint is implied by the others")
+                            .with_label(clause.span, "redundant"),
+                        |r, c| r.with_secondary_label(c.span, ""),
+                    )
+                    .with_note(format!("it follows from {}", names.join(" and ")))
+            }
+            Redundancy::Equivalent { first, second } => {
+                Report::warning("constraint is equivalent to an earlier one")
+                    .with_label(second.span, "redundant")
+                    .with_secondary_label(first.span, "says the same")
+                    .with_note(format!("{} and {}", quoted(first), quoted(second)))
+            }
+        };
+        Report {
+            severity: self.severity(),
+            ..report
+        }
+    }
+}
+
+impl Constraints {
+    /// Every clause the block could do without, in source order. Blocks
+    /// without a solution imply everything, so nothing is reported for them.
+    pub fn redundancies(&self) -> Vec<Redundancy> {
+        let mut solver = Solver::new();
+        let mut vars = HashMap::new();
+        let lits: Vec<Lit> = self
+            .exprs
+            .iter()
+            .map(|e| solver.expr(e, &mut vars))
+            .collect();
+        if !solver.solve_with(&lits) {
+            return vec![];
+        }
+        let clause = |i: usize| Clause {
+            index: i,
+            span: self.exprs[i].span,
+            expr: self.exprs[i].clone(),
+        };
+
+        // `equivalent[j]`: the first earlier clause that says the same as
+        // clause `j`.
+        let mut equivalent: Vec<Option<usize>> = vec![None; lits.len()];
+        for j in 0..lits.len() {
+            equivalent[j] = (0..j).find(|i| {
+                equivalent[*i].is_none()
+                    && !solver.solve_with(&[lits[*i], !lits[j]])
+                    && !solver.solve_with(&[!lits[*i], lits[j]])
+            });
+        }
+
+        let mut found = vec![];
+        for (i, lit) in lits.iter().enumerate() {
+            if let Some(first) = equivalent[i] {
+                found.push(Redundancy::Equivalent {
+                    first: clause(first),
+                    second: clause(i),
+                });
+                continue;
+            }
+            // A clause is trivially implied by its equivalents, which are
+            // reported as such, so it must follow without them.
+            let others: Vec<usize> = (0..lits.len())
+                .filter(|j| *j != i && equivalent[*j] != Some(i))
+                .collect();
+            let others_lits: Vec<Lit> = others.iter().map(|j| lits[*j]).collect();
+            if let Some(by) = solver.minimize(&[!*lit], &others_lits) {
+                found.push(Redundancy::Implied {
+                    clause: clause(i),
+                    by: by.iter().map(|k| clause(others[*k])).collect(),
+                });
+            }
+        }
+        found
+    }
+}
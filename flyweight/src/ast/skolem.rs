@@ -0,0 +1,195 @@
+//! Each bound variable as a closed-form function of the free variables.
+//!
+//! Once `assert_bound_used` has shown every input has exactly one
+//! solution, each bound variable is a Boolean function of the inputs, a
+//! Skolem function, and generated code can compute it with straight-line
+//! assignments instead of a solver.
+//!
+//! A function is found by first narrowing down the inputs it depends on,
+//! then asking the solver for its value under each assignment of those,
+//! and minimizing the resulting truth table with Quine–McCluskey. The
+//! table is exponential in the number of inputs, so a function of more
+//! than `MAX_INPUTS` of them is an error.
+
+use crate::ast::nodes::{Constraints, Ident};
+use crate::ast::solvability::Diagnostic;
+use crate::ast::symbols::{SymbolTable, VarId};
+use crate::bl::logic::Expr;
+use crate::bl::sat::{Lit, Solver};
+use crate::diagnostics::Report;
+use mckluskey::mccluskey::{self, BoolOrDontCare};
+use std::collections::HashMap;
+
+/// The most inputs a synthesized function may depend on.
+pub const MAX_INPUTS: usize = 16;
+
+/// `var` as a function of `inputs`, in ID order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skolem {
+    pub var: Ident,
+    pub inputs: Vec<VarId>,
+    pub function: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkolemError {
+    /// The block doesn't give its bound variables exactly one value, so
+    /// they aren't functions of the inputs.
+    Unsolvable(Vec<Diagnostic>),
+    /// `var` depends on more than `MAX_INPUTS` inputs.
+    TooManyInputs { var: Ident },
+}
+
+impl SkolemError {
+    pub fn reports(&self, symbols: &SymbolTable) -> Vec<Report> {
+        match self {
+            SkolemError::Unsolvable(errs) => errs.iter().map(|d| d.report(symbols)).collect(),
+            SkolemError::TooManyInputs { var } => vec![Report::error(format!(
+                "`{}` depends on more than {} inputs, too many to find its function",
+                symbols.name(var.id),
+                MAX_INPUTS
+            ))
+            .with_label(var.span, "")],
+        }
+    }
+}
+
+/// The block twice over, the copies' free variables tied together by
+/// `same` unless one is left out of the assumptions.
+struct Copies {
+    solver: Solver,
+    free: [Vec<Lit>; 2],
+    bound: [Vec<Lit>; 2],
+    all: [Lit; 2],
+    same: Vec<Lit>,
+    /// Whether each bound variable differs between the copies.
+    differ: Vec<Lit>,
+}
+
+impl Copies {
+    fn new(c: &Constraints, free: &[VarId]) -> Copies {
+        let mut solver = Solver::new();
+        let mut copy = || {
+            let mut vars: HashMap<VarId, Lit> = HashMap::new();
+            let mut new_vars = |ids: &mut dyn Iterator<Item = VarId>| -> Vec<Lit> {
+                ids.map(|v| *vars.entry(v).or_insert_with(|| solver.new_var().lit(true)))
+                    .collect()
+            };
+            let free = new_vars(&mut free.iter().copied());
+            let bound = new_vars(&mut c.bound_ids());
+            let clauses: Vec<Lit> = c.exprs.iter().map(|e| solver.expr(e, &mut vars)).collect();
+            let all = solver.and(&clauses);
+            (free, bound, all)
+        };
+        let (free0, bound0, all0) = copy();
+        let (free1, bound1, all1) = copy();
+        let same = free0
+            .iter()
+            .zip(&free1)
+            .map(|(a, b)| !solver.xor(*a, *b))
+            .collect();
+        let differ = bound0
+            .iter()
+            .zip(&bound1)
+            .map(|(a, b)| solver.xor(*a, *b))
+            .collect();
+        Copies {
+            solver,
+            free: [free0, free1],
+            bound: [bound0, bound1],
+            all: [all0, all1],
+            same,
+            differ,
+        }
+    }
+
+    /// Whether bound variable `k` can change when only input `i` does.
+    fn depends(&mut self, k: usize, i: usize) -> bool {
+        let mut assumptions = vec![self.all[0], self.all[1], self.differ[k]];
+        assumptions.extend(
+            self.same
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, s)| *s),
+        );
+        self.solver.solve_with(&assumptions)
+    }
+
+    /// The value of bound variable `k` when the inputs at `inputs` are
+    /// bits of `m`.
+    fn value(&mut self, k: usize, inputs: &[usize], m: usize) -> bool {
+        let mut assumptions = vec![self.all[0]];
+        assumptions.extend(inputs.iter().enumerate().map(|(bit, i)| {
+            let l = self.free[0][*i];
+            if m >> bit & 1 == 1 {
+                l
+            } else {
+                !l
+            }
+        }));
+        let solved = self.solver.solve_with(&assumptions);
+        debug_assert!(solved);
+        self.solver.model_value(self.bound[0][k])
+    }
+}
+
+impl Constraints {
+    /// Each bound variable as a minimized function of the inputs it
+    /// depends on, in declaration order.
+    pub fn skolem_functions(&self) -> Result<Vec<Skolem>, SkolemError> {
+        self.assert_bound_used().map_err(SkolemError::Unsolvable)?;
+        let free = self.free_vars();
+        let mut copies = Copies::new(self, &free);
+
+        let mut found = vec![];
+        for (k, var) in self.bound.iter().enumerate() {
+            let inputs: Vec<usize> = (0..free.len())
+                .filter(|i| copies.depends(k, *i))
+                .take(MAX_INPUTS + 1)
+                .collect();
+            if inputs.len() > MAX_INPUTS {
+                return Err(SkolemError::TooManyInputs { var: *var });
+            }
+            let ids: Vec<VarId> = inputs.iter().map(|i| free[*i]).collect();
+            let ones: Vec<usize> = (0..1 << inputs.len())
+                .filter(|m| copies.value(k, &inputs, *m))
+                .collect();
+            let cover = mccluskey::minimize(inputs.len(), &ones, &[]);
+            found.push(Skolem {
+                var: *var,
+                function: sum_of_products(&cover, &ids).canonical(),
+                inputs: ids,
+            });
+        }
+        Ok(found)
+    }
+}
+
+/// The `or` of the implicants of `cover`, variable `i` being `vars[i]`.
+fn sum_of_products(cover: &[Vec<BoolOrDontCare>], vars: &[VarId]) -> Expr {
+    let mut products: Vec<Box<Expr>> = cover
+        .iter()
+        .map(|implicant| {
+            let mut literals: Vec<Box<Expr>> = implicant
+                .iter()
+                .zip(vars)
+                .filter_map(|(value, v)| match value {
+                    BoolOrDontCare::One => Some(Box::new(Expr::Term(*v))),
+                    BoolOrDontCare::Zero => Some(Box::new(Expr::Not(Box::new(Expr::Term(*v))))),
+                    BoolOrDontCare::DontCare => None,
+                })
+                .collect();
+            match literals.len() {
+                0 => Box::new(Expr::True),
+                1 => literals.remove(0),
+                _ => Box::new(Expr::And(literals)),
+            }
+        })
+        .collect();
+    match products.len() {
+        0 => Expr::False,
+        1 => *products.remove(0),
+        _ => Expr::Or(products),
+    }
+}
@@ -0,0 +1,621 @@
+//! Whether a constraint block gives its bound variables exactly one value
+//! for every assignment of its free variables, and uses all of them.
+//!
+//! Every check is a query to one incremental SAT solver that holds the
+//! clauses twice, the two copies sharing the free variables but not the
+//! bound ones. "Every free assignment has a solution" has a quantifier
+//! alternation a single SAT call can't state, so it's decided by refinement
+//! instead: a second solver proposes free assignments, and each one found to
+//! have a solution is widened to every free assignment that same solution
+//! works for, which is then blocked.
+//!
+//! Blocks whose linear part settles everything (see `ast::linear`) are
+//! decided without the solver.
+
+use crate::ast::behavior::Behavior;
+use crate::ast::condense::Condensed;
+use crate::ast::nodes::{Constraints, Expr, Ident, VarMap};
+use crate::ast::span::Span;
+use crate::ast::symbols::{Names, SymbolTable, VarId};
+use crate::bl::sat::{Lit, Solver};
+use crate::diagnostics::{Report, Severity};
+use std::collections::HashMap;
+
+/// The clauses of a constraint block in a solver.
+struct Encoding {
+    solver: Solver,
+    free: Vec<(VarId, Lit)>,
+    bound: Vec<(VarId, Lit)>,
+    /// The second copy of the bound variables.
+    copy: Vec<(VarId, Lit)>,
+    /// Each clause, over the first copy.
+    clauses: Vec<Lit>,
+    /// Every clause, over the first and the second copy.
+    all: Lit,
+    all_copy: Lit,
+}
+
+impl Encoding {
+    fn new(free: &[VarId], bound: &[VarId], exprs: &[Expr]) -> Encoding {
+        let mut solver = Solver::new();
+        let mut new_vars = |vars: &[VarId]| -> Vec<(VarId, Lit)> {
+            vars.iter()
+                .map(|v| (*v, solver.new_var().lit(true)))
+                .collect()
+        };
+        let free = new_vars(free);
+        let bound = new_vars(bound);
+        let copy = new_vars(&bound.iter().map(|(v, _)| *v).collect::<Vec<_>>());
+
+        let mut vars: HashMap<VarId, Lit> = free.iter().chain(&bound).copied().collect();
+        let clauses: Vec<Lit> = exprs.iter().map(|e| solver.expr(e, &mut vars)).collect();
+        let all = solver.and(&clauses);
+        let mut vars: HashMap<VarId, Lit> = free.iter().chain(&copy).copied().collect();
+        let copies: Vec<Lit> = exprs.iter().map(|e| solver.expr(e, &mut vars)).collect();
+        let all_copy = solver.and(&copies);
+        Encoding {
+            solver,
+            free,
+            bound,
+            copy,
+            clauses,
+            all,
+            all_copy,
+        }
+    }
+
+    /// The values `vars` have in the solver's last model.
+    fn model(&self, vars: &[(VarId, Lit)]) -> VarMap {
+        vars.iter()
+            .map(|(v, l)| (*v, self.solver.model_value(*l)))
+            .collect::<HashMap<VarId, bool>>()
+            .into()
+    }
+
+    /// An assignment of the free variables under which `target` holds
+    /// whatever the bound variables of the first copy are.
+    fn forall(&mut self, target: Lit) -> Option<VarMap> {
+        let mut candidates = Solver::new();
+        let chosen: Vec<Lit> = self
+            .free
+            .iter()
+            .map(|_| candidates.new_var().lit(true))
+            .collect();
+        while candidates.solve() {
+            let values: Vec<bool> = chosen.iter().map(|c| candidates.model_value(*c)).collect();
+            let x: Vec<Lit> = self
+                .free
+                .iter()
+                .zip(&values)
+                .map(|((_, l), value)| if *value { *l } else { !*l })
+                .collect();
+            let mut assumptions = x.clone();
+            assumptions.push(!target);
+            if !self.solver.solve_with(&assumptions) {
+                return Some(
+                    self.free
+                        .iter()
+                        .zip(&values)
+                        .map(|((v, _), value)| (*v, *value))
+                        .collect::<HashMap<VarId, bool>>()
+                        .into(),
+                );
+            }
+
+            // Bound values that make `target` false here. The free values
+            // they make it false for whatever the others are are those that
+            // are needed to show it can't hold.
+            let mut assumptions: Vec<Lit> = self
+                .bound
+                .iter()
+                .map(|(_, l)| if self.solver.model_value(*l) { *l } else { !*l })
+                .collect();
+            assumptions.push(target);
+            assumptions.extend(&x);
+            let holds = self.solver.solve_with(&assumptions);
+            debug_assert!(!holds);
+            let core = self.solver.core();
+            let block: Vec<Lit> = x
+                .iter()
+                .zip(&chosen)
+                .filter(|(l, _)| core.contains(l))
+                .map(|(l, c)| c.var().lit(!l.is_positive()))
+                .collect();
+            candidates.add_clause(&block);
+        }
+        None
+    }
+
+    /// Given a free assignment under which the `clauses`, by index, have no
+    /// solution together: as few of them as still have none, and as few of
+    /// the free values as that needs.
+    fn conflict(&mut self, free_map: &VarMap, clauses: &[usize]) -> (Vec<usize>, VarMap) {
+        let cube: Vec<Lit> = self
+            .free
+            .iter()
+            .map(|(v, l)| match free_map.get(*v) {
+                Some(true) => *l,
+                _ => !*l,
+            })
+            .collect();
+        let lits: Vec<Lit> = clauses.iter().map(|i| self.clauses[*i]).collect();
+        let set = self
+            .solver
+            .minimize(&cube, &lits)
+            .expect("the clauses have a solution");
+        let lits: Vec<Lit> = set.iter().map(|i| lits[*i]).collect();
+        let needed = self.solver.minimize(&lits, &cube).unwrap();
+        let cube: HashMap<VarId, bool> = needed
+            .iter()
+            .map(|i| (self.free[*i].0, cube[*i].is_positive()))
+            .collect();
+        (set.iter().map(|i| clauses[*i]).collect(), cube.into())
+    }
+
+    /// Two different solutions for the same free assignment, if there are.
+    fn two_solutions(&mut self) -> Option<(VarMap, VarMap, VarMap)> {
+        let differ: Vec<Lit> = self
+            .bound
+            .iter()
+            .zip(&self.copy)
+            .map(|((_, a), (_, b))| (*a, *b))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|(a, b)| self.solver.xor(a, b))
+            .collect();
+        let differ = self.solver.or(&differ);
+        if !self.solver.solve_with(&[self.all, self.all_copy, differ]) {
+            return None;
+        }
+        Some((
+            self.model(&self.free),
+            self.model(&self.bound),
+            self.model(&self.copy),
+        ))
+    }
+
+    /// The variables of `candidates` whose value never changes whether
+    /// `exprs`, the clauses, hold. Flipping a variable in one of the
+    /// `solutions` and seeing a clause fail shows it matters without asking
+    /// the solver.
+    fn unused(
+        &mut self,
+        exprs: &[Expr],
+        candidates: &[VarId],
+        solutions: &[HashMap<VarId, bool>],
+    ) -> Vec<VarId> {
+        let breaks = |v: VarId| {
+            solutions.iter().any(|s| {
+                let mut flipped = s.clone();
+                flipped.insert(v, !s[&v]);
+                exprs.iter().any(|e| e.evaluate(&flipped).is_false())
+            })
+        };
+        let candidates: Vec<VarId> = candidates.iter().copied().filter(|v| !breaks(*v)).collect();
+        if candidates.is_empty() {
+            return vec![];
+        }
+        // `same[i]`, when assumed, makes the copies of bound variable `i`
+        // equal.
+        let pairs: Vec<(Lit, Lit)> = self
+            .bound
+            .iter()
+            .zip(&self.copy)
+            .map(|((_, a), (_, b))| (*a, *b))
+            .collect();
+        let same: Vec<Lit> = pairs
+            .iter()
+            .map(|(a, b)| {
+                let s = self.solver.new_var().lit(true);
+                self.solver.add_clause(&[!s, !*a, *b]);
+                self.solver.add_clause(&[!s, *a, !*b]);
+                s
+            })
+            .collect();
+        let changed = self.solver.xor(self.all, self.all_copy);
+        let mut unused = vec![];
+        for (i, (v, _)) in self.bound.iter().enumerate() {
+            if !candidates.contains(v) {
+                continue;
+            }
+            let mut assumptions: Vec<Lit> = same
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, s)| *s)
+                .collect();
+            assumptions.push(changed);
+            if !self.solver.solve_with(&assumptions) {
+                unused.push(*v);
+            }
+        }
+        unused
+    }
+}
+
+/// A way a constraint block fails to give its bound variables exactly one
+/// value, found by `Constraints::assert_bound_used`. Each carries the free
+/// assignment that shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// The bound variables have no value under `free`, nor under any
+    /// assignment that agrees with it on `cube`. `clauses` is a minimal set
+    /// of clauses that have no solution together, reduced by `cube`; if
+    /// it's a single clause, that clause can't be satisfied on its own.
+    NoSolution {
+        free: VarMap,
+        cube: VarMap,
+        clauses: Vec<ReducedClause>,
+    },
+    /// The bound variables have more than one value under `free`, two of
+    /// which are `solutions`.
+    MultipleSolutions {
+        free: VarMap,
+        solutions: [VarMap; 2],
+        reduced: Expr,
+        span: Span,
+    },
+    /// Bound variables the clauses never depend on.
+    UnusedBound { vars: Vec<Ident> },
+    /// Under `free` the clauses hold whatever the bound variables are, so
+    /// each of `bound` may take any value. Only found alongside
+    /// `MultipleSolutions`.
+    ConstantOutput {
+        free: VarMap,
+        bound: Vec<VarId>,
+        clauses: Expr,
+        span: Span,
+    },
+    /// A bound variable that has `value` in every solution.
+    StuckAt { var: Ident, value: bool },
+}
+
+/// A clause, by its index in `Constraints::exprs`, and what's left of it
+/// once some free variables are substituted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReducedClause {
+    pub index: usize,
+    pub span: Span,
+    pub reduced: Expr,
+}
+
+impl Diagnostic {
+    /// Constant output only explains where multiple solutions come from,
+    /// so it's a note beside that error.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Diagnostic::ConstantOutput { .. } => Severity::Note,
+            _ => Severity::Error,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Diagnostic::NoSolution { clauses, .. } => clauses[0].span,
+            Diagnostic::MultipleSolutions { span, .. }
+            | Diagnostic::ConstantOutput { span, .. } => *span,
+            Diagnostic::UnusedBound { vars } => vars[0].span,
+            Diagnostic::StuckAt { var, .. } => var.span,
+        }
+    }
+
+    /// The free assignment that shows the problem, if it needs one.
+    pub fn witness(&self) -> Option<&VarMap> {
+        match self {
+            Diagnostic::NoSolution { free, .. }
+            | Diagnostic::MultipleSolutions { free, .. }
+            | Diagnostic::ConstantOutput { free, .. } => Some(free),
+            Diagnostic::UnusedBound { .. } | Diagnostic::StuckAt { .. } => None,
+        }
+    }
+
+    pub fn report(&self, symbols: &SymbolTable) -> Report {
+        let report = match self {
+            Diagnostic::NoSolution {
+                free,
+                cube,
+                clauses,
+            } => {
+                let message = match clauses.len() {
+                    1 => "constraint has no solution",
+                    _ => "constraints have no solution",
+                };
+                // No input is involved when the clauses conflict outright.
+                let message = if cube.assignment().is_empty() {
+                    message.to_string()
+                } else {
+                    format!("{} when {}", message, cube.named(symbols))
+                };
+                let regardless: Vec<&str> = free
+                    .assignment()
+                    .iter()
+                    .filter(|(v, _)| cube.get(*v).is_none())
+                    .map(|(v, _)| symbols.name(*v))
+                    .collect();
+                clauses
+                    .iter()
+                    .fold(Report::error(message), |r, c| {
+                        r.with_label(c.span, format!("reduces to {}", c.reduced.named(symbols)))
+                    })
+                    .with_notes(
+                        (!regardless.is_empty())
+                            .then(|| format!("regardless of {}", regardless.join(", "))),
+                    )
+            }
+            Diagnostic::MultipleSolutions {
+                free,
+                solutions,
+                reduced,
+                span,
+            } => Report::error(format!(
+                "constraints have multiple solutions when {}",
+                free.named(symbols)
+            ))
+            .with_label(*span, "")
+            .with_note(format!(
+                "the constraints reduce to {}",
+                reduced.named(symbols)
+            ))
+            .with_notes(
+                solutions
+                    .iter()
+                    .map(|s| format!("one solution is {}", s.named(symbols))),
+            ),
+            Diagnostic::UnusedBound { vars } => vars.iter().fold(
+                Report::error("bound variables are never used in any constraint"),
+                |r, b| r.with_label(b.span, format!("`{}` is never used", symbols.name(b.id))),
+            ),
+            Diagnostic::ConstantOutput {
+                free,
+                bound,
+                clauses,
+                span,
+            } => {
+                let names: Vec<String> = bound
+                    .iter()
+                    .map(|b| format!("`{}`", symbols.name(*b)))
+                    .collect();
+                Report::error(format!(
+                    "when {} the constraints always hold, leaving {} unconstrained",
+                    free.named(symbols),
+                    names.join(", ")
+                ))
+                .with_label(*span, "")
+                .with_note(format!("the constraints are {}", clauses.named(symbols)))
+            }
+            Diagnostic::StuckAt { var, value } => Report::error(format!(
+                "bound variable `{}` is always {}",
+                symbols.name(var.id),
+                value
+            ))
+            .with_label(var.span, "")
+            .with_note(format!(
+                "no assignment of the free variables lets it be {}",
+                !value
+            )),
+        };
+        Report {
+            severity: self.severity(),
+            ..report
+        }
+    }
+}
+
+impl Constraints {
+    /// Proves that for every assignment of the free variables the bound
+    /// variables have exactly one solution, and that every bound variable
+    /// matters. Each kind of failure is reported once, with one assignment
+    /// that shows it, except bound variables stuck at one value, which are
+    /// each reported.
+    pub fn assert_bound_used(&self) -> Result<(), Vec<Diagnostic>> {
+        // Where the linear part settles it, each bound variable has exactly
+        // one value and so matters; it's stuck if its polynomial is constant.
+        if let Some(solution) = self.linear_solution() {
+            let stuck: Vec<Diagnostic> = solution
+                .iter()
+                .filter_map(|(var, p)| {
+                    p.as_constant()
+                        .map(|value| Diagnostic::StuckAt { var: *var, value })
+                })
+                .collect();
+            return if stuck.is_empty() { Ok(()) } else { Err(stuck) };
+        }
+
+        let mut errs = vec![];
+        let free = self.free_vars();
+        let bound: Vec<VarId> = self.bound_ids().collect();
+
+        // A clause that can't be satisfied on its own points straight at the
+        // problem, so those are reported against the clause. Each is checked
+        // in a solver of its own, over only its own variables.
+        for (i, e) in self.exprs.iter().enumerate() {
+            let mut vars: Vec<VarId> = e
+                .variables()
+                .into_iter()
+                .filter(|v| !self.is_bound(*v))
+                .collect();
+            vars.sort();
+            let mut enc =
+                Encoding::new(&vars, &self.bound_vars_in_expr(e), std::slice::from_ref(e));
+            if let Some(clause_map) = enc.forall(!enc.all) {
+                let (_, cube) = enc.conflict(&clause_map, &[0]);
+                // The free variables the clause doesn't use are shown false.
+                let free_map: VarMap = free
+                    .iter()
+                    .map(|v| (*v, clause_map.get(*v).unwrap_or(false)))
+                    .collect::<HashMap<VarId, bool>>()
+                    .into();
+                errs.push(Diagnostic::NoSolution {
+                    free: free_map,
+                    clauses: vec![self.reduced_clause(i, &cube)],
+                    cube,
+                });
+            }
+        }
+
+        let mut enc = Encoding::new(&free, &bound, &self.exprs);
+        if errs.is_empty() {
+            if let Some(free_map) = enc.forall(!enc.all) {
+                let all: Vec<usize> = (0..self.exprs.len()).collect();
+                let (conflicting, cube) = enc.conflict(&free_map, &all);
+                errs.push(Diagnostic::NoSolution {
+                    free: free_map,
+                    clauses: conflicting
+                        .iter()
+                        .map(|i| self.reduced_clause(*i, &cube))
+                        .collect(),
+                    cube,
+                });
+            }
+        }
+
+        let multiple = enc.two_solutions();
+        if let Some((free_map, a, b)) = &multiple {
+            errs.push(Diagnostic::MultipleSolutions {
+                free: free_map.clone(),
+                solutions: [a.clone(), b.clone()],
+                reduced: self.reduced(free_map),
+                span: self.span,
+            });
+        }
+
+        // Constraints that are always true have every solution, so they can
+        // only be where there are several.
+        if multiple.is_some() && !bound.is_empty() {
+            if let Some(free_map) = enc.forall(enc.all) {
+                errs.push(Diagnostic::ConstantOutput {
+                    free: free_map,
+                    bound: bound.clone(),
+                    clauses: self.conjunction().simplify(),
+                    span: self.span,
+                });
+            }
+        }
+
+        // A signal that can never show clear isn't doing anything.
+        errs.extend(
+            self.behaviors(false)
+                .into_iter()
+                .filter_map(|b| match b.behavior {
+                    Behavior::Constant(value) => Some(Diagnostic::StuckAt { var: b.var, value }),
+                    _ => None,
+                }),
+        );
+
+        // A variable no clause mentions is unused without asking.
+        let mentioned: Vec<VarId> = bound
+            .iter()
+            .copied()
+            .filter(|b| self.exprs.iter().any(|e| e.contains(*b)))
+            .collect();
+        let unused = match &multiple {
+            // Where there's a solution, flipping a variable that doesn't
+            // matter gives a second one; where there's none, NoSolution
+            // already says so.
+            None => vec![],
+            Some((free_map, a, b)) => {
+                let solution = |y: &VarMap| -> HashMap<VarId, bool> {
+                    free_map
+                        .values()
+                        .iter()
+                        .chain(y.values())
+                        .map(|(v, value)| (*v, *value))
+                        .collect()
+                };
+                enc.unused(&self.exprs, &mentioned, &[solution(a), solution(b)])
+            }
+        };
+        let unused_bound: Vec<Ident> = self
+            .bound
+            .iter()
+            .filter(|b| !mentioned.contains(&b.id) || unused.contains(&b.id))
+            .copied()
+            .collect();
+        if !unused_bound.is_empty() {
+            errs.push(Diagnostic::UnusedBound { vars: unused_bound });
+        }
+
+        if !errs.is_empty() {
+            return Err(errs);
+        }
+
+        Ok(())
+    }
+
+    /// `assert_bound_used` on the block with its free-only subexpressions
+    /// condensed, when that asks the same question (see
+    /// `Condensed::exact`), with the free assignments that show each
+    /// failure given back in the block's own variables.
+    pub fn assert_bound_used_condensed(
+        &self,
+        symbols: &mut SymbolTable,
+    ) -> Result<(), Vec<Diagnostic>> {
+        let condensed = self.condense(symbols);
+        if condensed.inputs.is_empty() || !condensed.exact() {
+            return self.assert_bound_used();
+        }
+        condensed
+            .constraints
+            .assert_bound_used()
+            .map_err(|errs| errs.into_iter().map(|d| self.lift(&condensed, d)).collect())
+    }
+
+    /// `d`, found in `condensed`, in terms of this block.
+    fn lift(&self, condensed: &Condensed, d: Diagnostic) -> Diagnostic {
+        match d {
+            Diagnostic::NoSolution {
+                free,
+                cube,
+                clauses,
+            } => {
+                let cube = condensed.lift(&cube);
+                Diagnostic::NoSolution {
+                    free: condensed.lift(&free),
+                    clauses: clauses
+                        .iter()
+                        .map(|c| self.reduced_clause(c.index, &cube))
+                        .collect(),
+                    cube,
+                }
+            }
+            Diagnostic::MultipleSolutions {
+                free,
+                solutions,
+                span,
+                ..
+            } => {
+                let free = condensed.lift(&free);
+                Diagnostic::MultipleSolutions {
+                    reduced: self.reduced(&free),
+                    free,
+                    solutions,
+                    span,
+                }
+            }
+            Diagnostic::ConstantOutput {
+                free, bound, span, ..
+            } => Diagnostic::ConstantOutput {
+                free: condensed.lift(&free),
+                bound,
+                clauses: self.conjunction().simplify(),
+                span,
+            },
+            d @ Diagnostic::UnusedBound { .. } | d @ Diagnostic::StuckAt { .. } => d,
+        }
+    }
+
+    fn reduced_clause(&self, index: usize, free_map: &VarMap) -> ReducedClause {
+        let e = &self.exprs[index];
+        ReducedClause {
+            index,
+            span: e.span,
+            reduced: e.evaluate(free_map.values()),
+        }
+    }
+
+    /// The clauses `and`ed together with the free variables of `free_map`
+    /// substituted, simplified.
+    fn reduced(&self, free_map: &VarMap) -> Expr {
+        self.conjunction().evaluate(free_map.values()).simplify()
+    }
+}
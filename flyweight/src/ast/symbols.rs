@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// An interned variable name. IDs are only meaningful with the
+/// `SymbolTable` that issued them.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VarId(u32);
+
+impl VarId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Debug for VarId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Interns variable names. One table is shared by every file compiled
+/// together, so the same name always gets the same `VarId`.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    names: Vec<String>,
+    ids: HashMap<String, VarId>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    pub fn intern(&mut self, name: &str) -> VarId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = VarId(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<VarId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: VarId) -> &str {
+        &self.names[id.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Something that mentions variables, and so needs the `SymbolTable` to be
+/// printed with their names.
+pub trait Names {
+    fn fmt_names(&self, symbols: &SymbolTable, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// `self` ready for `{}`, e.g. `println!("{}", expr.named(&symbols))`.
+    fn named<'s>(&'s self, symbols: &'s SymbolTable) -> Named<'s, Self> {
+        Named {
+            value: self,
+            symbols,
+        }
+    }
+}
+
+pub struct Named<'s, T: ?Sized> {
+    value: &'s T,
+    symbols: &'s SymbolTable,
+}
+
+impl<T: Names + ?Sized> fmt::Display for Named<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt_names(self.symbols, f)
+    }
+}
+
+impl Names for VarId {
+    fn fmt_names(&self, symbols: &SymbolTable, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", symbols.name(*self))
+    }
+}
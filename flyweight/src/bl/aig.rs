@@ -0,0 +1,410 @@
+//! And-inverter graphs: expressions as a shared DAG of two-input `and`s
+//! whose edges may be complemented.
+//!
+//! Nodes are hash-consed, so building the same `and` twice gives back the
+//! same node, and equal subexpressions are stored once however often they
+//! occur. Nodes are only ever appended, and each one's operands come before
+//! it, so the node list is already in topological order.
+//!
+//! Passes don't change a graph in place. `rewrite` and `balance` copy the
+//! part of it the roots reach into a new graph, which also drops nodes
+//! nothing uses any more.
+
+use crate::ast::symbols::VarId;
+use crate::bl::logic::Expr;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::ops::Not;
+
+/// A node, or its complement if the low bit is set. Node 0 is the constant
+/// false, so `Edge::FALSE` and `Edge::TRUE` are its two edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Edge(u32);
+
+impl Edge {
+    pub const FALSE: Edge = Edge(0);
+    pub const TRUE: Edge = Edge(1);
+
+    pub fn new(node: usize, complemented: bool) -> Edge {
+        Edge((node as u32) << 1 | complemented as u32)
+    }
+
+    pub fn node(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn is_complemented(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    /// `self`, complemented if `complemented`.
+    fn xor(self, complemented: bool) -> Edge {
+        Edge(self.0 ^ complemented as u32)
+    }
+}
+
+impl Not for Edge {
+    type Output = Edge;
+
+    fn not(self) -> Edge {
+        Edge(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Node {
+    False,
+    Input(VarId),
+    /// The operands, the smaller edge first.
+    And(Edge, Edge),
+}
+
+#[derive(Debug, Clone)]
+pub struct Aig {
+    nodes: Vec<Node>,
+    /// The longest path from each node to an input.
+    levels: Vec<usize>,
+    ands: HashMap<(Edge, Edge), usize>,
+    inputs: HashMap<VarId, usize>,
+}
+
+/// The size of the part of a graph some roots reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub inputs: usize,
+    pub ands: usize,
+    /// The most `and`s on any path from a root to an input.
+    pub depth: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} inputs, {} ands, depth {}",
+            self.inputs, self.ands, self.depth
+        )
+    }
+}
+
+impl Default for Aig {
+    fn default() -> Aig {
+        Aig::new()
+    }
+}
+
+impl Aig {
+    pub fn new() -> Aig {
+        Aig {
+            nodes: vec![Node::False],
+            levels: vec![0],
+            ands: HashMap::new(),
+            inputs: HashMap::new(),
+        }
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn node(&self, e: Edge) -> Node {
+        self.nodes[e.node()]
+    }
+
+    pub fn level(&self, e: Edge) -> usize {
+        self.levels[e.node()]
+    }
+
+    fn push(&mut self, node: Node, level: usize) -> Edge {
+        self.nodes.push(node);
+        self.levels.push(level);
+        Edge::new(self.nodes.len() - 1, false)
+    }
+
+    pub fn input(&mut self, v: VarId) -> Edge {
+        match self.inputs.get(&v) {
+            Some(n) => Edge::new(*n, false),
+            None => {
+                let e = self.push(Node::Input(v), 0);
+                self.inputs.insert(v, e.node());
+                e
+            }
+        }
+    }
+
+    /// `a & b`, folding constants and operands that are equal or opposite,
+    /// and reusing the node if it's already there.
+    pub fn and(&mut self, a: Edge, b: Edge) -> Edge {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        if a == Edge::FALSE || a == !b {
+            return Edge::FALSE;
+        }
+        if a == Edge::TRUE || a == b {
+            return b;
+        }
+        if let Some(n) = self.ands.get(&(a, b)) {
+            return Edge::new(*n, false);
+        }
+        let level = 1 + self.level(a).max(self.level(b));
+        let e = self.push(Node::And(a, b), level);
+        self.ands.insert((a, b), e.node());
+        e
+    }
+
+    pub fn or(&mut self, a: Edge, b: Edge) -> Edge {
+        !self.and(!a, !b)
+    }
+
+    pub fn xor(&mut self, a: Edge, b: Edge) -> Edge {
+        let (x, y) = (self.and(a, !b), self.and(!a, b));
+        self.or(x, y)
+    }
+
+    /// The `and` of `es` as a tree of the least depth.
+    fn and_all(&mut self, es: Vec<Edge>) -> Edge {
+        let mut heap: BinaryHeap<Reverse<(usize, Edge)>> = es
+            .into_iter()
+            .map(|e| Reverse((self.level(e), e)))
+            .collect();
+        loop {
+            match (heap.pop(), heap.pop()) {
+                (None, _) => return Edge::TRUE,
+                (Some(Reverse((_, e))), None) => return e,
+                (Some(Reverse((_, a))), Some(Reverse((_, b)))) => {
+                    let e = self.and(a, b);
+                    heap.push(Reverse((self.level(e), e)));
+                }
+            }
+        }
+    }
+
+    fn all(&mut self, es: &[Box<Expr>], negated: bool) -> Vec<Edge> {
+        es.iter().map(|e| self.expr(e).xor(negated)).collect()
+    }
+
+    /// The edge computing `e`.
+    pub fn expr(&mut self, e: &Expr) -> Edge {
+        match e {
+            Expr::True => Edge::TRUE,
+            Expr::False => Edge::FALSE,
+            Expr::Term(v) => self.input(*v),
+            Expr::Not(a) => !self.expr(a),
+            Expr::And(es) => {
+                let es = self.all(es, false);
+                self.and_all(es)
+            }
+            Expr::Or(es) => {
+                let es = self.all(es, true);
+                !self.and_all(es)
+            }
+            Expr::Xor(es) => {
+                let es = self.all(es, false);
+                es.into_iter().fold(Edge::FALSE, |a, b| self.xor(a, b))
+            }
+            Expr::Implication(a, b) => {
+                let (a, b) = (self.expr(a), self.expr(b));
+                !self.and(a, !b)
+            }
+            Expr::Biconditional(a, b) => {
+                let (a, b) = (self.expr(a), self.expr(b));
+                !self.xor(a, b)
+            }
+        }
+    }
+
+    /// The values of `roots` with the inputs set by `values`, or `None` if
+    /// one they use isn't there.
+    pub fn evaluate(&self, roots: &[Edge], values: &HashMap<VarId, bool>) -> Option<Vec<bool>> {
+        let last = roots.iter().map(|e| e.node()).max().unwrap_or(0);
+        let mut nodes: Vec<Option<bool>> = Vec::with_capacity(last + 1);
+        let value =
+            |nodes: &[Option<bool>], e: Edge| nodes[e.node()].map(|v| v != e.is_complemented());
+        for node in &self.nodes[..=last] {
+            let v = match node {
+                Node::False => Some(false),
+                Node::Input(v) => values.get(v).copied(),
+                Node::And(a, b) => match (value(&nodes, *a), value(&nodes, *b)) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                },
+            };
+            nodes.push(v);
+        }
+        roots.iter().map(|e| value(&nodes, *e)).collect()
+    }
+
+    /// Whether each node is reached from `roots`.
+    pub(crate) fn cone(&self, roots: &[Edge]) -> Vec<bool> {
+        let mut reached = vec![false; self.nodes.len()];
+        for e in roots {
+            reached[e.node()] = true;
+        }
+        for n in (0..self.nodes.len()).rev() {
+            if let (true, Node::And(a, b)) = (reached[n], self.nodes[n]) {
+                reached[a.node()] = true;
+                reached[b.node()] = true;
+            }
+        }
+        reached
+    }
+
+    pub fn stats(&self, roots: &[Edge]) -> Stats {
+        let mut stats = Stats {
+            inputs: 0,
+            ands: 0,
+            depth: roots.iter().map(|e| self.level(*e)).max().unwrap_or(0),
+        };
+        for (node, reached) in self.nodes.iter().zip(self.cone(roots)) {
+            match node {
+                Node::Input(_) if reached => stats.inputs += 1,
+                Node::And(..) if reached => stats.ands += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+
+    /// The `needed` nodes copied into a new graph in order, each `and`
+    /// built by `build` from the copies so far, and `roots` there.
+    fn copy<F>(&self, roots: &[Edge], needed: &[bool], mut build: F) -> (Aig, Vec<Edge>)
+    where
+        F: FnMut(&mut Aig, &[Edge], usize) -> Edge,
+    {
+        let mut copy = Aig::new();
+        let mut copied = vec![Edge::FALSE; self.nodes.len()];
+        for (n, node) in self.nodes.iter().enumerate() {
+            if needed[n] {
+                copied[n] = match node {
+                    Node::False => Edge::FALSE,
+                    Node::Input(v) => copy.input(*v),
+                    Node::And(..) => build(&mut copy, &copied, n),
+                };
+            }
+        }
+        let roots = roots
+            .iter()
+            .map(|e| copied[e.node()].xor(e.is_complemented()))
+            .collect();
+        (copy, roots)
+    }
+
+    /// The graph rebuilt with two-level rewriting rules, which look at an
+    /// `and` together with the `and`s that are its operands:
+    ///
+    /// - `a & (~a & b)` is false and `a & (a & b)` is `a & b`;
+    /// - `a & ~(~a & b)` is `a`, and `a & ~(a & b)` is `a & ~b`;
+    /// - `(a & b) & (~a & c)` is false;
+    /// - `~(a & b) & ~(a & ~b)` is `~a`.
+    pub fn rewrite(&self, roots: &[Edge]) -> (Aig, Vec<Edge>) {
+        self.copy(roots, &self.cone(roots), |copy, copied, n| {
+            let operand = |e: Edge| copied[e.node()].xor(e.is_complemented());
+            match self.nodes[n] {
+                Node::And(a, b) => copy.and_rewritten(operand(a), operand(b)),
+                _ => unreachable!("only ands are built"),
+            }
+        })
+    }
+
+    fn and_rewritten(&mut self, a: Edge, b: Edge) -> Edge {
+        let operands = |aig: &Aig, e: Edge| match aig.node(e) {
+            Node::And(x, y) => Some((x, y)),
+            _ => None,
+        };
+        for (a, b) in [(a, b), (b, a)] {
+            let (x, y) = match operands(self, b) {
+                Some(xy) => xy,
+                None => continue,
+            };
+            let other = operands(self, a).filter(|_| a.is_complemented() == b.is_complemented());
+            if !b.is_complemented() {
+                if x == !a || y == !a {
+                    return Edge::FALSE;
+                }
+                if x == a || y == a {
+                    return b;
+                }
+                if let Some((z, w)) = other {
+                    if [z, w].iter().any(|c| *c == !x || *c == !y) {
+                        return Edge::FALSE;
+                    }
+                }
+            } else {
+                if x == !a || y == !a {
+                    return a;
+                }
+                if x == a {
+                    return self.and_rewritten(a, !y);
+                }
+                if y == a {
+                    return self.and_rewritten(a, !x);
+                }
+                if let Some((z, w)) = other {
+                    for (s, p) in [(x, y), (y, x)] {
+                        if (z == s && w == !p) || (w == s && z == !p) {
+                            return !s;
+                        }
+                    }
+                }
+            }
+        }
+        self.and(a, b)
+    }
+
+    /// The graph rebuilt with each tree of `and`s regrouped to the least
+    /// depth. A tree stops at complemented edges and at nodes used more
+    /// than once, which would otherwise be copied into every tree using
+    /// them.
+    pub fn balance(&self, roots: &[Edge]) -> (Aig, Vec<Edge>) {
+        let reached = self.cone(roots);
+        let mut fanout = vec![0; self.nodes.len()];
+        for e in roots {
+            fanout[e.node()] += 1;
+        }
+        for (n, node) in self.nodes.iter().enumerate() {
+            if let (true, Node::And(a, b)) = (reached[n], node) {
+                fanout[a.node()] += 1;
+                fanout[b.node()] += 1;
+            }
+        }
+
+        // The leaves of each tree a needed node roots; nodes inside a tree
+        // aren't needed themselves.
+        let mut needed = vec![false; self.nodes.len()];
+        for e in roots {
+            needed[e.node()] = true;
+        }
+        let mut leaves: HashMap<usize, Vec<Edge>> = HashMap::new();
+        for n in (0..self.nodes.len()).rev() {
+            if !needed[n] || !matches!(self.nodes[n], Node::And(..)) {
+                continue;
+            }
+            let mut found = vec![];
+            let mut stack = vec![Edge::new(n, false)];
+            while let Some(e) = stack.pop() {
+                match self.node(e) {
+                    Node::And(a, b)
+                        if e.node() == n || (!e.is_complemented() && fanout[e.node()] == 1) =>
+                    {
+                        stack.push(a);
+                        stack.push(b);
+                    }
+                    _ => {
+                        needed[e.node()] = true;
+                        found.push(e);
+                    }
+                }
+            }
+            leaves.insert(n, found);
+        }
+
+        self.copy(roots, &needed, |copy, copied, n| {
+            let es = leaves[&n]
+                .iter()
+                .map(|e| copied[e.node()].xor(e.is_complemented()))
+                .collect();
+            copy.and_all(es)
+        })
+    }
+}
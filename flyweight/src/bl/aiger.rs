@@ -0,0 +1,425 @@
+//! Reading and writing and-inverter graphs in the AIGER format, ASCII
+//! (`aag`) and binary (`aig`), to cross-check with hardware verification
+//! and model checking tools.
+//!
+//! Only combinational circuits are supported: files with latches, or with
+//! the bad state, constraint, justice or fairness properties of AIGER 1.9,
+//! are rejected. An AIGER literal is twice the variable, plus one if
+//! complemented, with variable 0 the constant false, which is how `Edge`
+//! is laid out too; variables are renumbered on the way in and out, though,
+//! since AIGER puts the inputs first.
+
+use crate::ast::span::Span;
+use crate::ast::symbols::SymbolTable;
+use crate::bl::aig::{Aig, Edge, Node};
+use crate::diagnostics::Report;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// A graph and its named outputs, as an AIGER file holds.
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    pub aig: Aig,
+    pub outputs: Vec<(String, Edge)>,
+}
+
+fn varint(out: &mut Vec<u8>, mut x: u32) {
+    while x >= 0x80 {
+        out.push(x as u8 & 0x7f | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+/// The circuit in binary AIGER if `binary`, otherwise ASCII, with a
+/// symbol table naming the inputs, by the variables they are, and the
+/// outputs. Only what the outputs use is written.
+pub fn write_aiger(circuit: &Circuit, symbols: &SymbolTable, binary: bool) -> Vec<u8> {
+    let aig = &circuit.aig;
+    let roots: Vec<Edge> = circuit.outputs.iter().map(|(_, e)| *e).collect();
+    let reached = aig.cone(&roots);
+    let mut vars = vec![0; aig.nodes().len()];
+    let mut inputs = vec![];
+    let mut ands = vec![];
+    for (n, node) in aig.nodes().iter().enumerate() {
+        match node {
+            Node::Input(v) if reached[n] => inputs.push((n, *v)),
+            Node::And(a, b) if reached[n] => ands.push((n, *a, *b)),
+            _ => {}
+        }
+    }
+    for (i, (n, _)) in inputs.iter().enumerate() {
+        vars[*n] = i as u32 + 1;
+    }
+    for (i, (n, _, _)) in ands.iter().enumerate() {
+        vars[*n] = (inputs.len() + i) as u32 + 1;
+    }
+    let lit = |e: Edge| 2 * vars[e.node()] + e.is_complemented() as u32;
+
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{} {} {} 0 {} {}",
+        if binary { "aig" } else { "aag" },
+        inputs.len() + ands.len(),
+        inputs.len(),
+        roots.len(),
+        ands.len()
+    )
+    .unwrap();
+    if !binary {
+        for (n, _) in &inputs {
+            writeln!(text, "{}", 2 * vars[*n]).unwrap();
+        }
+    }
+    for e in &roots {
+        writeln!(text, "{}", lit(*e)).unwrap();
+    }
+    let mut out = text.into_bytes();
+    let mut text = String::new();
+    for (n, a, b) in &ands {
+        // The larger operand comes first, as the binary format needs.
+        let (lhs, rhs0, rhs1) = (2 * vars[*n], lit(*a).max(lit(*b)), lit(*a).min(lit(*b)));
+        if binary {
+            varint(&mut out, lhs - rhs0);
+            varint(&mut out, rhs0 - rhs1);
+        } else {
+            writeln!(text, "{} {} {}", lhs, rhs0, rhs1).unwrap();
+        }
+    }
+    for (i, (_, v)) in inputs.iter().enumerate() {
+        writeln!(text, "i{} {}", i, symbols.name(*v)).unwrap();
+    }
+    for (i, (name, _)) in circuit.outputs.iter().enumerate() {
+        if !name.is_empty() {
+            writeln!(text, "o{} {}", i, name).unwrap();
+        }
+    }
+    out.extend(text.into_bytes());
+    out
+}
+
+/// A position in the bytes of an AIGER file.
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn error<S: Into<String>>(&self, message: S) -> Vec<Report> {
+        let span = Span::new(self.pos, (self.pos + 1).min(self.bytes.len()));
+        vec![Report::error(message).with_label(span, "")]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn number(&mut self) -> Result<u32, Vec<Report>> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        let digits = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap();
+        digits.parse().map_err(|_| {
+            self.pos = start;
+            self.error("expected a number")
+        })
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), Vec<Report>> {
+        match self.peek() {
+            Some(c) if c == b => {
+                self.pos += 1;
+                Ok(())
+            }
+            None => Err(self.error("unexpected end of file")),
+            Some(_) if b == b'\n' => Err(self.error("expected the end of the line")),
+            Some(_) => Err(self.error("expected a space")),
+        }
+    }
+
+    /// A number on a line of its own.
+    fn line(&mut self) -> Result<u32, Vec<Report>> {
+        let n = self.number()?;
+        self.expect(b'\n')?;
+        Ok(n)
+    }
+
+    fn varint(&mut self) -> Result<u32, Vec<Report>> {
+        let mut x: u32 = 0;
+        for shift in (0..32).step_by(7) {
+            let b = match self.peek() {
+                Some(b) => b,
+                None => return Err(self.error("unexpected end of file")),
+            };
+            self.pos += 1;
+            x |= ((b & 0x7f) as u32) << shift;
+            if b & 0x80 == 0 {
+                return Ok(x);
+            }
+        }
+        Err(self.error("number too large"))
+    }
+
+    /// The rest of the line, without the newline.
+    fn rest(&mut self) -> &[u8] {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            if b == b'\n' {
+                return &self.bytes[start..self.pos - 1];
+            }
+        }
+        &self.bytes[start..]
+    }
+}
+
+/// How an AIGER variable is defined.
+#[derive(Clone, Copy)]
+enum Def {
+    Undefined,
+    Input(usize),
+    /// The operands, and where the and is defined.
+    And(u32, u32, usize),
+}
+
+/// Reads a combinational circuit in either AIGER format. Inputs are named
+/// from the symbol table, or `i0`, `i1` and so on (`i1_1` if `i1` names
+/// another input), and outputs likewise `o0`, `o1`. Errors point at byte
+/// offsets in `bytes`.
+pub fn read_aiger(bytes: &[u8], symbols: &mut SymbolTable) -> Result<Circuit, Vec<Report>> {
+    let mut r = Reader { bytes, pos: 0 };
+    let binary = match bytes.get(..4) {
+        Some(b"aag ") => false,
+        Some(b"aig ") => true,
+        _ => return Err(r.error("expected an `aag` or `aig` header")),
+    };
+    r.pos = 4;
+    let mut header = vec![r.number()?];
+    while r.peek() == Some(b' ') {
+        r.pos += 1;
+        header.push(r.number()?);
+    }
+    r.expect(b'\n')?;
+    let (m, i, l, o, a) = match header[..] {
+        [m, i, l, o, a, ref rest @ ..] if rest.len() <= 4 => (m, i, l, o, a),
+        _ => {
+            return Err(vec![Report::error("malformed header")
+                .with_label(Span::new(0, r.pos - 1), "expected `M I L O A`")])
+        }
+    };
+    if l > 0 {
+        return Err(vec![Report::error("latches aren't supported")
+            .with_label(Span::new(0, r.pos - 1), "")
+            .with_note("only combinational circuits can be read")]);
+    }
+    if header[5..].iter().any(|n| *n > 0) {
+        return Err(vec![Report::error("properties aren't supported")
+            .with_label(Span::new(0, r.pos - 1), "")
+            .with_note("only combinational circuits can be read")]);
+    }
+    if (m as u64) < i as u64 + a as u64 {
+        return Err(vec![Report::error(
+            "the header has fewer variables than inputs and ands",
+        )
+        .with_label(Span::new(0, r.pos - 1), "")]);
+    }
+
+    let mut defs = vec![Def::Undefined; m as usize + 1];
+    let mut define = |r: &Reader, lhs: u32, def: Def| {
+        if lhs % 2 == 1 || lhs == 0 || lhs / 2 > m {
+            return Err(r.error(format!("can't define literal {}", lhs)));
+        }
+        match defs[lhs as usize / 2] {
+            Def::Undefined => {
+                defs[lhs as usize / 2] = def;
+                Ok(())
+            }
+            _ => Err(r.error(format!("variable {} is defined twice", lhs / 2))),
+        }
+    };
+    for k in 0..i as usize {
+        if binary {
+            define(&r, 2 * (k as u32 + 1), Def::Input(k))?;
+        } else {
+            let start = r.pos;
+            let lhs = r.line()?;
+            define(&Reader { bytes, pos: start }, lhs, Def::Input(k))?;
+        }
+    }
+    let mut outputs = vec![];
+    for _ in 0..o {
+        outputs.push((r.pos, r.line()?));
+    }
+    for k in 0..a {
+        let start = r.pos;
+        let (lhs, rhs0, rhs1) = if binary {
+            let lhs = 2 * (i + k + 1);
+            let (delta0, delta1) = (r.varint()?, r.varint()?);
+            if delta0 == 0 || delta0 > lhs || delta1 > lhs - delta0 {
+                r.pos = start;
+                return Err(r.error("and operands out of order"));
+            }
+            (lhs, lhs - delta0, lhs - delta0 - delta1)
+        } else {
+            let lhs = r.number()?;
+            r.expect(b' ')?;
+            let rhs0 = r.number()?;
+            r.expect(b' ')?;
+            (lhs, rhs0, r.line()?)
+        };
+        define(
+            &Reader { bytes, pos: start },
+            lhs,
+            Def::And(rhs0, rhs1, start),
+        )?;
+    }
+
+    // The symbol table, up to the comments. Each input gets one name and
+    // each name one input, or two inputs would come back as the same
+    // `VarId`.
+    let mut errs = vec![];
+    let mut input_names: HashMap<usize, (String, Span)> = HashMap::new();
+    let mut named_inputs: HashMap<String, (usize, Span)> = HashMap::new();
+    let mut output_names: HashMap<usize, String> = HashMap::new();
+    while let Some(kind @ (b'i' | b'l' | b'o')) = r.peek() {
+        let entry_start = r.pos;
+        r.pos += 1;
+        let k = r.number()? as usize;
+        r.expect(b' ')?;
+        let start = r.pos;
+        let rest = r.rest();
+        let name_span = Span::new(start, start + rest.len());
+        let entry = Span::new(entry_start, name_span.end);
+        let name = String::from_utf8_lossy(rest).into_owned();
+        match kind {
+            b'i' if k < i as usize => {
+                if let Some((_, first)) = input_names.get(&k) {
+                    errs.push(
+                        Report::error(format!("input {} is named twice", k))
+                            .with_label(entry, "named again here")
+                            .with_secondary_label(*first, "first named here"),
+                    );
+                } else if let Some((m, first)) = named_inputs.get(&name) {
+                    errs.push(
+                        Report::error(format!("`{}` names both input {} and input {}", name, m, k))
+                            .with_label(name_span, "")
+                            .with_secondary_label(*first, format!("input {}", m)),
+                    );
+                } else {
+                    named_inputs.insert(name.clone(), (k, name_span));
+                    input_names.insert(k, (name, entry));
+                }
+            }
+            b'o' if k < o as usize => {
+                output_names.insert(k, name);
+            }
+            _ => {
+                r.pos = start - 1;
+                return Err(r.error(format!("no {} {}", kind as char, k)));
+            }
+        }
+    }
+    match r.peek() {
+        None | Some(b'c') => {}
+        Some(_) => return Err(r.error("expected a symbol or a comment")),
+    }
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    let mut aig = Aig::new();
+    let mut edges: Vec<Option<Edge>> = vec![None; defs.len()];
+    edges[0] = Some(Edge::FALSE);
+    for (var, def) in defs.iter().enumerate() {
+        if let Def::Input(k) = def {
+            let name = match input_names.remove(k) {
+                Some((name, _)) => name,
+                None => (0..)
+                    .map(|j| match j {
+                        0 => format!("i{}", k),
+                        _ => format!("i{}_{}", k, j),
+                    })
+                    .find(|name| !named_inputs.contains_key(name))
+                    .unwrap(),
+            };
+            edges[var] = Some(aig.input(symbols.intern(&name)));
+        }
+    }
+    // Ands may come in any order in ASCII, so each is built once its
+    // operands are, and one found again while they're being built is on a
+    // cycle.
+    let mut building = vec![false; defs.len()];
+    let mut edge = |aig: &mut Aig, lit: u32| -> Result<Edge, String> {
+        let root = lit as usize / 2;
+        if root >= defs.len() {
+            return Err(format!("literal {} is out of range", lit));
+        }
+        let mut stack = vec![root];
+        while let Some(&var) = stack.last() {
+            if edges[var].is_some() {
+                stack.pop();
+                continue;
+            }
+            match defs[var] {
+                Def::And(rhs0, rhs1, _) => {
+                    let operands = [rhs0 as usize / 2, rhs1 as usize / 2];
+                    if let Some(v) = operands.iter().find(|v| **v >= defs.len()) {
+                        return Err(format!("literal {} is out of range", 2 * v));
+                    }
+                    let pending: Vec<usize> = operands
+                        .iter()
+                        .copied()
+                        .filter(|v| edges[*v].is_none())
+                        .collect();
+                    if pending.is_empty() {
+                        let operand = |lit: u32| {
+                            let e = edges[lit as usize / 2].unwrap();
+                            if lit % 2 == 1 {
+                                !e
+                            } else {
+                                e
+                            }
+                        };
+                        edges[var] = Some(aig.and(operand(rhs0), operand(rhs1)));
+                        stack.pop();
+                    } else if building[var] {
+                        return Err(format!("variable {} is defined by a cycle", var));
+                    } else {
+                        building[var] = true;
+                        // The lower variable on top, so it's built first.
+                        stack.extend(pending.into_iter().rev());
+                    }
+                }
+                _ => return Err(format!("variable {} is never defined", var)),
+            }
+        }
+        let e = edges[root].unwrap();
+        Ok(if lit % 2 == 1 { !e } else { e })
+    };
+
+    // Built in variable order, so a file in the order `write_aiger` uses
+    // is numbered the same when it's written back.
+    let ands: Vec<(usize, usize)> = defs
+        .iter()
+        .enumerate()
+        .filter_map(|(var, def)| match def {
+            Def::And(_, _, pos) => Some((var, *pos)),
+            _ => None,
+        })
+        .collect();
+    for (var, pos) in ands {
+        edge(&mut aig, 2 * var as u32).map_err(|message| Reader { bytes, pos }.error(message))?;
+    }
+    let mut circuit_outputs = vec![];
+    for (k, (pos, lit)) in outputs.into_iter().enumerate() {
+        let e = edge(&mut aig, lit).map_err(|message| Reader { bytes, pos }.error(message))?;
+        let name = output_names.remove(&k).unwrap_or_else(|| format!("o{}", k));
+        circuit_outputs.push((name, e));
+    }
+    Ok(Circuit {
+        aig,
+        outputs: circuit_outputs,
+    })
+}
@@ -0,0 +1,288 @@
+//! Algebraic normal form: an expression as an `xor` of `and`s of
+//! variables, its Zhegalkin polynomial over GF(2), and Gaussian
+//! elimination for the equations among them that are linear.
+//!
+//! The polynomial of a function is unique, so two expressions are
+//! equivalent exactly when their polynomials are equal, and `xor`-heavy
+//! constraints that are tangled in CNF come out short. Each `or` doubles
+//! the monomials, though, so building a polynomial gives up once a product
+//! takes more than `MAX_PRODUCTS` steps.
+
+use crate::ast::symbols::{Names, SymbolTable, VarId};
+use crate::bl::logic::Expr;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// The most monomial products one multiplication may take.
+pub const MAX_PRODUCTS: usize = 1 << 16;
+
+/// A sum of monomials, each the product of its sorted variables; the empty
+/// monomial is 1.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Anf {
+    monomials: BTreeSet<Vec<VarId>>,
+}
+
+/// Monomials are joined by `+` inside parentheses, each printed as an
+/// `and`, and 1 and 0 as 𝕋 and 𝔽, as `logic::Expr` prints.
+impl Names for Anf {
+    fn fmt_names(&self, symbols: &SymbolTable, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let many = self.monomials.len() > 1;
+        if self.monomials.is_empty() {
+            return write!(f, "𝔽");
+        }
+        if many {
+            write!(f, "(")?;
+        }
+        for (i, m) in self.monomials.iter().enumerate() {
+            if i > 0 {
+                write!(f, " + ")?;
+            }
+            match m.len() {
+                0 => write!(f, "𝕋")?,
+                1 => write!(f, "{}", symbols.name(m[0]))?,
+                _ => {
+                    let names: Vec<&str> = m.iter().map(|v| symbols.name(*v)).collect();
+                    write!(f, "({})", names.join(" & "))?;
+                }
+            }
+        }
+        if many {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl Anf {
+    pub fn constant(value: bool) -> Anf {
+        let mut p = Anf::default();
+        if value {
+            p.monomials.insert(vec![]);
+        }
+        p
+    }
+
+    pub fn var(v: VarId) -> Anf {
+        let mut p = Anf::default();
+        p.monomials.insert(vec![v]);
+        p
+    }
+
+    pub fn monomials(&self) -> impl Iterator<Item = &[VarId]> {
+        self.monomials.iter().map(|m| &m[..])
+    }
+
+    /// The polynomial's value, if it's the same for every assignment.
+    pub fn as_constant(&self) -> Option<bool> {
+        match self.monomials.iter().next() {
+            None => Some(false),
+            Some(m) if m.is_empty() && self.monomials.len() == 1 => Some(true),
+            _ => None,
+        }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.monomials.iter().map(|m| m.len()).max().unwrap_or(0)
+    }
+
+    /// Adds `m`, cancelling it if it's already there.
+    fn toggle(&mut self, m: Vec<VarId>) {
+        if !self.monomials.remove(&m) {
+            self.monomials.insert(m);
+        }
+    }
+
+    pub fn xor(&self, other: &Anf) -> Anf {
+        let mut p = self.clone();
+        for m in &other.monomials {
+            p.toggle(m.clone());
+        }
+        p
+    }
+
+    /// The product, or `None` if it would take more than `MAX_PRODUCTS`
+    /// steps.
+    pub fn and(&self, other: &Anf) -> Option<Anf> {
+        if self.monomials.len() * other.monomials.len() > MAX_PRODUCTS {
+            return None;
+        }
+        let mut p = Anf::default();
+        for a in &self.monomials {
+            for b in &other.monomials {
+                // x & x is x.
+                let mut m: Vec<VarId> = a.iter().chain(b).copied().collect();
+                m.sort();
+                m.dedup();
+                p.toggle(m);
+            }
+        }
+        Some(p)
+    }
+
+    /// The polynomial of `e`, or `None` if it grows too large.
+    pub fn new(e: &Expr) -> Option<Anf> {
+        let all =
+            |es: &[Box<Expr>]| -> Option<Vec<Anf>> { es.iter().map(|e| Anf::new(e)).collect() };
+        let one = Anf::constant(true);
+        Some(match e {
+            Expr::True => one,
+            Expr::False => Anf::default(),
+            Expr::Term(v) => Anf::var(*v),
+            Expr::Not(a) => Anf::new(a)?.xor(&one),
+            Expr::And(es) => all(es)?.iter().try_fold(one, |p, q| p.and(q))?,
+            // a | b is a + b + a & b.
+            Expr::Or(es) => all(es)?
+                .iter()
+                .try_fold(Anf::default(), |p, q| Some(p.xor(q).xor(&p.and(q)?)))?,
+            Expr::Xor(es) => all(es)?.iter().fold(Anf::default(), |p, q| p.xor(q)),
+            // a -> b is 1 + a + a & b.
+            Expr::Implication(a, b) => {
+                let (a, b) = (Anf::new(a)?, Anf::new(b)?);
+                one.xor(&a).xor(&a.and(&b)?)
+            }
+            Expr::Biconditional(a, b) => one.xor(&Anf::new(a)?).xor(&Anf::new(b)?),
+        })
+    }
+
+    /// The polynomial with each variable in `values` replaced by its
+    /// polynomial there, or `None` if that grows too large.
+    pub fn substitute(&self, values: &HashMap<VarId, Anf>) -> Option<Anf> {
+        let mut p = Anf::default();
+        for m in &self.monomials {
+            let product = m
+                .iter()
+                .try_fold(Anf::constant(true), |q, v| match values.get(v) {
+                    Some(value) => q.and(value),
+                    None => q.and(&Anf::var(*v)),
+                })?;
+            p = p.xor(&product);
+        }
+        Some(p)
+    }
+
+    /// The polynomial as an `xor` of `and`s, in canonical form.
+    pub fn expr(&self) -> Expr {
+        let monomials = self
+            .monomials
+            .iter()
+            .map(|m| {
+                let vars = m.iter().map(|v| Box::new(Expr::Term(*v))).collect();
+                Box::new(Expr::And(vars))
+            })
+            .collect();
+        Expr::Xor(monomials).canonical()
+    }
+
+    pub fn evaluate(&self, values: &HashMap<VarId, bool>) -> Option<bool> {
+        let mut value = false;
+        for m in &self.monomials {
+            let mut product = true;
+            for v in m {
+                product &= *values.get(v)?;
+            }
+            value ^= product;
+        }
+        Some(value)
+    }
+}
+
+/// Equations over GF(2) that are linear in `vars`, their right-hand sides
+/// polynomials in any other variables.
+#[derive(Debug, Clone)]
+pub struct XorSystem {
+    vars: Vec<VarId>,
+    /// Each equation's coefficients, by position in `vars`, and right-hand
+    /// side.
+    rows: Vec<(Vec<bool>, Anf)>,
+}
+
+/// The solution of an `XorSystem`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Elimination {
+    /// Each variable the equations determine, as a polynomial in the
+    /// unconstrained ones and those outside the system.
+    pub determined: Vec<(VarId, Anf)>,
+    /// Variables that can take either value.
+    pub unconstrained: Vec<VarId>,
+    /// Polynomials in the variables outside the system that have to be 0
+    /// for there to be a solution.
+    pub conditions: Vec<Anf>,
+}
+
+impl XorSystem {
+    pub fn new(vars: &[VarId]) -> XorSystem {
+        XorSystem {
+            vars: vars.to_vec(),
+            rows: vec![],
+        }
+    }
+
+    /// Adds the equation `p = 1` if it's linear in the system's variables:
+    /// none of them is in a monomial with any other variable. Returns
+    /// whether it was added.
+    pub fn assert(&mut self, p: &Anf) -> bool {
+        let mut coefficients = vec![false; self.vars.len()];
+        let mut rhs = Anf::constant(true);
+        for m in p.monomials() {
+            let positions: Vec<usize> = m
+                .iter()
+                .filter_map(|v| self.vars.iter().position(|x| x == v))
+                .collect();
+            match positions[..] {
+                [] => rhs.toggle(m.to_vec()),
+                [i] if m.len() == 1 => coefficients[i] = true,
+                _ => return false,
+            }
+        }
+        self.rows.push((coefficients, rhs));
+        true
+    }
+
+    /// Solves the equations by Gauss–Jordan elimination.
+    pub fn eliminate(mut self) -> Elimination {
+        let mut pivots = vec![];
+        for col in 0..self.vars.len() {
+            let rank = pivots.len();
+            let r = match (rank..self.rows.len()).find(|r| self.rows[*r].0[col]) {
+                Some(r) => r,
+                None => continue,
+            };
+            self.rows.swap(rank, r);
+            let (pivot, pivot_rhs) = self.rows[rank].clone();
+            for (i, (coefficients, rhs)) in self.rows.iter_mut().enumerate() {
+                if i != rank && coefficients[col] {
+                    for (c, p) in coefficients.iter_mut().zip(&pivot) {
+                        *c ^= *p;
+                    }
+                    *rhs = rhs.xor(&pivot_rhs);
+                }
+            }
+            pivots.push(col);
+        }
+
+        let unconstrained: Vec<usize> = (0..self.vars.len())
+            .filter(|c| !pivots.contains(c))
+            .collect();
+        let determined = pivots
+            .iter()
+            .zip(&self.rows)
+            .map(|(col, (coefficients, rhs))| {
+                let value = unconstrained
+                    .iter()
+                    .filter(|c| coefficients[**c])
+                    .fold(rhs.clone(), |p, c| p.xor(&Anf::var(self.vars[*c])));
+                (self.vars[*col], value)
+            })
+            .collect();
+        Elimination {
+            determined,
+            unconstrained: unconstrained.iter().map(|c| self.vars[*c]).collect(),
+            conditions: self.rows[pivots.len()..]
+                .iter()
+                .filter(|(_, rhs)| rhs.as_constant() != Some(false))
+                .map(|(_, rhs)| rhs.clone())
+                .collect(),
+        }
+    }
+}
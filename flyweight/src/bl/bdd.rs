@@ -0,0 +1,475 @@
+//! Reduced ordered binary decision diagrams.
+//!
+//! A `Bdd` owns every node it has built, hash-consed through a unique table
+//! so that equal functions are the same `NodeId`. Variables are placed on
+//! levels in the order they're first seen, unless an order is given up
+//! front; `conjunction` tries a few ordering heuristics on a set of
+//! expressions and keeps whichever gives the smallest diagram.
+
+use crate::ast::nodes::{Expr, ExprKind};
+use crate::ast::symbols::VarId;
+use std::collections::{HashMap, HashSet};
+
+/// A node, and so a boolean function, in a `Bdd`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    pub const FALSE: NodeId = NodeId(0);
+    pub const TRUE: NodeId = NodeId(1);
+
+    pub fn is_const(self) -> bool {
+        self == NodeId::FALSE || self == NodeId::TRUE
+    }
+}
+
+/// The level of both terminals, below every variable.
+const TERMINAL: u32 = u32::MAX;
+
+#[derive(Clone, Copy)]
+struct Node {
+    level: u32,
+    lo: NodeId,
+    hi: NodeId,
+}
+
+pub struct Bdd {
+    nodes: Vec<Node>,
+    unique: HashMap<(u32, NodeId, NodeId), NodeId>,
+    ite_cache: HashMap<(NodeId, NodeId, NodeId), NodeId>,
+    /// Results of `exists`, by node and set of quantified levels. The sets
+    /// are numbered by `quantified`.
+    exists_cache: HashMap<(NodeId, usize), NodeId>,
+    quantified: HashMap<Vec<u32>, usize>,
+    /// The variable on each level, top first.
+    order: Vec<VarId>,
+    levels: HashMap<VarId, u32>,
+}
+
+impl Default for Bdd {
+    fn default() -> Bdd {
+        Bdd::new()
+    }
+}
+
+impl Bdd {
+    pub fn new() -> Bdd {
+        let terminal = Node {
+            level: TERMINAL,
+            lo: NodeId::FALSE,
+            hi: NodeId::FALSE,
+        };
+        Bdd {
+            nodes: vec![terminal, terminal],
+            unique: HashMap::new(),
+            ite_cache: HashMap::new(),
+            exists_cache: HashMap::new(),
+            quantified: HashMap::new(),
+            order: vec![],
+            levels: HashMap::new(),
+        }
+    }
+
+    /// A diagram whose top levels hold `order`, in that order.
+    pub fn with_order<I: IntoIterator<Item = VarId>>(order: I) -> Bdd {
+        let mut bdd = Bdd::new();
+        for v in order {
+            bdd.level(v);
+        }
+        bdd
+    }
+
+    /// The variable order, top first.
+    pub fn order(&self) -> &[VarId] {
+        &self.order
+    }
+
+    /// Nodes built so far, terminals included.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 2
+    }
+
+    /// `v`'s level, giving it the next free one if it hasn't got one.
+    fn level(&mut self, v: VarId) -> u32 {
+        if let Some(l) = self.levels.get(&v) {
+            return *l;
+        }
+        let l = self.order.len() as u32;
+        self.order.push(v);
+        self.levels.insert(v, l);
+        l
+    }
+
+    fn node(&self, f: NodeId) -> Node {
+        self.nodes[f.0 as usize]
+    }
+
+    /// The node testing `level`, unless both branches are the same.
+    fn mk(&mut self, level: u32, lo: NodeId, hi: NodeId) -> NodeId {
+        if lo == hi {
+            return lo;
+        }
+        if let Some(n) = self.unique.get(&(level, lo, hi)) {
+            return *n;
+        }
+        let n = NodeId(self.nodes.len() as u32);
+        self.nodes.push(Node { level, lo, hi });
+        self.unique.insert((level, lo, hi), n);
+        n
+    }
+
+    pub fn constant(&self, value: bool) -> NodeId {
+        if value {
+            NodeId::TRUE
+        } else {
+            NodeId::FALSE
+        }
+    }
+
+    pub fn var(&mut self, v: VarId) -> NodeId {
+        let l = self.level(v);
+        self.mk(l, NodeId::FALSE, NodeId::TRUE)
+    }
+
+    /// `f`'s branches for `level` being false and true.
+    fn cofactors(&self, f: NodeId, level: u32) -> (NodeId, NodeId) {
+        let n = self.node(f);
+        if n.level == level {
+            (n.lo, n.hi)
+        } else {
+            (f, f)
+        }
+    }
+
+    /// If `f` then `g` else `h`; every other operation is built on this.
+    pub fn ite(&mut self, f: NodeId, g: NodeId, h: NodeId) -> NodeId {
+        if f == NodeId::TRUE || g == h {
+            return g;
+        }
+        if f == NodeId::FALSE {
+            return h;
+        }
+        if g == NodeId::TRUE && h == NodeId::FALSE {
+            return f;
+        }
+        if let Some(r) = self.ite_cache.get(&(f, g, h)) {
+            return *r;
+        }
+        let level = [f, g, h].iter().map(|n| self.node(*n).level).min().unwrap();
+        let (f0, f1) = self.cofactors(f, level);
+        let (g0, g1) = self.cofactors(g, level);
+        let (h0, h1) = self.cofactors(h, level);
+        let lo = self.ite(f0, g0, h0);
+        let hi = self.ite(f1, g1, h1);
+        let r = self.mk(level, lo, hi);
+        self.ite_cache.insert((f, g, h), r);
+        r
+    }
+
+    pub fn not(&mut self, f: NodeId) -> NodeId {
+        self.ite(f, NodeId::FALSE, NodeId::TRUE)
+    }
+
+    pub fn and(&mut self, f: NodeId, g: NodeId) -> NodeId {
+        self.ite(f, g, NodeId::FALSE)
+    }
+
+    pub fn or(&mut self, f: NodeId, g: NodeId) -> NodeId {
+        self.ite(f, NodeId::TRUE, g)
+    }
+
+    pub fn xor(&mut self, f: NodeId, g: NodeId) -> NodeId {
+        let ng = self.not(g);
+        self.ite(f, ng, g)
+    }
+
+    pub fn implies(&mut self, f: NodeId, g: NodeId) -> NodeId {
+        self.ite(f, g, NodeId::TRUE)
+    }
+
+    pub fn iff(&mut self, f: NodeId, g: NodeId) -> NodeId {
+        let ng = self.not(g);
+        self.ite(f, g, ng)
+    }
+
+    /// The function of an expression.
+    pub fn expr(&mut self, e: &Expr) -> NodeId {
+        match &e.kind {
+            ExprKind::True => NodeId::TRUE,
+            ExprKind::False => NodeId::FALSE,
+            ExprKind::Term(v) => self.var(*v),
+            ExprKind::Not(a) => {
+                let a = self.expr(a);
+                self.not(a)
+            }
+            ExprKind::And(a, b)
+            | ExprKind::Or(a, b)
+            | ExprKind::Xor(a, b)
+            | ExprKind::Implication(a, b)
+            | ExprKind::Biconditional(a, b) => {
+                let a = self.expr(a);
+                let b = self.expr(b);
+                match e.kind {
+                    ExprKind::And(..) => self.and(a, b),
+                    ExprKind::Or(..) => self.or(a, b),
+                    ExprKind::Xor(..) => self.xor(a, b),
+                    ExprKind::Implication(..) => self.implies(a, b),
+                    _ => self.iff(a, b),
+                }
+            }
+        }
+    }
+
+    /// `f` with `v` fixed to `value`.
+    pub fn restrict(&mut self, f: NodeId, v: VarId, value: bool) -> NodeId {
+        match self.levels.get(&v) {
+            Some(&level) => self.restrict_level(f, level, value, &mut HashMap::new()),
+            None => f,
+        }
+    }
+
+    fn restrict_level(
+        &mut self,
+        f: NodeId,
+        level: u32,
+        value: bool,
+        memo: &mut HashMap<NodeId, NodeId>,
+    ) -> NodeId {
+        let n = self.node(f);
+        if n.level > level {
+            return f;
+        }
+        if n.level == level {
+            return if value { n.hi } else { n.lo };
+        }
+        if let Some(r) = memo.get(&f) {
+            return *r;
+        }
+        let lo = self.restrict_level(n.lo, level, value, memo);
+        let hi = self.restrict_level(n.hi, level, value, memo);
+        let r = self.mk(n.level, lo, hi);
+        memo.insert(f, r);
+        r
+    }
+
+    /// `f` with each `(v, value)` of `assignment` fixed.
+    pub fn restrict_all(&mut self, f: NodeId, assignment: &[(VarId, bool)]) -> NodeId {
+        assignment
+            .iter()
+            .fold(f, |f, (v, value)| self.restrict(f, *v, *value))
+    }
+
+    /// Whether `f` holds for some value of each of `vars`.
+    pub fn exists(&mut self, f: NodeId, vars: &[VarId]) -> NodeId {
+        let mut levels: Vec<u32> = vars
+            .iter()
+            .filter_map(|v| self.levels.get(v).copied())
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+        let next = self.quantified.len();
+        let set = *self.quantified.entry(levels.clone()).or_insert(next);
+        let levels: HashSet<u32> = levels.into_iter().collect();
+        self.exists_levels(f, &levels, set)
+    }
+
+    fn exists_levels(&mut self, f: NodeId, levels: &HashSet<u32>, set: usize) -> NodeId {
+        if f.is_const() {
+            return f;
+        }
+        if let Some(r) = self.exists_cache.get(&(f, set)) {
+            return *r;
+        }
+        let n = self.node(f);
+        let lo = self.exists_levels(n.lo, levels, set);
+        let hi = self.exists_levels(n.hi, levels, set);
+        let r = if levels.contains(&n.level) {
+            self.or(lo, hi)
+        } else {
+            self.mk(n.level, lo, hi)
+        };
+        self.exists_cache.insert((f, set), r);
+        r
+    }
+
+    /// Whether `f` holds for every value of each of `vars`.
+    pub fn forall(&mut self, f: NodeId, vars: &[VarId]) -> NodeId {
+        let nf = self.not(f);
+        let e = self.exists(nf, vars);
+        self.not(e)
+    }
+
+    /// The variables `f` depends on, top first.
+    pub fn support(&self, f: NodeId) -> Vec<VarId> {
+        let mut levels = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![f];
+        while let Some(f) = stack.pop() {
+            if f.is_const() || !seen.insert(f) {
+                continue;
+            }
+            let n = self.node(f);
+            levels.insert(n.level);
+            stack.push(n.lo);
+            stack.push(n.hi);
+        }
+        let mut levels: Vec<u32> = levels.into_iter().collect();
+        levels.sort_unstable();
+        levels.iter().map(|l| self.order[*l as usize]).collect()
+    }
+
+    /// Nodes reachable from `f`, terminals included.
+    pub fn node_count(&self, f: NodeId) -> usize {
+        let mut seen = HashSet::new();
+        let mut stack = vec![f];
+        while let Some(f) = stack.pop() {
+            if !seen.insert(f) || f.is_const() {
+                continue;
+            }
+            let n = self.node(f);
+            stack.push(n.lo);
+            stack.push(n.hi);
+        }
+        seen.len()
+    }
+
+    /// The number of assignments to `vars` satisfying `f`, which must not
+    /// depend on anything else. A float, since with a few hundred variables
+    /// it can outgrow any integer type.
+    pub fn sat_count(&mut self, f: NodeId, vars: &[VarId]) -> f64 {
+        for v in vars {
+            self.level(*v);
+        }
+        let all = self.order.len() as i32;
+        let count = self.sat_count_from(f, &mut HashMap::new()) * 2f64.powi(self.top_gap(f));
+        count / 2f64.powi(all - vars.len() as i32)
+    }
+
+    /// Levels above `f`'s that it doesn't test.
+    fn top_gap(&self, f: NodeId) -> i32 {
+        self.node(f).level.min(self.order.len() as u32) as i32
+    }
+
+    /// Satisfying assignments of the levels from `f`'s down.
+    fn sat_count_from(&self, f: NodeId, memo: &mut HashMap<NodeId, f64>) -> f64 {
+        if f == NodeId::FALSE {
+            return 0.0;
+        }
+        if f == NodeId::TRUE {
+            return 1.0;
+        }
+        if let Some(c) = memo.get(&f) {
+            return *c;
+        }
+        let n = self.node(f);
+        let below = |child: NodeId| {
+            let gap = self.node(child).level.min(self.order.len() as u32) - n.level - 1;
+            2f64.powi(gap as i32)
+        };
+        let c = self.sat_count_from(n.lo, memo) * below(n.lo)
+            + self.sat_count_from(n.hi, memo) * below(n.hi);
+        memo.insert(f, c);
+        c
+    }
+
+    /// One assignment satisfying `f`, of just the variables on its path;
+    /// the others may take any value. Prefers `false` where both work.
+    pub fn any_sat(&self, f: NodeId) -> Option<Vec<(VarId, bool)>> {
+        if f == NodeId::FALSE {
+            return None;
+        }
+        let mut out = vec![];
+        let mut f = f;
+        while !f.is_const() {
+            let n = self.node(f);
+            let v = self.order[n.level as usize];
+            if n.lo != NodeId::FALSE {
+                out.push((v, false));
+                f = n.lo;
+            } else {
+                out.push((v, true));
+                f = n.hi;
+            }
+        }
+        Some(out)
+    }
+}
+
+/// Variables in the order a depth-first walk of `exprs` first meets them,
+/// which keeps variables used together close together.
+pub fn first_use_order(exprs: &[Expr]) -> Vec<VarId> {
+    let mut out = vec![];
+    let mut seen = HashSet::new();
+    for e in exprs {
+        for i in e.idents() {
+            if seen.insert(i.id) {
+                out.push(i.id);
+            }
+        }
+    }
+    out
+}
+
+/// Variables used most often first, ties in first-use order.
+pub fn occurrence_order(exprs: &[Expr]) -> Vec<VarId> {
+    let mut counts: HashMap<VarId, usize> = HashMap::new();
+    for e in exprs {
+        for i in e.idents() {
+            *counts.entry(i.id).or_default() += 1;
+        }
+    }
+    let mut out = first_use_order(exprs);
+    out.sort_by_key(|v| std::cmp::Reverse(counts[v]));
+    out
+}
+
+/// Builds the conjunction of `exprs` under each ordering heuristic and
+/// returns the diagram and function of the smallest. A bad order can blow
+/// up exponentially, so each attempt after the first is abandoned once it
+/// has built more nodes than the best so far.
+pub fn conjunction(exprs: &[Expr]) -> (Bdd, NodeId) {
+    let first_use = first_use_order(exprs);
+    let mut reversed = first_use.clone();
+    reversed.reverse();
+    let mut best: Option<(Bdd, NodeId)> = None;
+    for order in [first_use, occurrence_order(exprs), reversed] {
+        let budget = best
+            .as_ref()
+            .map(|(bdd, _)| bdd.len())
+            .unwrap_or(usize::MAX);
+        let mut bdd = Bdd::with_order(order);
+        // Conjoining pairwise keeps the intermediate diagrams small; folding
+        // clause by clause rebuilds the growing conjunction every time.
+        let mut fs: Vec<NodeId> = exprs.iter().map(|e| bdd.expr(e)).collect();
+        while fs.len() > 1 && bdd.len() <= budget {
+            let mut next = vec![];
+            for pair in fs.chunks(2) {
+                next.push(match pair {
+                    [f, g] => bdd.and(*f, *g),
+                    _ => pair[0],
+                });
+            }
+            fs = next;
+        }
+        let f = match fs.as_slice() {
+            _ if bdd.len() > budget => None,
+            [] => Some(NodeId::TRUE),
+            [f] => Some(*f),
+            _ => None,
+        };
+        if let Some(f) = f {
+            let smaller = match &best {
+                Some((b, g)) => bdd.node_count(f) < b.node_count(*g),
+                None => true,
+            };
+            if smaller {
+                best = Some((bdd, f));
+            }
+        }
+    }
+    best.unwrap()
+}
@@ -0,0 +1,447 @@
+//! Conjunctive normal form by the Tseitin encoding, with the polarity
+//! optimization of Plaisted and Greenbaum, and DIMACS output.
+//!
+//! Each `and`, `or` and `xor` gets an auxiliary variable standing for it,
+//! so the CNF grows linearly with the expression where distributing `or`
+//! over `and` (as `Expr::cnf` in `ast::nodes` does) blows up, e.g. on
+//! chains of `xor`s. A gate only used positively only needs the clauses
+//! saying its variable implies it, and one only used negatively only the
+//! converse. The result is equisatisfiable with the expression, not
+//! equivalent: auxiliary variables are constrained only as far as needed.
+//!
+//! Gates are shared between equal subexpressions, so expressions should be
+//! in canonical form (see `bl::logic`) to share the most.
+//!
+//! `parse_dimacs` reads DIMACS back, so problems from other tools can be
+//! replayed against `bl::sat` and the simplifier.
+
+use crate::ast::span::Span;
+use crate::ast::symbols::{Names, SymbolTable, VarId};
+use crate::bl::logic::Expr;
+use crate::bl::sat::{Lit, Solver};
+use crate::diagnostics::Report;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// What a CNF variable stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CnfVar {
+    Input(VarId),
+    /// An auxiliary variable for this subexpression.
+    Aux(Expr),
+}
+
+/// Clauses of DIMACS literals: variable `n` is `n`, its negation `-n`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cnf {
+    pub clauses: Vec<Vec<i32>>,
+    /// Variable `n` is `vars[n - 1]`.
+    pub vars: Vec<CnfVar>,
+}
+
+impl Cnf {
+    pub fn num_vars(&self) -> usize {
+        self.vars.len()
+    }
+
+    /// The DIMACS `c var 3 = B_occ` comment name for each input variable,
+    /// and `c aux 7 = (a & b)` for each auxiliary one, then the problem.
+    pub fn dimacs(&self, symbols: &SymbolTable) -> String {
+        let mut out = String::new();
+        for (i, v) in self.vars.iter().enumerate() {
+            match v {
+                CnfVar::Input(id) => writeln!(out, "c var {} = {}", i + 1, symbols.name(*id)),
+                CnfVar::Aux(e) => writeln!(out, "c aux {} = {}", i + 1, e.named(symbols)),
+            }
+            .unwrap();
+        }
+        writeln!(out, "p cnf {} {}", self.vars.len(), self.clauses.len()).unwrap();
+        for c in &self.clauses {
+            for l in c {
+                write!(out, "{} ", l).unwrap();
+            }
+            writeln!(out, "0").unwrap();
+        }
+        out
+    }
+
+    /// The clauses as an expression in canonical form, each auxiliary
+    /// variable replaced by what it stands for. For clauses from `Encoder`
+    /// that's equivalent to the expressions asserted.
+    pub fn expr(&self) -> Expr {
+        let var = |n: i32| match &self.vars[n.unsigned_abs() as usize - 1] {
+            CnfVar::Input(id) => Expr::Term(*id),
+            CnfVar::Aux(e) => e.clone(),
+        };
+        let clauses = self
+            .clauses
+            .iter()
+            .map(|c| {
+                let literals = c
+                    .iter()
+                    .map(|l| match var(*l) {
+                        e if *l > 0 => Box::new(e),
+                        e => Box::new(Expr::Not(Box::new(e))),
+                    })
+                    .collect();
+                Box::new(Expr::Or(literals))
+            })
+            .collect();
+        Expr::And(clauses).canonical()
+    }
+
+    /// A solver loaded with the clauses, and the literal of each variable:
+    /// variable `n` is at `n - 1`.
+    pub fn solver(&self) -> (Solver, Vec<Lit>) {
+        let mut solver = Solver::new();
+        let lits: Vec<Lit> = self
+            .vars
+            .iter()
+            .map(|_| solver.new_var().lit(true))
+            .collect();
+        for c in &self.clauses {
+            let c: Vec<Lit> = c
+                .iter()
+                .map(|l| lits[l.unsigned_abs() as usize - 1].var().lit(*l > 0))
+                .collect();
+            solver.add_clause(&c);
+        }
+        (solver, lits)
+    }
+}
+
+/// The whitespace-separated words of `line`, which starts at `offset`.
+fn words(line: &str, offset: usize) -> Vec<(&str, Span)> {
+    let mut words = vec![];
+    let mut start = None;
+    for (i, c) in line
+        .char_indices()
+        .chain(std::iter::once((line.len(), ' ')))
+    {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                words.push((&line[s..i], Span::new(offset + s, offset + i)));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Reads a DIMACS CNF problem. Comment lines `c var 3 = B_occ` name
+/// variables, and the others are called `x3` and so on (`x3_1` if `x3`
+/// names another); `c aux` lines are ignored, so auxiliary variables come
+/// back as inputs. A `%` line ends
+/// the problem, as in the SATLIB benchmarks.
+pub fn parse_dimacs(src: &str, symbols: &mut SymbolTable) -> Result<Cnf, Vec<Report>> {
+    let mut errs = vec![];
+    // The number of variables and clauses and where each is declared.
+    let mut header: Option<(usize, Span, usize, Span)> = None;
+    let mut names: Vec<(usize, Span, &str, Span)> = vec![];
+    let mut clauses = vec![];
+    let mut clause = vec![];
+    let mut offset = 0;
+    for line in src.split_inclusive('\n') {
+        let words = words(line, offset);
+        let line_span = Span::new(offset, offset + line.trim_end().len());
+        offset += line.len();
+        match words[..] {
+            [] => {}
+            [(c, _), ..] if c.starts_with('c') => {
+                if let [_, ("var", _), (n, span), ("=", _), (name, name_span)] = words[..] {
+                    match n.parse() {
+                        Ok(n) => names.push((n, span, name, name_span)),
+                        Err(_) => errs
+                            .push(Report::error("expected a variable number").with_label(span, "")),
+                    }
+                }
+            }
+            [("%", _), ..] => break,
+            [("p", _), ..] => {
+                if header.is_some() {
+                    errs.push(
+                        Report::error("there's already a problem line")
+                            .with_label(line_span, "second problem line"),
+                    );
+                    continue;
+                }
+                let number = |(word, span): (&str, Span)| word.parse().map(|n| (n, span)).ok();
+                header = match words[1..] {
+                    [("cnf", _), vars, count] => number(vars).zip(number(count)),
+                    _ => None,
+                }
+                .map(|((vars, vars_span), (count, count_span))| {
+                    (vars, vars_span, count, count_span)
+                });
+                if header.is_none() {
+                    errs.push(
+                        Report::error("malformed problem line")
+                            .with_label(line_span, "expected `p cnf <variables> <clauses>`"),
+                    );
+                }
+            }
+            _ => {
+                let (vars, vars_span) = match header {
+                    Some((vars, vars_span, _, _)) => (vars, vars_span),
+                    // A malformed problem line has been reported already.
+                    None if !errs.is_empty() => break,
+                    None => {
+                        errs.push(
+                            Report::error("clauses before the problem line")
+                                .with_label(line_span, ""),
+                        );
+                        break;
+                    }
+                };
+                for (word, span) in words {
+                    match word.parse::<i32>() {
+                        Ok(0) => clauses.push(std::mem::take(&mut clause)),
+                        Ok(l) if l.unsigned_abs() as usize <= vars => clause.push(l),
+                        Ok(l) => errs.push(
+                            Report::error(format!("variable {} is out of range", l.abs()))
+                                .with_label(span, "")
+                                .with_secondary_label(
+                                    vars_span,
+                                    format!("the problem has {} variables", vars),
+                                ),
+                        ),
+                        Err(_) => errs.push(
+                            Report::error("expected a literal")
+                                .with_label(span, "not a nonzero integer"),
+                        ),
+                    }
+                }
+            }
+        }
+    }
+    // The last clause's `0` is often left off.
+    if !clause.is_empty() {
+        clauses.push(clause);
+    }
+
+    let (vars, vars_span, count, count_span) = match header {
+        Some(header) => header,
+        None => {
+            if errs.is_empty() {
+                errs.push(
+                    Report::error("missing the problem line")
+                        .with_label(Span::new(0, 0), "expected `p cnf <variables> <clauses>`"),
+                );
+            }
+            return Err(errs);
+        }
+    };
+    if clauses.len() != count {
+        errs.push(
+            Report::error(format!(
+                "the problem has {} clauses but the file has {}",
+                count,
+                clauses.len()
+            ))
+            .with_label(count_span, ""),
+        );
+    }
+    // Each variable gets one name and each name one variable, or two DIMACS
+    // variables would come back as the same `VarId`.
+    let mut named: HashMap<usize, (&str, Span)> = HashMap::new();
+    let mut numbered: HashMap<&str, (usize, Span)> = HashMap::new();
+    for (n, span, name, name_span) in names {
+        if n == 0 || n > vars {
+            errs.push(
+                Report::error(format!("variable {} is out of range", n))
+                    .with_label(span, "")
+                    .with_secondary_label(vars_span, format!("the problem has {} variables", vars)),
+            );
+        } else if let Some((_, first)) = named.get(&n) {
+            errs.push(
+                Report::error(format!("variable {} is named twice", n))
+                    .with_label(span, "named again here")
+                    .with_secondary_label(*first, "first named here"),
+            );
+        } else if let Some((m, first)) = numbered.get(name) {
+            errs.push(
+                Report::error(format!(
+                    "`{}` names both variable {} and variable {}",
+                    name, m, n
+                ))
+                .with_label(name_span, "")
+                .with_secondary_label(*first, format!("variable {}", m)),
+            );
+        } else {
+            named.insert(n, (name, span));
+            numbered.insert(name, (n, name_span));
+        }
+    }
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    let vars = (1..=vars)
+        .map(|n| {
+            let name = match named.get(&n) {
+                Some((name, _)) => name.to_string(),
+                None => (0..)
+                    .map(|k| match k {
+                        0 => format!("x{}", n),
+                        _ => format!("x{}_{}", n, k),
+                    })
+                    .find(|name| !numbered.contains_key(&name[..]))
+                    .unwrap(),
+            };
+            CnfVar::Input(symbols.intern(&name))
+        })
+        .collect();
+    Ok(Cnf { clauses, vars })
+}
+
+/// Builds a `Cnf` from expressions asserted one at a time.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    cnf: Cnf,
+    inputs: HashMap<VarId, i32>,
+    /// Each gate's variable, and whether the clauses for it being used
+    /// positively and negatively have been added.
+    gates: HashMap<Expr, (i32, bool, bool)>,
+}
+
+impl Encoder {
+    /// An encoder whose first variables are `inputs`, in order. Other
+    /// inputs are numbered as they're seen.
+    pub fn new(inputs: &[VarId]) -> Encoder {
+        let mut encoder = Encoder::default();
+        for v in inputs {
+            encoder.input(*v);
+        }
+        encoder
+    }
+
+    fn new_var(&mut self, v: CnfVar) -> i32 {
+        self.cnf.vars.push(v);
+        self.cnf.vars.len() as i32
+    }
+
+    fn input(&mut self, v: VarId) -> i32 {
+        match self.inputs.get(&v) {
+            Some(n) => *n,
+            None => {
+                let n = self.new_var(CnfVar::Input(v));
+                self.inputs.insert(v, n);
+                n
+            }
+        }
+    }
+
+    /// Adds clauses that hold only if `e` does. Top-level `and`s and
+    /// clauses of variables need no auxiliary variables.
+    pub fn assert(&mut self, e: &Expr) {
+        let literal = |e: &Expr| match e {
+            Expr::Term(_) => true,
+            Expr::Not(a) => matches!(**a, Expr::Term(_)),
+            _ => false,
+        };
+        match e {
+            Expr::True => {}
+            Expr::False => self.cnf.clauses.push(vec![]),
+            Expr::And(es) => es.iter().for_each(|e| self.assert(e)),
+            Expr::Or(es) if es.iter().all(|e| literal(e)) => {
+                let clause = es.iter().map(|e| self.lit(e, true, false)).collect();
+                self.cnf.clauses.push(clause);
+            }
+            _ => {
+                let l = self.lit(e, true, false);
+                self.cnf.clauses.push(vec![l]);
+            }
+        }
+    }
+
+    /// A literal that implies `e` if `positive` and is implied by it if
+    /// `negative`.
+    fn lit(&mut self, e: &Expr, positive: bool, negative: bool) -> i32 {
+        let (operands, xor) = match e {
+            Expr::Term(v) => return self.input(*v),
+            Expr::Not(a) => return -self.lit(a, negative, positive),
+            Expr::True => return self.lit(&Expr::And(vec![]), positive, negative),
+            Expr::False => return self.lit(&Expr::Or(vec![]), positive, negative),
+            Expr::Implication(a, b) => {
+                let or = Expr::Or(vec![Box::new(Expr::Not(a.clone())), b.clone()]);
+                return self.lit(&or, positive, negative);
+            }
+            Expr::Biconditional(a, b) => {
+                let xor = Expr::Not(Box::new(Expr::Xor(vec![a.clone(), b.clone()])));
+                return self.lit(&xor, positive, negative);
+            }
+            Expr::Xor(es) if es.is_empty() => return self.lit(&Expr::False, positive, negative),
+            Expr::Xor(es) if es.len() == 1 => return self.lit(&es[0], positive, negative),
+            // Wider `xor`s are chains of two-input ones.
+            Expr::Xor(es) if es.len() > 2 => {
+                let (last, rest) = es.split_last().unwrap();
+                let chain = Expr::Xor(vec![Box::new(Expr::Xor(rest.to_vec())), last.clone()]);
+                return self.lit(&chain, positive, negative);
+            }
+            Expr::Xor(es) => (es, true),
+            Expr::And(es) | Expr::Or(es) => (es, false),
+        };
+
+        let (g, done_positive, done_negative) = match self.gates.get(e) {
+            Some(gate) => *gate,
+            None => {
+                let g = self.new_var(CnfVar::Aux(e.clone()));
+                (g, false, false)
+            }
+        };
+        let positive = positive && !done_positive;
+        let negative = negative && !done_negative;
+        self.gates.insert(
+            e.clone(),
+            (g, done_positive || positive, done_negative || negative),
+        );
+        if !positive && !negative {
+            return g;
+        }
+
+        // Both values of an `xor`'s operands matter either way.
+        let (p, n) = if xor {
+            (true, true)
+        } else {
+            (positive, negative)
+        };
+        let ls: Vec<i32> = operands.iter().map(|o| self.lit(o, p, n)).collect();
+        let clauses = &mut self.cnf.clauses;
+        match e {
+            Expr::And(_) => {
+                if positive {
+                    clauses.extend(ls.iter().map(|l| vec![-g, *l]));
+                }
+                if negative {
+                    clauses.push(std::iter::once(g).chain(ls.iter().map(|l| -l)).collect());
+                }
+            }
+            Expr::Or(_) => {
+                if positive {
+                    clauses.push(std::iter::once(-g).chain(ls.iter().copied()).collect());
+                }
+                if negative {
+                    clauses.extend(ls.iter().map(|l| vec![g, -l]));
+                }
+            }
+            _ => {
+                let (a, b) = (ls[0], ls[1]);
+                if positive {
+                    clauses.push(vec![-g, a, b]);
+                    clauses.push(vec![-g, -a, -b]);
+                }
+                if negative {
+                    clauses.push(vec![g, -a, b]);
+                    clauses.push(vec![g, a, -b]);
+                }
+            }
+        }
+        g
+    }
+
+    pub fn finish(self) -> Cnf {
+        self.cnf
+    }
+}
@@ -1,39 +1,268 @@
-use crate::ast::nodes::Expr as astExpr;
-
-type TermName<'a> = &'a str;
-
-#[derive(PartialEq, Eq, Clone, Debug)]
-pub enum Expr<'a> {
-    Term(TermName<'a>),
-    And(Vec<Box<Expr<'a>>>),
-    Or(Vec<Box<Expr<'a>>>),
-    Xor(Vec<Box<Expr<'a>>>),
-    Implication(Box<Expr<'a>>, Box<Expr<'a>>),
-    Biconditional(Box<Expr<'a>>, Box<Expr<'a>>),
-    Not(Box<Expr<'a>>),
+//! Boolean expressions with n-ary `and`, `or` and `xor`, and the canonical
+//! form the rest of `bl` works on.
+//!
+//! In canonical form, expressions that differ only in how operands are
+//! grouped, ordered or repeated, or in where negations sit, are equal and
+//! hash equal:
+//!
+//! - `->` and `<->` are rewritten, `a -> b` as `~a | b` and `a <-> b` as
+//!   `~(a + b)`;
+//! - negations are pushed into `and` and `or` by De Morgan, and pulled out
+//!   of `xor`, so `~` only applies to variables and `xor`s;
+//! - nested operators of the same kind are flattened, and operands sorted;
+//! - repeated operands of `and` and `or` are dropped, and those of `xor`
+//!   cancel in pairs;
+//! - constants are folded away, and an operand alongside its negation
+//!   makes an `and` false and an `or` true;
+//! - an operator left with one operand is replaced by it.
+
+use crate::ast::nodes::{Expr as astExpr, ExprKind as astExprKind};
+use crate::ast::symbols::{Names, SymbolTable, VarId};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+type TermName = VarId;
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum Expr {
+    Term(TermName),
+    And(Vec<Box<Expr>>),
+    Or(Vec<Box<Expr>>),
+    Xor(Vec<Box<Expr>>),
+    Implication(Box<Expr>, Box<Expr>),
+    Biconditional(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
     True,
     False,
 }
 
-#[macro_export]
-macro_rules! matchtovec {
-    ( $x:ident, $a:ident, $b:ident ) => {
-        match ($a, $b) {
-            (astExpr::$x(box c, box d), astExpr::$x(box e, box f)) => Box::new(Expr::$x(
-                (vec![astToLogic(c), astToLogic(d), astToLogic(e), astToLogic(f)]).flatten(),
-            )),
-            (astExpr::$x(box c, box d), e) | (e, astExpr::$x(box c, box d)) => Box::new(Expr::$x(
-                vec![astToLogic(c), astToLogic(d), astToLogic(e)].flatten(),
-            )),
-            (c, d) => Box::new(Expr::$x([astToLogic(c), astToLogic(d)].flatten())),
-        }
-    };
+/// Operands are joined by their operator inside parentheses, as
+/// `ast::nodes::Expr` prints; a single operand prints alone.
+impl Names for Expr {
+    fn fmt_names(&self, symbols: &SymbolTable, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let joined = |f: &mut fmt::Formatter<'_>, es: &[&Expr], op: &str| {
+            if es.len() > 1 {
+                write!(f, "(")?;
+            }
+            for (i, e) in es.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", op)?;
+                }
+                e.fmt_names(symbols, f)?;
+            }
+            if es.len() > 1 {
+                write!(f, ")")?;
+            }
+            Ok(())
+        };
+        fn unboxed(es: &[Box<Expr>]) -> Vec<&Expr> {
+            es.iter().map(|e| &**e).collect()
+        }
+        match self {
+            Expr::Term(x) => write!(f, "{}", symbols.name(*x)),
+            Expr::True => write!(f, "𝕋"),
+            Expr::False => write!(f, "𝔽"),
+            Expr::Not(a) => {
+                write!(f, "~")?;
+                a.fmt_names(symbols, f)
+            }
+            Expr::And(es) => joined(f, &unboxed(es), "&"),
+            Expr::Or(es) => joined(f, &unboxed(es), "|"),
+            Expr::Xor(es) => joined(f, &unboxed(es), "+"),
+            Expr::Implication(a, b) => joined(f, &[a, b], "->"),
+            Expr::Biconditional(a, b) => joined(f, &[a, b], "<->"),
+        }
+    }
 }
 
-pub fn astToLogic<'a>(ae: &astExpr<'a>) -> Box<Expr<'a>> {
-    match ae {
-        astExpr::Term(a) => Box::new(Expr::Term(a)),
-        astExpr::And(a, b) => matchtovec!(And, a, b),
-        _ => Box::new(Expr::False),
+/// A negation sorts right after what it negates, so `a`, `~a`, `b`; other
+/// expressions sort by kind, then by their operands.
+impl Ord for Expr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn rank(e: &Expr) -> u8 {
+            match e {
+                Expr::True => 0,
+                Expr::False => 1,
+                Expr::Term(_) => 2,
+                Expr::And(_) => 3,
+                Expr::Or(_) => 4,
+                Expr::Xor(_) => 5,
+                Expr::Implication(..) => 6,
+                Expr::Biconditional(..) => 7,
+                Expr::Not(_) => 8,
+            }
+        }
+        match (self, other) {
+            (Expr::Not(a), Expr::Not(b)) => a.cmp(b),
+            (Expr::Not(a), b) => (**a).cmp(b).then(Ordering::Greater),
+            (a, Expr::Not(b)) => a.cmp(&**b).then(Ordering::Less),
+            (Expr::Term(a), Expr::Term(b)) => a.cmp(b),
+            (Expr::And(a), Expr::And(b))
+            | (Expr::Or(a), Expr::Or(b))
+            | (Expr::Xor(a), Expr::Xor(b)) => a.cmp(b),
+            (Expr::Implication(a, b), Expr::Implication(c, d))
+            | (Expr::Biconditional(a, b), Expr::Biconditional(c, d)) => (a, b).cmp(&(c, d)),
+            (a, b) => rank(a).cmp(&rank(b)),
+        }
+    }
+}
+
+impl PartialOrd for Expr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// `ae` in canonical form.
+#[allow(non_snake_case)]
+pub fn astToLogic(ae: &astExpr) -> Box<Expr> {
+    Box::new(lower(ae).canonical())
+}
+
+/// `ae` operator for operator, without any normalizing.
+fn lower(ae: &astExpr) -> Expr {
+    let pair = |a: &astExpr, b: &astExpr| vec![Box::new(lower(a)), Box::new(lower(b))];
+    match &ae.kind {
+        astExprKind::Term(a) => Expr::Term(*a),
+        astExprKind::True => Expr::True,
+        astExprKind::False => Expr::False,
+        astExprKind::Not(a) => Expr::Not(Box::new(lower(a))),
+        astExprKind::And(a, b) => Expr::And(pair(a, b)),
+        astExprKind::Or(a, b) => Expr::Or(pair(a, b)),
+        astExprKind::Xor(a, b) => Expr::Xor(pair(a, b)),
+        astExprKind::Implication(a, b) => Expr::Implication(Box::new(lower(a)), Box::new(lower(b))),
+        astExprKind::Biconditional(a, b) => {
+            Expr::Biconditional(Box::new(lower(a)), Box::new(lower(b)))
+        }
+    }
+}
+
+impl Expr {
+    fn not(e: Expr) -> Expr {
+        Expr::Not(Box::new(e))
+    }
+
+    /// The canonical form of `self`, described at the top of this module.
+    pub fn canonical(self) -> Expr {
+        self.normal(false)
+    }
+
+    /// The canonical form of `self`, or of `~self` if `negated`.
+    fn normal(self, negated: bool) -> Expr {
+        let all = |es: Vec<Box<Expr>>, negated: bool| -> Vec<Expr> {
+            es.into_iter().map(|e| e.normal(negated)).collect()
+        };
+        match self {
+            Expr::Term(_) if negated => Expr::not(self),
+            Expr::Term(_) => self,
+            Expr::True | Expr::False => match (self == Expr::True) != negated {
+                true => Expr::True,
+                false => Expr::False,
+            },
+            Expr::Not(a) => a.normal(!negated),
+            // ~(a & b) is ~a | ~b.
+            Expr::And(es) => Expr::lattice(!negated, all(es, negated)),
+            Expr::Or(es) => Expr::lattice(negated, all(es, negated)),
+            Expr::Implication(a, b) => Expr::Or(vec![Box::new(Expr::not(*a)), b]).normal(negated),
+            Expr::Biconditional(a, b) => Expr::Xor(vec![a, b]).normal(!negated),
+            Expr::Xor(es) => Expr::parity(negated, all(es, false)),
+        }
+    }
+
+    /// The `and` of `operands` if `and`, otherwise their `or`; they're
+    /// already canonical.
+    fn lattice(and: bool, operands: Vec<Expr>) -> Expr {
+        let (zero, unit) = match and {
+            true => (Expr::False, Expr::True),
+            false => (Expr::True, Expr::False),
+        };
+        let mut flat = vec![];
+        for e in operands {
+            match e {
+                Expr::And(es) if and => flat.extend(es.into_iter().map(|e| *e)),
+                Expr::Or(es) if !and => flat.extend(es.into_iter().map(|e| *e)),
+                e if e == unit => {}
+                e if e == zero => return zero,
+                e => flat.push(e),
+            }
+        }
+        flat.sort();
+        flat.dedup();
+        // Sorting puts `~a` right after `a`.
+        if flat.windows(2).any(|w| w[1] == Expr::not(w[0].clone())) {
+            return zero;
+        }
+        match flat.len() {
+            0 => unit,
+            1 => flat.remove(0),
+            _ => {
+                let es = flat.into_iter().map(Box::new).collect();
+                if and {
+                    Expr::And(es)
+                } else {
+                    Expr::Or(es)
+                }
+            }
+        }
+    }
+
+    /// The `xor` of `operands`, negated if `negated`; they're already
+    /// canonical.
+    fn parity(mut negated: bool, operands: Vec<Expr>) -> Expr {
+        let mut flat = vec![];
+        let mut pending = operands;
+        while let Some(e) = pending.pop() {
+            match e {
+                Expr::True => negated = !negated,
+                Expr::False => {}
+                Expr::Not(a) => {
+                    negated = !negated;
+                    pending.push(*a);
+                }
+                Expr::Xor(es) => pending.extend(es.into_iter().map(|e| *e)),
+                e => flat.push(e),
+            }
+        }
+        flat.sort();
+        // Equal operands cancel in pairs.
+        let mut kept: Vec<Expr> = vec![];
+        for e in flat {
+            if kept.last() == Some(&e) {
+                kept.pop();
+            } else {
+                kept.push(e);
+            }
+        }
+        match kept.len() {
+            0 => Expr::False.normal(negated),
+            1 => kept.remove(0).normal(negated),
+            _ => {
+                let e = Expr::Xor(kept.into_iter().map(Box::new).collect());
+                if negated {
+                    Expr::not(e)
+                } else {
+                    e
+                }
+            }
+        }
+    }
+
+    /// The value of `self` when its variables have the values in `m`, or
+    /// `None` if one of them has none.
+    pub fn evaluate(&self, m: &HashMap<VarId, bool>) -> Option<bool> {
+        let all =
+            |es: &[Box<Expr>]| -> Option<Vec<bool>> { es.iter().map(|e| e.evaluate(m)).collect() };
+        Some(match self {
+            Expr::Term(v) => *m.get(v)?,
+            Expr::True => true,
+            Expr::False => false,
+            Expr::Not(a) => !a.evaluate(m)?,
+            Expr::And(es) => all(es)?.iter().all(|v| *v),
+            Expr::Or(es) => all(es)?.iter().any(|v| *v),
+            Expr::Xor(es) => all(es)?.iter().filter(|v| **v).count() % 2 == 1,
+            Expr::Implication(a, b) => !a.evaluate(m)? || b.evaluate(m)?,
+            Expr::Biconditional(a, b) => a.evaluate(m)? == b.evaluate(m)?,
+        })
     }
 }
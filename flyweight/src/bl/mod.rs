@@ -1,3 +1,9 @@
+pub mod aig;
+pub mod aiger;
+pub mod anf;
+pub mod bdd;
+pub mod cnf;
 pub mod logic;
+pub mod sat;
 
 pub use logic::astToLogic;
@@ -0,0 +1,820 @@
+//! A conflict-driven clause-learning SAT solver.
+//!
+//! Clauses are watched by two literals each, conflicts are analysed back to
+//! their first unique implication point and the learnt clause kept, and the
+//! search restarts on the Luby sequence. Decisions follow variable activity
+//! with saved phases, starting with every variable false.
+//!
+//! The solver is incremental: clauses can be added between calls to
+//! `solve_with`, whose assumptions hold for that call only. When the
+//! assumptions can't all hold, `core` names the ones that were needed to
+//! show it. Expressions are added with the Tseitin encoding, each gate
+//! getting a variable of its own.
+
+use crate::ast::nodes::{Expr, ExprKind};
+use crate::ast::symbols::VarId;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Not;
+
+/// A variable of a `Solver`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Var(u32);
+
+impl Var {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The literal that's true when `self` is `value`.
+    pub fn lit(self, value: bool) -> Lit {
+        Lit(self.0 << 1 | !value as u32)
+    }
+}
+
+/// A variable or its negation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn var(self) -> Var {
+        Var(self.0 >> 1)
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+/// `x3` or `~x3`.
+impl fmt::Debug for Lit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_positive() { "" } else { "~" };
+        write!(f, "{}x{}", sign, self.var().0)
+    }
+}
+
+type ClauseRef = usize;
+
+struct Clause {
+    /// The first two are watched; a clause that is some variable's reason
+    /// has the literal it implied first.
+    lits: Vec<Lit>,
+    learnt: bool,
+    activity: f64,
+}
+
+#[derive(Clone, Copy)]
+struct Watcher {
+    clause: ClauseRef,
+    /// Another literal of the clause; if it's true the clause needn't be
+    /// looked at.
+    blocker: Lit,
+}
+
+/// The value of `l` under `assigns`, if its variable has one.
+fn value_of(assigns: &[Option<bool>], l: Lit) -> Option<bool> {
+    assigns[l.var().index()].map(|v| v == l.is_positive())
+}
+
+/// The `i`th term of the Luby sequence 1, 1, 2, 1, 1, 2, 4, 1, ...
+fn luby(mut i: u64) -> u64 {
+    let (mut size, mut seq) = (1, 0);
+    while size < i + 1 {
+        seq += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != i {
+        size = (size - 1) / 2;
+        seq -= 1;
+        i %= size;
+    }
+    1 << seq
+}
+
+/// Conflicts before the first restart; later ones are this times `luby`.
+const RESTART_BASE: u64 = 100;
+const VAR_DECAY: f64 = 0.95;
+const CLAUSE_DECAY: f64 = 0.999;
+
+pub struct Solver {
+    /// Indexed by `ClauseRef`; deleted learnt clauses leave a `None` that
+    /// the next learnt clause reuses.
+    clauses: Vec<Option<Clause>>,
+    free_refs: Vec<ClauseRef>,
+    /// The clauses watching each literal, by `Lit::index`.
+    watches: Vec<Vec<Watcher>>,
+    assigns: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<ClauseRef>>,
+    /// The value each variable last had, which it's given when next decided.
+    phases: Vec<bool>,
+    trail: Vec<Lit>,
+    /// Where on the trail each decision level starts.
+    trail_lim: Vec<usize>,
+    /// How much of the trail has been propagated.
+    propagated: usize,
+    activity: Vec<f64>,
+    var_inc: f64,
+    clause_inc: f64,
+    order: VarOrder,
+    seen: Vec<bool>,
+    learnts: usize,
+    max_learnts: f64,
+    /// False once the clauses are unsatisfiable with no assumptions.
+    ok: bool,
+    model: Vec<bool>,
+    core: Vec<Lit>,
+    truth: Option<Lit>,
+    conflicts: u64,
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver {
+            clauses: vec![],
+            free_refs: vec![],
+            watches: vec![],
+            assigns: vec![],
+            levels: vec![],
+            reasons: vec![],
+            phases: vec![],
+            trail: vec![],
+            trail_lim: vec![],
+            propagated: 0,
+            activity: vec![],
+            var_inc: 1.0,
+            clause_inc: 1.0,
+            order: VarOrder::default(),
+            seen: vec![],
+            learnts: 0,
+            max_learnts: 1000.0,
+            ok: true,
+            model: vec![],
+            core: vec![],
+            truth: None,
+            conflicts: 0,
+        }
+    }
+
+    pub fn new_var(&mut self) -> Var {
+        let v = Var(self.assigns.len() as u32);
+        self.watches.push(vec![]);
+        self.watches.push(vec![]);
+        self.assigns.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.phases.push(false);
+        self.activity.push(0.0);
+        self.seen.push(false);
+        self.order.insert(v, &self.activity);
+        v
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.assigns.len()
+    }
+
+    /// Conflicts met over every call to `solve_with`.
+    pub fn conflicts(&self) -> u64 {
+        self.conflicts
+    }
+
+    fn value(&self, l: Lit) -> Option<bool> {
+        value_of(&self.assigns, l)
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    /// Adds `lits` as a clause. Returns false if the clauses have become
+    /// unsatisfiable, as they stay from then on.
+    pub fn add_clause(&mut self, lits: &[Lit]) -> bool {
+        if !self.ok {
+            return false;
+        }
+        self.backtrack(0);
+        let mut lits = lits.to_vec();
+        lits.sort();
+        lits.dedup();
+        let tautology = lits.windows(2).any(|w| w[0] == !w[1]);
+        if tautology || lits.iter().any(|l| self.value(*l) == Some(true)) {
+            return true;
+        }
+        lits.retain(|l| self.value(*l).is_none());
+        match lits.len() {
+            0 => self.ok = false,
+            1 => {
+                self.assign(lits[0], None);
+                self.ok = self.propagate().is_none();
+            }
+            _ => {
+                self.attach(Clause {
+                    lits,
+                    learnt: false,
+                    activity: 0.0,
+                });
+            }
+        }
+        self.ok
+    }
+
+    fn attach(&mut self, clause: Clause) -> ClauseRef {
+        let (a, b) = (clause.lits[0], clause.lits[1]);
+        let r = match self.free_refs.pop() {
+            Some(r) => {
+                self.clauses[r] = Some(clause);
+                r
+            }
+            None => {
+                self.clauses.push(Some(clause));
+                self.clauses.len() - 1
+            }
+        };
+        self.watches[a.index()].push(Watcher {
+            clause: r,
+            blocker: b,
+        });
+        self.watches[b.index()].push(Watcher {
+            clause: r,
+            blocker: a,
+        });
+        r
+    }
+
+    fn detach(&mut self, r: ClauseRef) {
+        let clause = self.clauses[r].take().unwrap();
+        for l in &clause.lits[..2] {
+            self.watches[l.index()].retain(|w| w.clause != r);
+        }
+        if clause.learnt {
+            self.learnts -= 1;
+        }
+        self.free_refs.push(r);
+    }
+
+    fn assign(&mut self, l: Lit, reason: Option<ClauseRef>) {
+        let v = l.var().index();
+        self.assigns[v] = Some(l.is_positive());
+        self.levels[v] = self.decision_level();
+        self.reasons[v] = reason;
+        self.trail.push(l);
+    }
+
+    /// Propagates the trail's new assignments through the watches, and
+    /// returns the clause that became false if one did.
+    fn propagate(&mut self) -> Option<ClauseRef> {
+        while self.propagated < self.trail.len() {
+            let falsified = !self.trail[self.propagated];
+            self.propagated += 1;
+            let mut watchers = std::mem::take(&mut self.watches[falsified.index()]);
+            let mut kept = 0;
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watchers.len() {
+                let w = watchers[i];
+                i += 1;
+                if self.value(w.blocker) == Some(true) {
+                    watchers[kept] = w;
+                    kept += 1;
+                    continue;
+                }
+                let clause = self.clauses[w.clause].as_mut().unwrap();
+                if clause.lits[0] == falsified {
+                    clause.lits.swap(0, 1);
+                }
+                let first = clause.lits[0];
+                if first != w.blocker && value_of(&self.assigns, first) == Some(true) {
+                    watchers[kept] = Watcher {
+                        clause: w.clause,
+                        blocker: first,
+                    };
+                    kept += 1;
+                    continue;
+                }
+                let assigns = &self.assigns;
+                let replacement = (2..clause.lits.len())
+                    .find(|k| value_of(assigns, clause.lits[*k]) != Some(false));
+                if let Some(k) = replacement {
+                    clause.lits.swap(1, k);
+                    let watched = clause.lits[1];
+                    self.watches[watched.index()].push(Watcher {
+                        clause: w.clause,
+                        blocker: first,
+                    });
+                    continue;
+                }
+                watchers[kept] = w;
+                kept += 1;
+                if self.value(first) == Some(false) {
+                    conflict = Some(w.clause);
+                    while i < watchers.len() {
+                        watchers[kept] = watchers[i];
+                        kept += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.assign(first, Some(w.clause));
+                }
+            }
+            watchers.truncate(kept);
+            self.watches[falsified.index()] = watchers;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Undoes every decision level above `level`.
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        let start = self.trail_lim[level];
+        for l in self.trail.drain(start..).rev() {
+            let v = l.var();
+            self.assigns[v.index()] = None;
+            self.reasons[v.index()] = None;
+            self.phases[v.index()] = l.is_positive();
+            self.order.insert(v, &self.activity);
+        }
+        self.trail_lim.truncate(level);
+        self.propagated = start;
+    }
+
+    fn bump_var(&mut self, v: Var) {
+        self.activity[v.index()] += self.var_inc;
+        if self.activity[v.index()] > 1e100 {
+            for a in &mut self.activity {
+                *a *= 1e-100;
+            }
+            self.var_inc *= 1e-100;
+        }
+        self.order.increased(v, &self.activity);
+    }
+
+    fn bump_clause(&mut self, r: ClauseRef) {
+        let clause = self.clauses[r].as_mut().unwrap();
+        if !clause.learnt {
+            return;
+        }
+        clause.activity += self.clause_inc;
+        if clause.activity > 1e20 {
+            for c in self.clauses.iter_mut().flatten() {
+                c.activity *= 1e-20;
+            }
+            self.clause_inc *= 1e-20;
+        }
+    }
+
+    /// Learns a clause from `conflict` that has exactly one literal from the
+    /// current decision level, which comes first, and returns it with the
+    /// level to jump back to.
+    fn analyze(&mut self, mut conflict: ClauseRef) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut next = self.trail.len();
+        let mut implied: Option<Lit> = None;
+        loop {
+            self.bump_clause(conflict);
+            let skip = implied.is_some() as usize;
+            let lits = self.clauses[conflict].as_ref().unwrap().lits.clone();
+            for q in &lits[skip..] {
+                let v = q.var();
+                if !self.seen[v.index()] && self.levels[v.index()] > 0 {
+                    self.seen[v.index()] = true;
+                    self.bump_var(v);
+                    if self.levels[v.index()] == self.decision_level() {
+                        pending += 1;
+                    } else {
+                        learnt.push(*q);
+                    }
+                }
+            }
+            let p = loop {
+                next -= 1;
+                if self.seen[self.trail[next].var().index()] {
+                    break self.trail[next];
+                }
+            };
+            self.seen[p.var().index()] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !p;
+                break;
+            }
+            implied = Some(p);
+            conflict = self.reasons[p.var().index()].unwrap();
+        }
+
+        // Drop literals implied by the others.
+        let redundant: Vec<bool> = learnt[1..]
+            .iter()
+            .map(|q| match self.reasons[q.var().index()] {
+                None => false,
+                Some(r) => self.clauses[r].as_ref().unwrap().lits[1..]
+                    .iter()
+                    .all(|l| self.seen[l.var().index()] || self.levels[l.var().index()] == 0),
+            })
+            .collect();
+        for q in &learnt[1..] {
+            self.seen[q.var().index()] = false;
+        }
+        let mut learnt: Vec<Lit> = std::iter::once(learnt[0])
+            .chain(
+                learnt[1..]
+                    .iter()
+                    .zip(&redundant)
+                    .filter(|(_, redundant)| !**redundant)
+                    .map(|(l, _)| *l),
+            )
+            .collect();
+
+        // The second literal is the one from the highest remaining level,
+        // so that it's watched and the clause becomes unit on backtracking.
+        let mut level = 0;
+        if learnt.len() > 1 {
+            let i = (1..learnt.len())
+                .max_by_key(|i| self.levels[learnt[*i].var().index()])
+                .unwrap();
+            learnt.swap(1, i);
+            level = self.levels[learnt[1].var().index()];
+        }
+        (learnt, level)
+    }
+
+    /// The assumptions that, with the clauses, imply `!failed`, where
+    /// `failed` is an assumption that turned out false.
+    fn analyze_final(&mut self, failed: Lit) {
+        self.core = vec![failed];
+        if self.decision_level() == 0 {
+            return;
+        }
+        self.seen[failed.var().index()] = true;
+        for i in (self.trail_lim[0]..self.trail.len()).rev() {
+            let l = self.trail[i];
+            let v = l.var().index();
+            if !self.seen[v] {
+                continue;
+            }
+            match self.reasons[v] {
+                // Only assumptions have been decided so far.
+                None => self.core.push(l),
+                Some(r) => {
+                    for q in &self.clauses[r].as_ref().unwrap().lits[1..] {
+                        if self.levels[q.var().index()] > 0 {
+                            self.seen[q.var().index()] = true;
+                        }
+                    }
+                }
+            }
+            self.seen[v] = false;
+        }
+        self.seen[failed.var().index()] = false;
+    }
+
+    /// Deletes the less active half of the learnt clauses, other than those
+    /// that are the reason for a current assignment.
+    fn reduce_learnts(&mut self) {
+        let locked = |s: &Solver, r: ClauseRef| {
+            let first = s.clauses[r].as_ref().unwrap().lits[0];
+            s.reasons[first.var().index()] == Some(r) && s.value(first) == Some(true)
+        };
+        let mut learnt: Vec<(f64, ClauseRef)> = self
+            .clauses
+            .iter()
+            .enumerate()
+            .filter_map(|(r, c)| c.as_ref().map(|c| (r, c)))
+            .filter(|(_, c)| c.learnt && c.lits.len() > 2)
+            .map(|(r, c)| (c.activity, r))
+            .collect();
+        learnt.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, r) in &learnt[..learnt.len() / 2] {
+            if !locked(self, *r) {
+                self.detach(*r);
+            }
+        }
+    }
+
+    fn pick_branch(&mut self) -> Option<Lit> {
+        while let Some(v) = self.order.pop(&self.activity) {
+            if self.assigns[v.index()].is_none() {
+                return Some(v.lit(self.phases[v.index()]));
+            }
+        }
+        None
+    }
+
+    /// Searches until a model is found, the assumptions fail, the clauses
+    /// are shown unsatisfiable, or `budget` conflicts have passed.
+    fn search(&mut self, assumptions: &[Lit], budget: u64) -> Option<bool> {
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.conflicts += 1;
+                conflicts += 1;
+                if self.decision_level() == 0 {
+                    self.ok = false;
+                    return Some(false);
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learnt.len() == 1 {
+                    self.assign(learnt[0], None);
+                } else {
+                    let first = learnt[0];
+                    let r = self.attach(Clause {
+                        lits: learnt,
+                        learnt: true,
+                        activity: 0.0,
+                    });
+                    self.learnts += 1;
+                    self.bump_clause(r);
+                    self.assign(first, Some(r));
+                }
+                self.var_inc /= VAR_DECAY;
+                self.clause_inc /= CLAUSE_DECAY;
+                continue;
+            }
+            if conflicts >= budget {
+                self.backtrack(0);
+                return None;
+            }
+            if self.learnts as f64 - self.trail.len() as f64 >= self.max_learnts {
+                self.reduce_learnts();
+            }
+            let mut decision = None;
+            while self.decision_level() < assumptions.len() {
+                let a = assumptions[self.decision_level()];
+                match self.value(a) {
+                    Some(true) => self.trail_lim.push(self.trail.len()),
+                    Some(false) => {
+                        self.analyze_final(a);
+                        return Some(false);
+                    }
+                    None => {
+                        decision = Some(a);
+                        break;
+                    }
+                }
+            }
+            let decision = match decision.or_else(|| self.pick_branch()) {
+                Some(d) => d,
+                None => return Some(true),
+            };
+            self.trail_lim.push(self.trail.len());
+            self.assign(decision, None);
+        }
+    }
+
+    /// Whether the clauses are satisfiable.
+    pub fn solve(&mut self) -> bool {
+        self.solve_with(&[])
+    }
+
+    /// Whether the clauses are satisfiable with every literal of
+    /// `assumptions` true. If they are, `value` reads the model found;
+    /// if not, `core` holds the assumptions that were needed to show it.
+    pub fn solve_with(&mut self, assumptions: &[Lit]) -> bool {
+        self.core.clear();
+        if !self.ok {
+            return false;
+        }
+        self.max_learnts = self.max_learnts.max(self.clauses.len() as f64 / 3.0);
+        let mut restarts = 0;
+        let sat = loop {
+            if let Some(sat) = self.search(assumptions, RESTART_BASE * luby(restarts)) {
+                break sat;
+            }
+            restarts += 1;
+            self.max_learnts *= 1.05;
+        };
+        if sat {
+            self.model = self.assigns.iter().map(|v| v.unwrap()).collect();
+        }
+        self.backtrack(0);
+        sat
+    }
+
+    /// `l`'s value in the last model found. Variables made since are false.
+    pub fn model_value(&self, l: Lit) -> bool {
+        self.model.get(l.var().index()).copied().unwrap_or(false) == l.is_positive()
+    }
+
+    /// The assumptions the last unsatisfiable `solve_with` needed, empty if
+    /// the clauses are unsatisfiable without any.
+    pub fn core(&self) -> &[Lit] {
+        &self.core
+    }
+
+    /// If the clauses are unsatisfiable with all of `fixed` and
+    /// `assumptions` true, the positions in `assumptions` of a subset that
+    /// is still enough, none of which can be dropped.
+    pub fn minimize(&mut self, fixed: &[Lit], assumptions: &[Lit]) -> Option<Vec<usize>> {
+        // The part of `keep` the solver needed, if it's still enough.
+        let needed = |s: &mut Solver, keep: &[usize]| {
+            let mut all = fixed.to_vec();
+            all.extend(keep.iter().map(|i| assumptions[*i]));
+            match s.solve_with(&all) {
+                true => None,
+                false => Some(
+                    keep.iter()
+                        .copied()
+                        .filter(|i| s.core.contains(&assumptions[*i]))
+                        .collect::<Vec<usize>>(),
+                ),
+            }
+        };
+        let all: Vec<usize> = (0..assumptions.len()).collect();
+        let mut keep = needed(self, &all)?;
+        // Those before `i` have each been found necessary, so any smaller
+        // set that's still enough keeps them.
+        let mut i = 0;
+        while i < keep.len() {
+            let mut without = keep.clone();
+            without.remove(i);
+            match needed(self, &without) {
+                Some(smaller) => keep = smaller,
+                None => i += 1,
+            }
+        }
+        Some(keep)
+    }
+
+    /// A literal that's always `value`.
+    pub fn constant(&mut self, value: bool) -> Lit {
+        let t = match self.truth {
+            Some(t) => t,
+            None => {
+                let t = self.new_var().lit(true);
+                self.add_clause(&[t]);
+                self.truth = Some(t);
+                t
+            }
+        };
+        if value {
+            t
+        } else {
+            !t
+        }
+    }
+
+    /// A new literal that's true exactly when all of `lits` are.
+    pub fn and(&mut self, lits: &[Lit]) -> Lit {
+        let g = self.new_var().lit(true);
+        let mut all = vec![g];
+        for l in lits {
+            self.add_clause(&[!g, *l]);
+            all.push(!*l);
+        }
+        self.add_clause(&all);
+        g
+    }
+
+    /// A new literal that's true exactly when one of `lits` is.
+    pub fn or(&mut self, lits: &[Lit]) -> Lit {
+        let negated: Vec<Lit> = lits.iter().map(|l| !*l).collect();
+        !self.and(&negated)
+    }
+
+    pub fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        let g = self.new_var().lit(true);
+        self.add_clause(&[!g, a, b]);
+        self.add_clause(&[!g, !a, !b]);
+        self.add_clause(&[g, !a, b]);
+        self.add_clause(&[g, a, !b]);
+        g
+    }
+
+    /// A literal that's true exactly when `e` is, with its variables given
+    /// the literals in `vars`; variables not in it get new ones.
+    pub fn expr(&mut self, e: &Expr, vars: &mut HashMap<VarId, Lit>) -> Lit {
+        match &e.kind {
+            ExprKind::True => self.constant(true),
+            ExprKind::False => self.constant(false),
+            ExprKind::Term(v) => match vars.get(v) {
+                Some(l) => *l,
+                None => {
+                    let l = self.new_var().lit(true);
+                    vars.insert(*v, l);
+                    l
+                }
+            },
+            ExprKind::Not(a) => !self.expr(a, vars),
+            ExprKind::And(a, b) => {
+                let (a, b) = (self.expr(a, vars), self.expr(b, vars));
+                self.and(&[a, b])
+            }
+            ExprKind::Or(a, b) => {
+                let (a, b) = (self.expr(a, vars), self.expr(b, vars));
+                self.or(&[a, b])
+            }
+            ExprKind::Implication(a, b) => {
+                let (a, b) = (self.expr(a, vars), self.expr(b, vars));
+                self.or(&[!a, b])
+            }
+            ExprKind::Xor(a, b) => {
+                let (a, b) = (self.expr(a, vars), self.expr(b, vars));
+                self.xor(a, b)
+            }
+            ExprKind::Biconditional(a, b) => {
+                let (a, b) = (self.expr(a, vars), self.expr(b, vars));
+                !self.xor(a, b)
+            }
+        }
+    }
+}
+
+/// The unassigned variables, most active first, as a binary heap.
+#[derive(Default)]
+struct VarOrder {
+    heap: Vec<Var>,
+    /// Each variable's index in `heap`, if it's there.
+    positions: Vec<Option<usize>>,
+}
+
+impl VarOrder {
+    fn insert(&mut self, v: Var, activity: &[f64]) {
+        if self.positions.len() <= v.index() {
+            self.positions.resize(v.index() + 1, None);
+        }
+        if self.positions[v.index()].is_some() {
+            return;
+        }
+        self.heap.push(v);
+        self.positions[v.index()] = Some(self.heap.len() - 1);
+        self.up(self.heap.len() - 1, activity);
+    }
+
+    fn increased(&mut self, v: Var, activity: &[f64]) {
+        if let Some(i) = self.positions[v.index()] {
+            self.up(i, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<Var> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.positions[top.index()] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.positions[last.index()] = Some(0);
+            self.down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        self.positions[self.heap[i].index()] = Some(i);
+        self.positions[self.heap[j].index()] = Some(j);
+    }
+
+    fn up(&mut self, mut i: usize, activity: &[f64]) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent].index()] >= activity[self.heap[i].index()] {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn down(&mut self, mut i: usize, activity: &[f64]) {
+        loop {
+            let a = |j: usize| activity[self.heap[j].index()];
+            let mut largest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.heap.len() && a(child) > a(largest) {
+                    largest = child;
+                }
+            }
+            if largest == i {
+                return;
+            }
+            self.swap(i, largest);
+            i = largest;
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Rendering of errors and warnings against the source they came from, in
+//! the same shape rustc uses:
+//!
+//! ```text
+//! error: unexpected `)`
+//!   --> sample.flyweightkeen4:12:12
+//!    |
+//! 12 |   A_occ -> )
+//!    |            ^ expected `(`, `false`, `true`, `~` or an identifier
+//! ```
+
+use crate::ast::span::Span;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A span with an optional message. Primary labels are underlined with `^`,
+/// secondary ones with `-`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Report {
+    pub fn error<S: Into<String>>(message: S) -> Report {
+        Report {
+            severity: Severity::Error,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+        }
+    }
+
+    pub fn warning<S: Into<String>>(message: S) -> Report {
+        Report {
+            severity: Severity::Warning,
+            ..Report::error(message)
+        }
+    }
+
+    pub fn with_label<S: Into<String>>(mut self, span: Span, message: S) -> Report {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary_label<S: Into<String>>(mut self, span: Span, message: S) -> Report {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note<S: Into<String>>(mut self, note: S) -> Report {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_notes<I, S>(self, notes: I) -> Report
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        notes.into_iter().fold(self, |r, n| r.with_note(n))
+    }
+
+    /// Where the report points: its first primary label, if any.
+    pub fn span(&self) -> Option<Span> {
+        self.labels
+            .iter()
+            .find(|l| l.primary)
+            .or_else(|| self.labels.first())
+            .map(|l| l.span)
+    }
+}
+
+/// A named piece of source text, with the line starts precomputed so spans
+/// can be turned into line and column numbers. A file loaded into a
+/// `SourceMap` starts at `base`, and spans into it are offset by that much.
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    pub base: usize,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new<N: Into<String>, T: Into<String>>(name: N, text: T) -> SourceFile {
+        let text = text.into();
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile {
+            name: name.into(),
+            text,
+            base: 0,
+            line_starts,
+        }
+    }
+
+    /// Whether `offset` falls in this file (its end included, for spans
+    /// at the end of the file).
+    pub fn contains(&self, offset: usize) -> bool {
+        self.base <= offset && offset <= self.base + self.text.len()
+    }
+
+    /// Zero-based line index containing byte `offset` of the text.
+    fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        }
+    }
+
+    /// One-based line and column (in characters) of byte `offset`.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let mut offset = offset.saturating_sub(self.base).min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_index(offset);
+        let col = self.text[self.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    /// `name:line:col` of byte `offset`.
+    pub fn location(&self, offset: usize) -> String {
+        let (line, col) = self.line_col(offset);
+        format!("{}:{}:{}", self.name, line, col)
+    }
+
+    /// The text of the one-based line `line`, without its line ending.
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map(|e| e - 1)
+            .unwrap_or_else(|| self.text.len());
+        self.text[start..end].trim_end_matches('\r')
+    }
+
+    pub fn render(&self, report: &Report) -> String {
+        let labels: Vec<&Label> = report.labels.iter().collect();
+        let gutter = self.gutter(&labels);
+        let mut out = format!("{}: {}\n", report.severity, report.message);
+        if let Some(span) = report.span() {
+            out += &self.snippet(&labels, span, "-->", gutter);
+        }
+        out + &render_notes(report, gutter)
+    }
+
+    /// Width of the line numbers `labels` will print.
+    fn gutter(&self, labels: &[&Label]) -> usize {
+        labels
+            .iter()
+            .map(|l| self.line_col(l.span.start).0.to_string().len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The lines `labels` point at, underlined, headed by `arrow` and the
+    /// location of `at`.
+    fn snippet(&self, labels: &[&Label], at: Span, arrow: &str, gutter: usize) -> String {
+        let mut labels = labels.to_vec();
+        labels.sort_by_key(|l| (l.span.start, !l.primary));
+        let pad = " ".repeat(gutter);
+
+        let mut out = format!("{}{} {}\n", pad, arrow, self.location(at.start));
+        out += &format!("{} |\n", pad);
+        let mut last_line = None;
+        for label in labels {
+            let (line, col) = self.line_col(label.span.start);
+            let text = self.line(line);
+            // Labels on the same line share one copy of it.
+            if last_line != Some(line) {
+                if last_line.is_some_and(|l| l + 1 < line) {
+                    out += "...\n";
+                }
+                out += &format!("{:>w$} | {}\n", line, text, w = gutter);
+                last_line = Some(line);
+            }
+            // Spans running past the end of the line are underlined to the
+            // end of it; empty spans (e.g. end of file) still get one mark.
+            let (end_line, end_col) = self.line_col(label.span.end);
+            let end_col = if end_line == line {
+                end_col
+            } else {
+                text.chars().count() + 1
+            };
+            let width = end_col.saturating_sub(col).max(1);
+            let mark = if label.primary { "^" } else { "-" };
+            out += &format!("{} | {}{}", pad, " ".repeat(col - 1), mark.repeat(width));
+            if !label.message.is_empty() {
+                out += &format!(" {}", label.message);
+            }
+            out += "\n";
+        }
+        out
+    }
+}
+
+fn render_notes(report: &Report, gutter: usize) -> String {
+    let pad = " ".repeat(gutter);
+    report
+        .notes
+        .iter()
+        .map(|note| format!("{} = note: {}\n", pad, note))
+        .collect()
+}
+
+/// Every file of a program that spans several, laid end to end so a `Span`
+/// alone says which file it is in.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    /// Adds a file, returning its `base`. Spans parsed from `text` must be
+    /// offset by it.
+    pub fn add<N: Into<String>, T: Into<String>>(&mut self, name: N, text: T) -> usize {
+        // Leave a gap so one file's end-of-file span isn't the next one's
+        // first character.
+        let base = self
+            .files
+            .last()
+            .map(|f| f.base + f.text.len() + 1)
+            .unwrap_or(0);
+        self.files.push(SourceFile {
+            base,
+            ..SourceFile::new(name, text)
+        });
+        base
+    }
+
+    pub fn file(&self, offset: usize) -> Option<&SourceFile> {
+        self.files.iter().find(|f| f.contains(offset))
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &SourceFile> {
+        self.files.iter()
+    }
+
+    /// `name:line:col` of byte `offset`.
+    pub fn location(&self, offset: usize) -> String {
+        self.file(offset)
+            .map(|f| f.location(offset))
+            .unwrap_or_else(|| "<unknown>".to_string())
+    }
+
+    /// Renders `report` like `SourceFile::render`. Labels in other files
+    /// than the primary one follow it, each headed by `:::` and its location.
+    pub fn render(&self, report: &Report) -> String {
+        let mut out = format!("{}: {}\n", report.severity, report.message);
+        let mut by_file: Vec<(&SourceFile, Vec<&Label>)> = vec![];
+        for label in &report.labels {
+            let file = match self.file(label.span.start) {
+                Some(file) => file,
+                None => continue,
+            };
+            match by_file.iter_mut().find(|(f, _)| f.base == file.base) {
+                Some((_, labels)) => labels.push(label),
+                None => by_file.push((file, vec![label])),
+            }
+        }
+        let primary = report.span().and_then(|s| self.file(s.start));
+        by_file.sort_by_key(|(f, _)| Some(f.base) != primary.map(|p| p.base));
+
+        let gutter = by_file
+            .iter()
+            .map(|(f, labels)| f.gutter(labels))
+            .max()
+            .unwrap_or(0);
+        for (i, (file, labels)) in by_file.iter().enumerate() {
+            let (arrow, at) = match (i, report.span()) {
+                (0, Some(span)) => ("-->", span),
+                _ => (":::", labels.iter().map(|l| l.span).min().unwrap()),
+            };
+            out += &file.snippet(labels, at, arrow, gutter);
+        }
+        out + &render_notes(report, gutter)
+    }
+}
@@ -0,0 +1,302 @@
+//! The canonical layout of a Flyweight file, as printed by `keen4 fmt`.
+//!
+//! Items and the lines of blocks keep their order, comments and single blank
+//! lines between them; everything else is normalized. Blocks are indented
+//! by two spaces, lists are separated by `, `, declarations use `<-`, and
+//! expressions get only the parentheses the grammar's precedence needs, so
+//! the output parses back to the same program.
+//!
+//! The lexer drops comments, so they are found again by scanning the source
+//! and placed by their offsets relative to the spans of the parsed items.
+
+use crate::ast::nodes::{
+    Constraints, Expr, ExprKind, Ident, Item, Program, Statement, StatementKind, Value,
+};
+use crate::ast::span::Span;
+use crate::ast::symbols::SymbolTable;
+
+const INDENT: &str = "  ";
+
+/// Formats `program`, parsed from `src` with `symbols`.
+pub fn format_program(program: &Program, src: &str, symbols: &SymbolTable) -> String {
+    let mut f = Formatter {
+        src,
+        symbols,
+        comments: comments(src),
+        next_comment: 0,
+        last: 0,
+        fresh: true,
+        out: String::new(),
+    };
+    for item in &program.items {
+        f.item(item);
+    }
+    f.leading(src.len(), 0);
+    f.out
+}
+
+/// Formats a single expression with minimal parentheses.
+pub fn format_expr(e: &Expr, symbols: &SymbolTable) -> String {
+    let mut out = String::new();
+    write_expr(&mut out, e, symbols, Prec::Biconditional);
+    out
+}
+
+/// Binding strength, loosest first.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Biconditional,
+    Implication,
+    Or,
+    Xor,
+    And,
+    Term,
+}
+
+impl Prec {
+    fn of(kind: &ExprKind) -> Prec {
+        match kind {
+            ExprKind::Biconditional(..) => Prec::Biconditional,
+            ExprKind::Implication(..) => Prec::Implication,
+            ExprKind::Or(..) => Prec::Or,
+            ExprKind::Xor(..) => Prec::Xor,
+            ExprKind::And(..) => Prec::And,
+            ExprKind::Term(_) | ExprKind::True | ExprKind::False | ExprKind::Not(_) => Prec::Term,
+        }
+    }
+
+    fn tighter(self) -> Prec {
+        match self {
+            Prec::Biconditional => Prec::Implication,
+            Prec::Implication => Prec::Or,
+            Prec::Or => Prec::Xor,
+            Prec::Xor => Prec::And,
+            Prec::And | Prec::Term => Prec::Term,
+        }
+    }
+}
+
+/// Writes `e` where the grammar expects something binding at least as
+/// tightly as `min`, parenthesizing it if it doesn't.
+fn write_expr(out: &mut String, e: &Expr, symbols: &SymbolTable, min: Prec) {
+    let prec = Prec::of(&e.kind);
+    if prec < min {
+        out.push('(');
+        write_expr(out, e, symbols, Prec::Biconditional);
+        out.push(')');
+        return;
+    }
+    let (a, op, b) = match &e.kind {
+        ExprKind::Term(id) => return out.push_str(symbols.name(*id)),
+        ExprKind::True => return out.push_str("true"),
+        ExprKind::False => return out.push_str("false"),
+        ExprKind::Not(a) => {
+            out.push('~');
+            return write_expr(out, a, symbols, Prec::Term);
+        }
+        ExprKind::Biconditional(a, b) => (a, "<->", b),
+        ExprKind::Implication(a, b) => (a, "->", b),
+        ExprKind::Or(a, b) => (a, "|", b),
+        ExprKind::Xor(a, b) => (a, "+", b),
+        ExprKind::And(a, b) => (a, "&", b),
+    };
+    // `->` associates to the right, everything else to the left.
+    let (left, right) = match e.kind {
+        ExprKind::Implication(..) => (prec.tighter(), prec),
+        _ => (prec, prec.tighter()),
+    };
+    write_expr(out, a, symbols, left);
+    out.push_str(&format!(" {} ", op));
+    write_expr(out, b, symbols, right);
+}
+
+/// Spans of the `#` comments in `src`, each to the end of its line.
+fn comments(src: &str) -> Vec<Span> {
+    let mut out = vec![];
+    let mut chars = src.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // Import paths may contain `#`.
+            '"' => {
+                while let Some((_, c)) = chars.next_if(|&(_, c)| c != '\n') {
+                    if c == '"' {
+                        break;
+                    }
+                }
+            }
+            '#' => {
+                let end = src[i..].find('\n').map(|n| i + n).unwrap_or(src.len());
+                out.push(Span::new(i, end));
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+            }
+            _ => (),
+        }
+    }
+    out
+}
+
+struct Formatter<'s> {
+    src: &'s str,
+    symbols: &'s SymbolTable,
+    comments: Vec<Span>,
+    next_comment: usize,
+    /// Where in the source the last thing written ended.
+    last: usize,
+    /// Nothing has been written in the current block yet, so a blank line
+    /// from the source isn't kept.
+    fresh: bool,
+    out: String,
+}
+
+impl<'s> Formatter<'s> {
+    fn name(&self, i: &Ident) -> &str {
+        self.symbols.name(i.id)
+    }
+
+    fn names(&self, idents: &[Ident]) -> String {
+        idents
+            .iter()
+            .map(|i| self.name(i))
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+
+    fn expr(&self, e: &Expr) -> String {
+        format_expr(e, self.symbols)
+    }
+
+    fn comment_text(&self, c: Span) -> &'s str {
+        self.src[c.start..c.end].trim_end()
+    }
+
+    /// Keeps one blank line if the source had any before `start`.
+    fn blank_line(&mut self, start: usize) {
+        let gap = &self.src[self.last.min(start)..start];
+        let blank = gap
+            .split('\n')
+            .skip(1)
+            .take(gap.matches('\n').count().saturating_sub(1))
+            .any(|l| l.trim().is_empty());
+        if blank && !self.fresh {
+            self.out.push('\n');
+        }
+    }
+
+    /// Writes the comments before `before` on lines of their own.
+    fn leading(&mut self, before: usize, depth: usize) {
+        while let Some(&c) = self.comments.get(self.next_comment) {
+            if c.start >= before {
+                break;
+            }
+            self.blank_line(c.start);
+            let text = self.comment_text(c);
+            self.out += &format!("{}{}\n", INDENT.repeat(depth), text);
+            self.next_comment += 1;
+            self.last = c.end;
+            self.fresh = false;
+        }
+    }
+
+    /// Writes `text` as a line starting at source offset `at`, after the
+    /// comments before it and followed by any comment on the same line.
+    fn line(&mut self, depth: usize, text: &str, at: usize, end: usize) {
+        self.leading(at, depth);
+        self.blank_line(at);
+        self.out += &INDENT.repeat(depth);
+        self.out += text;
+        let line_end = self.src[at..]
+            .find('\n')
+            .map(|n| at + n)
+            .unwrap_or(self.src.len());
+        self.last = end.max(at);
+        if let Some(&c) = self.comments.get(self.next_comment) {
+            if c.start < line_end {
+                self.out += " ";
+                self.out += self.comment_text(c);
+                self.next_comment += 1;
+                self.last = self.last.max(c.end);
+            }
+        }
+        self.out.push('\n');
+        self.fresh = false;
+    }
+
+    /// Writes a block's `header {` line; its lines follow, then `close`.
+    fn open(&mut self, depth: usize, header: &str, span: Span) {
+        self.line(depth, &format!("{} {{", header), span.start, span.start);
+        self.fresh = true;
+    }
+
+    fn close(&mut self, depth: usize, span: Span) {
+        let brace = span.end - 1;
+        self.leading(brace, depth + 1);
+        self.fresh = true;
+        self.line(depth, "}", brace, span.end);
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Import(i) => {
+                let text = format!("import \"{}\"", i.path);
+                self.line(0, &text, i.span.start, i.span.end);
+            }
+            Item::Declaration(d) => {
+                let text = format!("{} <- {}", self.name(&d.name), d.value);
+                self.line(0, &text, d.span.start, d.span.end);
+            }
+            Item::Constraints(c) => self.constraints(c),
+            Item::Run(r) => {
+                self.open(0, "run", r.span);
+                for s in &r.statements {
+                    self.statement(1, s);
+                }
+                self.close(0, r.span);
+            }
+        }
+    }
+
+    fn constraints(&mut self, c: &Constraints) {
+        let mut params = vec![];
+        if !c.free.is_empty() {
+            params.push(format!("free={}", self.names(&c.free)));
+        }
+        if !c.bound.is_empty() {
+            params.push(format!("bound={}", self.names(&c.bound)));
+        }
+        let header = format!("constraints({})", params.join("; "));
+        self.open(0, &header, c.span);
+        for e in &c.exprs {
+            let text = self.expr(e);
+            self.line(1, &text, e.span.start, e.span.end);
+        }
+        self.close(0, c.span);
+    }
+
+    fn statement(&mut self, depth: usize, s: &Statement) {
+        match &s.kind {
+            StatementKind::Assign { targets, value } => {
+                let lhs = self.names(targets);
+                match value {
+                    Value::Input(_) => {
+                        self.line(depth, &format!("{} <- IN", lhs), s.span.start, s.span.end)
+                    }
+                    Value::Expr(e) => {
+                        let text = format!("{} <- {}", lhs, self.expr(e));
+                        self.line(depth, &text, s.span.start, s.span.end);
+                    }
+                    Value::Transaction(body, _) => {
+                        self.open(depth, &format!("{} <- transaction", lhs), s.span);
+                        for s in body {
+                            self.statement(depth + 1, s);
+                        }
+                        self.close(depth, s.span);
+                    }
+                }
+            }
+            StatementKind::Output(e) => {
+                let text = format!("OUT <- {}", self.expr(e));
+                self.line(depth, &text, s.span.start, s.span.end);
+            }
+        }
+    }
+}
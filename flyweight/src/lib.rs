@@ -0,0 +1,13 @@
+#![feature(box_patterns)]
+
+pub mod ast;
+pub mod bl;
+pub mod diagnostics;
+pub mod format;
+pub mod loader;
+pub mod parser;
+pub mod runtime;
+#[macro_use]
+extern crate lalrpop_util;
+
+lalrpop_mod!(#[allow(clippy::all)] pub keen4); // synthesized by LALRPOP
@@ -0,0 +1,251 @@
+//! Loading a program together with the files it imports.
+//!
+//! `import "stations/stationA.fw"` pulls in the items of that file, with the
+//! names it declares moved into the namespace named by the file's stem:
+//! `B_occ` there is `stationA::B_occ` everywhere else. Names already
+//! qualified (`stationB::B_occ`) are left alone, so two files importing the
+//! same third one agree on its names, and each file is only loaded once.
+//!
+//! The imported items are merged into one program in place of the `import`
+//! that brought them in, and their spans point into the imported file's
+//! entry in the `SourceMap`.
+
+use crate::ast::nodes::{Expr, ExprKind, Ident, Item, Program, Statement, StatementKind, Value};
+use crate::ast::span::Span;
+use crate::ast::symbols::SymbolTable;
+use crate::diagnostics::{Report, SourceMap};
+use crate::parser;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Parses the program in `text`, read from `path`, along with everything it
+/// imports. Imports are resolved relative to the importing file and read
+/// with `read`. Like `parser::parse_program_partial`, what could be parsed
+/// is returned alongside every error, and the program is `None` only if the
+/// root file couldn't be parsed at all.
+pub fn load_program<R>(
+    path: &Path,
+    text: String,
+    sources: &mut SourceMap,
+    symbols: &mut SymbolTable,
+    read: R,
+) -> (Option<Program>, Vec<Report>)
+where
+    R: FnMut(&Path) -> io::Result<String>,
+{
+    let mut loader = Loader {
+        sources,
+        symbols,
+        read,
+        loaded: HashSet::new(),
+        errs: vec![],
+    };
+    let root = normalize(path);
+    loader.loaded.insert(root.clone());
+    let program = loader.load(&root, text, None);
+    let mut errs = loader.errs;
+    errs.sort_by_key(|e| e.span());
+    (program, errs)
+}
+
+struct Loader<'m, R> {
+    sources: &'m mut SourceMap,
+    symbols: &'m mut SymbolTable,
+    read: R,
+    loaded: HashSet<PathBuf>,
+    errs: Vec<Report>,
+}
+
+impl<R> Loader<'_, R>
+where
+    R: FnMut(&Path) -> io::Result<String>,
+{
+    fn load(&mut self, path: &Path, text: String, namespace: Option<&str>) -> Option<Program> {
+        let (program, errs) = parser::parse_program_partial(&text, self.symbols);
+        let base = self.sources.add(path.display().to_string(), text);
+        self.errs
+            .extend(errs.into_iter().map(|e| shift_report(e, base)));
+        let mut program = program?;
+
+        let mut relocate = Relocate {
+            base,
+            namespace,
+            symbols: self.symbols,
+        };
+        relocate.span(&mut program.span);
+        for item in &mut program.items {
+            relocate.item(item);
+        }
+
+        let mut items = vec![];
+        for item in program.items {
+            match item {
+                Item::Import(import) => items.extend(self.import(path, &import.path, import.span)),
+                item => items.push(item),
+            }
+        }
+        Some(Program {
+            items,
+            span: program.span,
+        })
+    }
+
+    /// The items of the file `import` names, or none if it was already
+    /// loaded or can't be.
+    fn import(&mut self, from: &Path, import: &str, span: Span) -> Vec<Item> {
+        let path = normalize(&from.parent().unwrap_or_else(|| Path::new("")).join(import));
+        let namespace = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(stem) if is_name(stem) => stem.to_string(),
+            _ => {
+                self.errs.push(
+                    Report::error(format!("`{}` can't be used as a namespace", import))
+                        .with_label(span, "")
+                        .with_note("the file's name, without its extension, must be an identifier"),
+                );
+                return vec![];
+            }
+        };
+        if !self.loaded.insert(path.clone()) {
+            return vec![];
+        }
+        let text = match (self.read)(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.errs.push(
+                    Report::error(format!("could not read `{}`", path.display()))
+                        .with_label(span, e.to_string()),
+                );
+                return vec![];
+            }
+        };
+        self.load(&path, text, Some(&namespace))
+            .map(|p| p.items)
+            .unwrap_or_default()
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `path` with `.` and `..` resolved without touching the file system, so
+/// the same file reached two ways is only loaded once.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => (),
+            Component::ParentDir if out.file_name().is_some() => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn shift_report(mut report: Report, base: usize) -> Report {
+    for label in &mut report.labels {
+        label.span = Span::new(label.span.start + base, label.span.end + base);
+    }
+    report
+}
+
+/// Moves a parsed file's spans to its place in the `SourceMap` and its
+/// unqualified names into its namespace.
+struct Relocate<'a> {
+    base: usize,
+    namespace: Option<&'a str>,
+    symbols: &'a mut SymbolTable,
+}
+
+impl Relocate<'_> {
+    fn span(&mut self, span: &mut Span) {
+        *span = Span::new(span.start + self.base, span.end + self.base);
+    }
+
+    fn ident(&mut self, ident: &mut Ident) {
+        self.span(&mut ident.span);
+        if let Some(ns) = self.namespace {
+            let name = self.symbols.name(ident.id);
+            if !name.contains("::") {
+                let qualified = format!("{}::{}", ns, name);
+                ident.id = self.symbols.intern(&qualified);
+            }
+        }
+    }
+
+    fn item(&mut self, item: &mut Item) {
+        match item {
+            Item::Import(i) => self.span(&mut i.span),
+            Item::Declaration(d) => {
+                self.span(&mut d.span);
+                self.ident(&mut d.name);
+            }
+            Item::Constraints(c) => {
+                self.span(&mut c.span);
+                for i in c.free.iter_mut().chain(c.bound.iter_mut()) {
+                    self.ident(i);
+                }
+                for e in &mut c.exprs {
+                    self.expr(e);
+                }
+            }
+            Item::Run(r) => {
+                self.span(&mut r.span);
+                for s in &mut r.statements {
+                    self.statement(s);
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self, s: &mut Statement) {
+        self.span(&mut s.span);
+        match &mut s.kind {
+            StatementKind::Assign { targets, value } => {
+                for t in targets {
+                    self.ident(t);
+                }
+                match value {
+                    Value::Input(span) => self.span(span),
+                    Value::Expr(e) => self.expr(e),
+                    Value::Transaction(body, span) => {
+                        self.span(span);
+                        for s in body {
+                            self.statement(s);
+                        }
+                    }
+                }
+            }
+            StatementKind::Output(e) => self.expr(e),
+        }
+    }
+
+    fn expr(&mut self, e: &mut Expr) {
+        match &mut e.kind {
+            ExprKind::Term(id) => {
+                let mut ident = Ident {
+                    id: *id,
+                    span: e.span,
+                };
+                self.ident(&mut ident);
+                *id = ident.id;
+                e.span = ident.span;
+                return;
+            }
+            ExprKind::True | ExprKind::False => (),
+            ExprKind::Not(a) => self.expr(a),
+            ExprKind::And(a, b)
+            | ExprKind::Or(a, b)
+            | ExprKind::Implication(a, b)
+            | ExprKind::Biconditional(a, b)
+            | ExprKind::Xor(a, b) => {
+                self.expr(a);
+                self.expr(b);
+            }
+        }
+        self.span(&mut e.span);
+    }
+}
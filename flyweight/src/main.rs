@@ -1,97 +1,356 @@
-#![feature(iterator_fold_self)]
-#![feature(type_ascription)]
-#![feature(box_patterns)]
-#![feature(log_syntax)]
-#![feature(trace_macros)]
+use keen4_flyweight::ast::nodes::{Constraints, Literal, Program, MAX_TABLE_VARS};
+use keen4_flyweight::ast::symbols::{Names, SymbolTable};
+use keen4_flyweight::bl::aig::Aig;
+use keen4_flyweight::bl::aiger::{write_aiger, Circuit};
+use keen4_flyweight::bl::astToLogic;
+use keen4_flyweight::bl::cnf::Encoder;
+use keen4_flyweight::diagnostics::{Report, Severity, SourceFile, SourceMap};
+use keen4_flyweight::format::format_program;
+use keen4_flyweight::{loader, parser};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
 
-//trace_macros!(true);
+const USAGE: &str = "usage: keen4 <command> <file>
 
-pub mod ast;
-pub mod bl;
-#[macro_use]
-extern crate lalrpop_util;
+<file> is read along with every file it imports, except by fmt.
 
-lalrpop_mod!(pub keen4); // synthesized by LALRPOP
+commands:
+  check        parse the file, resolve names and prove every constraint
+               block solvable, warning about redundant constraints and
+               bound variables that copy another
+  truth-table  print each constraint's truth table over the free variables
+               it depends on
+  cnf          print each constraint block in conjunctive normal form
+  aig          print the size of each constraint block as an and-inverter
+               graph, as converted, rewritten and balanced
+  aiger        print every constraint block as an output of one ASCII AIGER
+               circuit, named by where the block is
+  dimacs       print every constraint block together as a DIMACS CNF
+               problem, with comments naming each variable
+  simplify     print each constraint block simplified
+  functions    print each bound variable as a minimized function of the
+               free variables
+  condense     print each constraint block with every subexpression of only
+               free variables replaced by a new one, and what those stand for
+  fmt          print the file in its canonical layout";
 
 fn main() {
-    let parser = keen4::TermParser::new();
-    let x = parser.parse("x");
-    assert!(x.is_ok());
-    println!("{:?}", x.unwrap());
-
-    let parser = keen4::TermParser::new();
-    let x = parser.parse("(y)");
-    assert!(x.is_ok());
-    println!("{:?}", x.unwrap());
-
-    let x = parser.parse("((z))");
-    assert!(x.is_ok());
-    println!("{:?}", x.unwrap());
-
-    let x = parser.parse("(x&((z)+y))");
-    assert!(x.is_ok());
-    println!("{:?}", x.unwrap());
-
-    let x = parser.parse("(x&((z)+y))");
-    assert!(x.is_ok());
-    println!("{:?}", x.unwrap());
-
-    let x = parser.parse("(((z)+y)&~x)");
-    assert!(x.is_ok());
-    println!("{:?}", x.unwrap());
-    println!();
-
-    let parser = keen4::ConstraintsParser::new();
-    let x = parser.parse(
-        " constraint(SB_stop,SB_appr,SB_clear) {
-  (~A_occ & ~B_occ & ~C_occ) -> SB_stop,
-  (A_occ | B_occ | C_occ) -> ~SB_stop,
-
-  (A_occ & ~B_occ & ~C_occ) -> SB_clear,
-  B_occ -> SB_stop,
-  (A_occ & ~B_occ & C_occ)-> SB_appr,
-
-    (SB_appr + SB_clear) + (~SB_clear & ~SB_appr),
-    SB_stop + SB_appr + SB_clear,
-    }",
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (command, path) = match args.as_slice() {
+        [command, path] => (command.as_str(), path),
+        _ => usage_error(),
+    };
+    let run: Option<fn(&SourceMap, &SymbolTable, &Program) -> bool> = match command {
+        "check" => Some(check),
+        "truth-table" => Some(truth_table),
+        "cnf" => Some(cnf),
+        "dimacs" => Some(dimacs),
+        "aig" => Some(aig),
+        "aiger" => Some(aiger),
+        "simplify" => Some(simplify),
+        "condense" => Some(condense),
+        "functions" => Some(functions),
+        "fmt" => None,
+        _ => usage_error(),
+    };
+
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("error: could not read {}: {}", path, e);
+            process::exit(2);
+        }
+    };
+    let run = match run {
+        Some(run) => run,
+        None => fmt(path, text),
+    };
+    let mut sources = SourceMap::new();
+    let mut symbols = SymbolTable::new();
+    let (program, errs) =
+        loader::load_program(Path::new(path), text, &mut sources, &mut symbols, |p| {
+            fs::read_to_string(p)
+        });
+    for e in &errs {
+        eprint!("{}", sources.render(e));
+    }
+    let program = match program {
+        Some(program) if errs.is_empty() => program,
+        // What did parse may still be worth checking names against, so
+        // `check` can report more than the syntax errors in one go.
+        Some(program) if command == "check" => {
+            report(
+                &sources,
+                program
+                    .check_names_partial(&symbols)
+                    .err()
+                    .unwrap_or_default(),
+                errs.len(),
+            );
+            process::exit(1);
+        }
+        _ => {
+            eprintln!("{}: {} error(s)", path, errs.len());
+            process::exit(1);
+        }
+    };
+    if !run(&sources, &symbols, &program) {
+        process::exit(1);
+    }
+}
+
+/// Prints `errs` and a count of the errors among them, including `earlier`
+/// already-printed errors. Notes aren't counted.
+fn report(sources: &SourceMap, errs: Vec<Report>, earlier: usize) {
+    for e in &errs {
+        eprint!("{}", sources.render(e));
+    }
+    let count = errs
+        .iter()
+        .filter(|e| e.severity == Severity::Error)
+        .count();
+    // The file named on the command line is always loaded first.
+    let root = sources.files().next().map(|f| f.name.as_str());
+    eprintln!("{}: {} error(s)", root.unwrap_or(""), earlier + count);
+}
+
+/// Prints the file reformatted. Imports are left as they are, so only the
+/// file itself is parsed.
+fn fmt(path: &str, text: String) -> ! {
+    let file = SourceFile::new(path, text);
+    let mut symbols = SymbolTable::new();
+    match parser::parse_program(&file.text, &mut symbols) {
+        Ok(program) => {
+            print!("{}", format_program(&program, &file.text, &symbols));
+            process::exit(0);
+        }
+        Err(errs) => {
+            for e in &errs {
+                eprint!("{}", file.render(e));
+            }
+            eprintln!("{}: {} error(s)", path, errs.len());
+            process::exit(1);
+        }
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// `file:line:col` of the start of a constraint block, to head its output.
+fn location(sources: &SourceMap, c: &Constraints) -> String {
+    sources.location(c.span.start)
+}
+
+fn check(sources: &SourceMap, symbols: &SymbolTable, program: &Program) -> bool {
+    // Solving constraints that mention misspelled names only produces noise,
+    // so stop at name errors.
+    if let Err(errs) = program.check_names(symbols) {
+        report(sources, errs, 0);
+        return false;
+    }
+
+    // Redundant clauses and copied bound variables are only worth a
+    // warning.
+    for c in program.constraints() {
+        for r in c.redundancies() {
+            eprint!("{}", sources.render(&r.report(symbols)));
+        }
+        for w in c.bound_behavior().iter().filter_map(|b| b.warning(symbols)) {
+            eprint!("{}", sources.render(&w));
+        }
+    }
+
+    // Condensing names new variables, which only the solver sees.
+    let mut condensed = symbols.clone();
+    let errs: Vec<Report> = program
+        .constraints()
+        .flat_map(|c| {
+            c.assert_bound_used_condensed(&mut condensed)
+                .err()
+                .unwrap_or_default()
+        })
+        .map(|d| d.report(symbols))
+        .collect();
+    if !errs.is_empty() {
+        report(sources, errs, 0);
+        return false;
+    }
+    true
+}
+
+fn truth_table(sources: &SourceMap, symbols: &SymbolTable, program: &Program) -> bool {
+    let mut errs = vec![];
+    for c in program.constraints() {
+        println!("{}", location(sources, c));
+        for (e, ftt) in c.exprs.iter().zip(c.free_truth_tables()) {
+            println!("  {}", e.named(symbols));
+            let ftt = match ftt {
+                Some(ftt) => ftt,
+                None => {
+                    println!("    (too many free variables)");
+                    errs.push(
+                        Report::error("too many free variables to tabulate")
+                            .with_label(e.span, "")
+                            .with_note(format!(
+                                "a table has at most {} free variables",
+                                MAX_TABLE_VARS
+                            )),
+                    );
+                    continue;
+                }
+            };
+            for tt in &ftt {
+                println!(
+                    "    {}  =>  {}",
+                    tt.free_map.named(symbols),
+                    tt.result.named(symbols)
+                );
+            }
+        }
+    }
+    if !errs.is_empty() {
+        report(sources, errs, 0);
+        return false;
+    }
+    true
+}
+
+fn clause_to_string(symbols: &SymbolTable, clause: &[Literal]) -> String {
+    if clause.is_empty() {
+        return "false".to_string();
+    }
+    clause
+        .iter()
+        .map(|(id, positive)| {
+            let sign = if *positive { "" } else { "~" };
+            format!("{}{}", sign, symbols.name(*id))
+        })
+        .collect::<Vec<String>>()
+        .join(" | ")
+}
+
+fn cnf(sources: &SourceMap, symbols: &SymbolTable, program: &Program) -> bool {
+    for c in program.constraints() {
+        println!("{}", location(sources, c));
+        let clauses = c.conjunction().cnf();
+        if clauses.is_empty() {
+            println!("  true");
+        }
+        for clause in clauses {
+            println!("  {}", clause_to_string(symbols, &clause));
+        }
+    }
+    true
+}
+
+fn aig(sources: &SourceMap, _: &SymbolTable, program: &Program) -> bool {
+    for c in program.constraints() {
+        println!("{}", location(sources, c));
+        let mut graph = Aig::new();
+        let root = graph.expr(&astToLogic(&c.conjunction()));
+        println!("  converted:  {}", graph.stats(&[root]));
+        let (graph, roots) = graph.rewrite(&[root]);
+        println!("  rewritten:  {}", graph.stats(&roots));
+        let (graph, roots) = graph.balance(&roots);
+        println!("  balanced:   {}", graph.stats(&roots));
+    }
+    true
+}
+
+fn aiger(sources: &SourceMap, symbols: &SymbolTable, program: &Program) -> bool {
+    let mut aig = Aig::new();
+    let outputs = program
+        .constraints()
+        .map(|c| {
+            (
+                location(sources, c),
+                aig.expr(&astToLogic(&c.conjunction())),
+            )
+        })
+        .collect();
+    let circuit = Circuit { aig, outputs };
+    print!(
+        "{}",
+        String::from_utf8(write_aiger(&circuit, symbols, false)).unwrap()
     );
-    "
-  B_occ -> SB_stop,
-  C_occ -> SC_stop,
-
-
-  (~SA_appr & ~SA_stop) -> ~A_occ,
-  ~SB_stop -> ~B_occ,
-  ";
-    println!("{:?}", x);
-    assert!(x.is_ok());
-    let x = x.unwrap();
-    for (i, ftt) in x.free_truth_tables().iter().enumerate() {
-        for tt in ftt {
-            println!("\t{:?}", bl::astToLogic(&x.exprs[i]));
-            //        println!("\t{:?}/{:?} <-> {:?}", x.exprs[i], tt.free_map, tt.result);
+    true
+}
+
+fn dimacs(_: &SourceMap, symbols: &SymbolTable, program: &Program) -> bool {
+    let mut inputs = vec![];
+    for c in program.constraints() {
+        for v in c.free_vars().into_iter().chain(c.bound_ids()) {
+            if !inputs.contains(&v) {
+                inputs.push(v);
+            }
         }
     }
-    //if let Err(errs) = x.assert_bound_used() {
-    //    for e in errs {
-    //        println!("{}", e)
-    //    }
-    //}
-    //println!();
-
-    //let parser = keen4::ConstraintsParser::new();
-    //let x = parser.parse(
-    //    "constraint(x){
-    //    x+y,
-    //}",
-    //);
-    //assert!(x.is_ok());
-    //let x = x.unwrap();
-    //println!("{:?}", x);
-    //if let Err(errs) = x.assert_bound_used() {
-    //    for e in errs {
-    //        println!("{}", e)
-    //    }
-    //}
-    //println!();
+    let mut encoder = Encoder::new(&inputs);
+    for c in program.constraints() {
+        encoder.assert(&astToLogic(&c.conjunction()));
+    }
+    print!("{}", encoder.finish().dimacs(symbols));
+    true
+}
+
+fn simplify(sources: &SourceMap, symbols: &SymbolTable, program: &Program) -> bool {
+    for c in program.constraints() {
+        println!("{}", location(sources, c));
+        for e in &c.exprs {
+            println!("  {}", e.clone().simplify().named(symbols));
+        }
+    }
+    true
+}
+
+fn condense(sources: &SourceMap, symbols: &SymbolTable, program: &Program) -> bool {
+    let mut symbols = symbols.clone();
+    for c in program.constraints() {
+        println!("{}", location(sources, c));
+        let condensed = c.condense(&mut symbols);
+        for e in &condensed.constraints.exprs {
+            println!("  {}", e.named(&symbols));
+        }
+        for i in &condensed.inputs {
+            println!(
+                "  where {} = {}",
+                symbols.name(i.var.id),
+                i.expr.named(&symbols)
+            );
+        }
+    }
+    true
+}
+
+fn functions(sources: &SourceMap, symbols: &SymbolTable, program: &Program) -> bool {
+    if let Err(errs) = program.check_names(symbols) {
+        report(sources, errs, 0);
+        return false;
+    }
+    let mut errs = vec![];
+    for c in program.constraints() {
+        match c.skolem_functions() {
+            Ok(functions) => {
+                println!("{}", location(sources, c));
+                for f in functions {
+                    println!(
+                        "  {} = {}",
+                        symbols.name(f.var.id),
+                        f.function.named(symbols)
+                    );
+                }
+            }
+            Err(e) => errs.extend(e.reports(symbols)),
+        }
+    }
+    if !errs.is_empty() {
+        report(sources, errs, 0);
+        return false;
+    }
+    true
 }
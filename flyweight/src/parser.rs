@@ -0,0 +1,110 @@
+use crate::ast::nodes::{Constraints, Program};
+use crate::ast::span::Span;
+use crate::ast::symbols::SymbolTable;
+use crate::diagnostics::Report;
+use crate::keen4::{ConstraintsParser, ProgramParser};
+use lalrpop_util::lexer::Token;
+
+pub type ParseError<'input> = lalrpop_util::ParseError<usize, Token<'input>, Report>;
+type ErrorRecovery<'input> = lalrpop_util::ErrorRecovery<usize, Token<'input>, Report>;
+
+/// Parses a whole program, reporting every syntax error in it. Variable
+/// names are interned into `symbols`.
+pub fn parse_program(src: &str, symbols: &mut SymbolTable) -> Result<Program, Vec<Report>> {
+    match parse_program_partial(src, symbols) {
+        (Some(program), errs) if errs.is_empty() => Ok(program),
+        (_, errs) => Err(errs),
+    }
+}
+
+/// Parses as much of a program as possible. Lines that fail to parse are
+/// reported and left out of the returned program, which is `None` only if
+/// nothing could be recovered at all.
+pub fn parse_program_partial(
+    src: &str,
+    symbols: &mut SymbolTable,
+) -> (Option<Program>, Vec<Report>) {
+    let mut recovered = vec![];
+    let result = ProgramParser::new().parse(&mut recovered, symbols, src);
+    collect(result, recovered)
+}
+
+pub fn parse_constraints(src: &str, symbols: &mut SymbolTable) -> Result<Constraints, Vec<Report>> {
+    let mut recovered = vec![];
+    let result = ConstraintsParser::new().parse(&mut recovered, symbols, src);
+    match collect(result, recovered) {
+        (Some(constraints), errs) if errs.is_empty() => Ok(constraints),
+        (_, errs) => Err(errs),
+    }
+}
+
+fn collect<'input, T>(
+    result: Result<T, ParseError<'input>>,
+    recovered: Vec<ErrorRecovery<'input>>,
+) -> (Option<T>, Vec<Report>) {
+    let mut errs: Vec<Report> = recovered
+        .into_iter()
+        .map(|e| parse_error_report(e.error))
+        .collect();
+    let parsed = match result {
+        Ok(t) => Some(t),
+        Err(e) => {
+            errs.push(parse_error_report(e));
+            None
+        }
+    };
+    errs.sort_by_key(|e| e.span());
+    (parsed, errs)
+}
+
+pub fn parse_error_report(e: ParseError) -> Report {
+    match e {
+        ParseError::InvalidToken { location } => Report::error("invalid token")
+            .with_label(Span::new(location, location + 1), "not part of any token"),
+        ParseError::UnrecognizedEOF { location, expected } => {
+            Report::error("unexpected end of file")
+                .with_label(Span::new(location, location), describe_expected(&expected))
+        }
+        ParseError::UnrecognizedToken {
+            token: (lo, t, hi),
+            expected,
+        } => Report::error(format!("unexpected {}", describe_token(t.1)))
+            .with_label(Span::new(lo, hi), describe_expected(&expected)),
+        ParseError::ExtraToken { token: (lo, t, hi) } => {
+            Report::error(format!("unexpected {}", describe_token(t.1)))
+                .with_label(Span::new(lo, hi), "expected nothing more")
+        }
+        ParseError::User { error } => error,
+    }
+}
+
+fn describe_token(text: &str) -> String {
+    match text {
+        "\n" => "end of line".to_string(),
+        t => format!("`{}`", t),
+    }
+}
+
+/// lalrpop names terminals by their pattern, e.g. `"&"` or
+/// `r#"[a-zA-Z0-9_]+"#`. Turn those into something readable.
+fn describe_terminal(terminal: &str) -> String {
+    if terminal.starts_with("r#\"") {
+        if terminal.contains("[a-zA-Z0-9_]") {
+            return "an identifier".to_string();
+        }
+        return "a quoted path".to_string();
+    }
+    match terminal.trim_matches('"') {
+        "NL" => "end of line".to_string(),
+        t => format!("`{}`", t),
+    }
+}
+
+fn describe_expected(expected: &[String]) -> String {
+    let expected: Vec<String> = expected.iter().map(|t| describe_terminal(t)).collect();
+    match expected.as_slice() {
+        [] => String::new(),
+        [only] => format!("expected {}", only),
+        [init @ .., last] => format!("expected {} or {}", init.join(", "), last),
+    }
+}
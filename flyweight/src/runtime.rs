@@ -0,0 +1,132 @@
+//! Computing the bound variables of a constraint block from its inputs,
+//! which is what the controller does every cycle.
+//!
+//! The clauses go into a SAT solver once; each call only assumes the free
+//! values, so a `Solver` can be called in a loop. Whether the answer is the
+//! only one is a property of the block, proven once by
+//! `Constraints::assert_bound_used`, and not checked again per call.
+
+use crate::ast::nodes::{Constraints, Expr, VarMap};
+use crate::ast::solvability::{Diagnostic, ReducedClause};
+use crate::ast::symbols::{Names, SymbolTable, VarId};
+use crate::bl::sat::{self, Lit};
+use crate::diagnostics::Report;
+use std::collections::HashMap;
+
+/// A constraint block ready to be solved for its bound variables.
+pub struct Solver {
+    solver: sat::Solver,
+    free: Vec<(VarId, Lit)>,
+    bound: Vec<(VarId, Lit)>,
+    /// Each clause, by its index in `exprs`.
+    clauses: Vec<Lit>,
+    exprs: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// The inputs have no value for this free variable.
+    MissingInput(VarId),
+    /// No bound assignment satisfies the clauses for these inputs.
+    /// `clauses` is a minimal set of clauses that can't hold together,
+    /// reduced by `cube`, as few of the inputs as that needs.
+    NoSolution {
+        cube: VarMap,
+        clauses: Vec<ReducedClause>,
+    },
+}
+
+impl SolveError {
+    pub fn report(&self, symbols: &SymbolTable) -> Report {
+        match self {
+            SolveError::MissingInput(v) => {
+                Report::error(format!("no value given for input `{}`", symbols.name(*v)))
+            }
+            SolveError::NoSolution { cube, clauses } => {
+                // No input is involved when the clauses conflict outright.
+                let message = if cube.assignment().is_empty() {
+                    "constraints can't all hold".to_string()
+                } else {
+                    format!("constraints can't all hold when {}", cube.named(symbols))
+                };
+                clauses.iter().fold(Report::error(message), |r, c| {
+                    r.with_label(c.span, format!("reduces to {}", c.reduced.named(symbols)))
+                })
+            }
+        }
+    }
+}
+
+impl Solver {
+    /// A solver for `c`, which is assumed to have passed
+    /// `assert_bound_used`; see `checked`.
+    pub fn new(c: &Constraints) -> Solver {
+        let mut solver = sat::Solver::new();
+        let mut new_vars = |vars: Vec<VarId>| -> Vec<(VarId, Lit)> {
+            vars.into_iter()
+                .map(|v| (v, solver.new_var().lit(true)))
+                .collect()
+        };
+        let free = new_vars(c.free_vars());
+        let bound = new_vars(c.bound_ids().collect());
+        let mut vars: HashMap<VarId, Lit> = free.iter().chain(&bound).copied().collect();
+        let clauses: Vec<Lit> = c.exprs.iter().map(|e| solver.expr(e, &mut vars)).collect();
+        Solver {
+            solver,
+            free,
+            bound,
+            clauses,
+            exprs: c.exprs.clone(),
+        }
+    }
+
+    /// A solver for `c`, if it gives its bound variables exactly one value
+    /// for every input.
+    pub fn checked(c: &Constraints) -> Result<Solver, Vec<Diagnostic>> {
+        c.assert_bound_used()?;
+        Ok(Solver::new(c))
+    }
+
+    /// The bound variables' values for `inputs`, which must give every
+    /// free variable the clauses use a value; others are ignored.
+    pub fn solve(&mut self, inputs: &VarMap) -> Result<VarMap, SolveError> {
+        let mut cube = Vec::with_capacity(self.free.len());
+        for (v, l) in &self.free {
+            match inputs.get(*v) {
+                Some(value) => cube.push(if value { *l } else { !*l }),
+                None => return Err(SolveError::MissingInput(*v)),
+            }
+        }
+        let mut assumptions = cube.clone();
+        assumptions.extend(&self.clauses);
+        if self.solver.solve_with(&assumptions) {
+            return Ok(self
+                .bound
+                .iter()
+                .map(|(v, l)| (*v, self.solver.model_value(*l)))
+                .collect::<HashMap<VarId, bool>>()
+                .into());
+        }
+
+        // As few clauses as can't hold together, then as few inputs as
+        // that needs.
+        let set = self.solver.minimize(&cube, &self.clauses).unwrap();
+        let lits: Vec<Lit> = set.iter().map(|i| self.clauses[*i]).collect();
+        let needed = self.solver.minimize(&lits, &cube).unwrap();
+        let cube: HashMap<VarId, bool> = needed
+            .iter()
+            .map(|i| (self.free[*i].0, cube[*i].is_positive()))
+            .collect();
+        Err(SolveError::NoSolution {
+            clauses: set
+                .iter()
+                .map(|i| ReducedClause {
+                    index: *i,
+                    span: self.exprs[*i].span,
+                    reduced: self.exprs[*i].evaluate(&cube),
+                })
+                .collect(),
+            cube: cube.into(),
+        })
+    }
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use common::{assignments, random_expr, Rng};
+use keen4_flyweight::ast::nodes::Expr;
+use keen4_flyweight::ast::symbols::{Names, SymbolTable, VarId};
+use keen4_flyweight::bl::aig::{Aig, Edge, Node, Stats};
+use keen4_flyweight::bl::astToLogic;
+
+fn inputs(aig: &mut Aig, symbols: &mut SymbolTable, names: &[&str]) -> Vec<Edge> {
+    names.iter().map(|n| aig.input(symbols.intern(n))).collect()
+}
+
+#[test]
+fn equal_ands_are_shared() {
+    let mut symbols = SymbolTable::new();
+    let mut aig = Aig::new();
+    let v = inputs(&mut aig, &mut symbols, &["a", "b", "c"]);
+    let ab = aig.and(v[0], v[1]);
+    assert_eq!(aig.and(v[1], v[0]), ab);
+    assert_eq!(aig.and(v[0], !v[0]), Edge::FALSE);
+    assert_eq!(aig.and(v[0], Edge::TRUE), v[0]);
+    assert_eq!(aig.and(ab, ab), ab);
+
+    // (a & b) | c and ~(a & b) & c use the one `a & b`.
+    let x = aig.or(ab, v[2]);
+    let y = aig.and(!ab, v[2]);
+    assert_eq!(
+        aig.stats(&[x, y]),
+        Stats {
+            inputs: 3,
+            ands: 3,
+            depth: 2
+        }
+    );
+    assert_eq!(aig.nodes().len(), 1 + 3 + 3);
+}
+
+#[test]
+fn rewriting_applies_two_level_rules() {
+    let mut symbols = SymbolTable::new();
+    let mut aig = Aig::new();
+    let v = inputs(&mut aig, &mut symbols, &["a", "b", "c"]);
+    let (a, b, c) = (v[0], v[1], v[2]);
+    let na_b = aig.and(!a, b);
+    let a_b = aig.and(a, b);
+    let a_nb = aig.and(a, !b);
+    let na_c = aig.and(!a, c);
+    let cases = [
+        (aig.and(a, na_b), Edge::FALSE),
+        (aig.and(a, !na_b), a),
+        (aig.and(a_b, na_c), Edge::FALSE),
+        (aig.and(!a_b, !a_nb), !a),
+    ];
+    let roots: Vec<Edge> = cases.iter().map(|(e, _)| *e).collect();
+    let (rewritten, roots) = aig.rewrite(&roots);
+    for (root, (_, expected)) in roots.iter().zip(&cases) {
+        assert_eq!(root, expected);
+    }
+    assert_eq!(rewritten.stats(&roots).ands, 0);
+
+    // a & ~(a & b) is a & ~b.
+    let root = aig.and(a, !a_b);
+    let (rewritten, roots) = aig.rewrite(&[root]);
+    assert_eq!(rewritten.stats(&roots).ands, 1);
+    match rewritten.node(roots[0]) {
+        Node::And(x, y) => {
+            assert_eq!(rewritten.node(x), Node::Input(symbols.intern("a")));
+            assert_eq!(rewritten.node(y), Node::Input(symbols.intern("b")));
+            assert!(!x.is_complemented() && y.is_complemented());
+        }
+        node => panic!("expected an and, got {:?}", node),
+    }
+}
+
+#[test]
+fn balancing_evens_out_chains() {
+    let mut symbols = SymbolTable::new();
+    let mut aig = Aig::new();
+    let v = inputs(
+        &mut aig,
+        &mut symbols,
+        &["a", "b", "c", "d", "e", "f", "g", "h"],
+    );
+    let chain = v[1..].iter().fold(v[0], |e, x| aig.and(e, *x));
+    assert_eq!(aig.stats(&[chain]).depth, 7);
+    let (balanced, roots) = aig.balance(&[!chain]);
+    assert_eq!(
+        balanced.stats(&roots),
+        Stats {
+            inputs: 8,
+            ands: 7,
+            depth: 3
+        }
+    );
+    assert!(roots[0].is_complemented());
+}
+
+#[test]
+fn passes_keep_the_truth_table() {
+    let mut symbols = SymbolTable::new();
+    let vars: Vec<VarId> = ["w", "x", "y", "z"]
+        .iter()
+        .map(|n| symbols.intern(n))
+        .collect();
+    let mut rng = Rng::new(0x0bad_cafe_f00d_1234);
+    for _ in 0..500 {
+        // Several roots at once, so they share nodes.
+        let exprs: Vec<Expr> = (0..3)
+            .map(|_| {
+                let depth = 1 + rng.below(6) as u32;
+                random_expr(&mut rng, &vars, depth)
+            })
+            .collect();
+        let mut aig = Aig::new();
+        let roots: Vec<Edge> = exprs.iter().map(|e| aig.expr(&astToLogic(e))).collect();
+        let (rewritten, rewritten_roots) = aig.rewrite(&roots);
+        let (balanced, balanced_roots) = rewritten.balance(&rewritten_roots);
+        assert!(rewritten.stats(&rewritten_roots).ands <= aig.stats(&roots).ands);
+        assert!(balanced.stats(&balanced_roots).depth <= rewritten.stats(&rewritten_roots).depth);
+        for m in assignments(&vars) {
+            let expected: Vec<bool> = exprs.iter().map(|e| e.evaluate(&m).is_true()).collect();
+            let names: Vec<String> = exprs
+                .iter()
+                .map(|e| e.named(&symbols).to_string())
+                .collect();
+            assert_eq!(
+                aig.evaluate(&roots, &m),
+                Some(expected.clone()),
+                "{:?}",
+                names
+            );
+            assert_eq!(
+                rewritten.evaluate(&rewritten_roots, &m),
+                Some(expected.clone()),
+                "{:?}",
+                names
+            );
+            assert_eq!(
+                balanced.evaluate(&balanced_roots, &m),
+                Some(expected),
+                "{:?}",
+                names
+            );
+        }
+    }
+}
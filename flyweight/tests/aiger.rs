@@ -0,0 +1,202 @@
+mod common;
+
+use common::{assignments, random_expr, Rng};
+use keen4_flyweight::ast::symbols::{SymbolTable, VarId};
+use keen4_flyweight::bl::aig::{Aig, Edge};
+use keen4_flyweight::bl::aiger::{read_aiger, write_aiger, Circuit};
+use keen4_flyweight::bl::astToLogic;
+use keen4_flyweight::diagnostics::SourceFile;
+use std::collections::HashMap;
+
+/// The half adder from the AIGER format description.
+const HALF_ADDER: &str = "aag 7 2 0 2 3
+2
+4
+6
+12
+6 13 15
+12 2 4
+14 3 5
+i0 x
+i1 y
+o0 s
+o1 c
+c
+half adder
+";
+
+fn values(symbols: &mut SymbolTable, assignment: &[(&str, bool)]) -> HashMap<VarId, bool> {
+    assignment
+        .iter()
+        .map(|(n, v)| (symbols.intern(n), *v))
+        .collect()
+}
+
+#[test]
+fn reference_circuits_read() {
+    let mut symbols = SymbolTable::new();
+    let circuit = read_aiger(HALF_ADDER.as_bytes(), &mut symbols).unwrap();
+    let names: Vec<&str> = circuit.outputs.iter().map(|(n, _)| &n[..]).collect();
+    assert_eq!(names, ["s", "c"]);
+    let roots: Vec<Edge> = circuit.outputs.iter().map(|(_, e)| *e).collect();
+    for (x, y) in [(false, false), (false, true), (true, false), (true, true)] {
+        let m = values(&mut symbols, &[("x", x), ("y", y)]);
+        assert_eq!(circuit.aig.evaluate(&roots, &m), Some(vec![x != y, x && y]));
+    }
+    assert_eq!(circuit.aig.stats(&roots).ands, 3);
+
+    // x & y, with unnamed inputs and outputs.
+    let mut binary = b"aig 3 2 0 1 1\n6\n".to_vec();
+    binary.extend([2, 2]);
+    let circuit = read_aiger(&binary, &mut symbols).unwrap();
+    let (name, root) = &circuit.outputs[0];
+    assert_eq!(name, "o0");
+    let m = values(&mut symbols, &[("i0", true), ("i1", false)]);
+    assert_eq!(circuit.aig.evaluate(&[*root], &m), Some(vec![false]));
+    let m = values(&mut symbols, &[("i0", true), ("i1", true)]);
+    assert_eq!(circuit.aig.evaluate(&[*root], &m), Some(vec![true]));
+}
+
+#[test]
+fn written_circuits_are_standard() {
+    let mut symbols = SymbolTable::new();
+    let circuit = read_aiger(HALF_ADDER.as_bytes(), &mut symbols).unwrap();
+    assert_eq!(
+        String::from_utf8(write_aiger(&circuit, &symbols, false)).unwrap(),
+        "aag 5 2 0 2 3
+2
+4
+10
+6
+6 4 2
+8 5 3
+10 9 7
+i0 x
+i1 y
+o0 s
+o1 c
+"
+    );
+    let mut binary = b"aig 5 2 0 2 3\n10\n6\n".to_vec();
+    binary.extend([2, 2, 3, 2, 1, 2]);
+    binary.extend(b"i0 x\ni1 y\no0 s\no1 c\n");
+    assert_eq!(write_aiger(&circuit, &symbols, true), binary);
+}
+
+#[test]
+fn both_formats_round_trip() {
+    let mut symbols = SymbolTable::new();
+    let vars: Vec<VarId> = ["w", "x", "y", "z"]
+        .iter()
+        .map(|n| symbols.intern(n))
+        .collect();
+    let mut rng = Rng::new(0x5eed_1e55_dead_beef);
+    for _ in 0..300 {
+        let mut aig = Aig::new();
+        let outputs: Vec<(String, Edge)> = (0..3)
+            .map(|k| {
+                let depth = 1 + rng.below(6) as u32;
+                let e = random_expr(&mut rng, &vars, depth);
+                (format!("out{}", k), aig.expr(&astToLogic(&e)))
+            })
+            .collect();
+        let circuit = Circuit { aig, outputs };
+        let roots: Vec<Edge> = circuit.outputs.iter().map(|(_, e)| *e).collect();
+        for binary in [false, true] {
+            let written = write_aiger(&circuit, &symbols, binary);
+            let read = read_aiger(&written, &mut symbols).unwrap();
+            assert_eq!(write_aiger(&read, &symbols, binary), written);
+            let read_roots: Vec<Edge> = read.outputs.iter().map(|(_, e)| *e).collect();
+            for m in assignments(&vars) {
+                let expected = circuit.aig.evaluate(&roots, &m);
+                assert_eq!(read.aig.evaluate(&read_roots, &m), expected);
+            }
+        }
+    }
+
+    // Operands far apart take several bytes each in binary.
+    let mut aig = Aig::new();
+    let inputs: Vec<Edge> = (0..300)
+        .map(|i| aig.input(symbols.intern(&format!("in{}", i))))
+        .collect();
+    let chain = inputs[1..].iter().fold(inputs[0], |e, x| aig.and(e, !*x));
+    let circuit = Circuit {
+        aig,
+        outputs: vec![("chain".to_string(), chain)],
+    };
+    let written = write_aiger(&circuit, &symbols, true);
+    let read = read_aiger(&written, &mut symbols).unwrap();
+    assert_eq!(read.aig.stats(&[read.outputs[0].1]).ands, 299);
+    assert_eq!(write_aiger(&read, &symbols, true), written);
+}
+
+#[test]
+fn malformed_files_are_reported() {
+    let cases: [(&[u8], &str); 10] = [
+        (b"aig\n", "expected an `aag` or `aig` header"),
+        (b"aag 1 1 0\n", "malformed header"),
+        (b"aag 1 0 1 0 0\n2 3\n", "latches aren't supported"),
+        (b"aag 1 0 0 0 0 1\n", "properties aren't supported"),
+        (b"aag 2 1 0 1 0\n2\n4\n", "variable 2 is never defined"),
+        (
+            b"aag 2 0 0 1 2\n2\n2 4 1\n4 2 1\n",
+            "variable 1 is defined by a cycle",
+        ),
+        (b"aag 1 1 0 1 0\n2\n2", "unexpected end of file"),
+        (b"aig 2 1 0 1 1\n4\n\x05\x00", "and operands out of order"),
+        (b"aag 1 1 0 0 0\n2\ni0 a\ni0 b\n", "input 0 is named twice"),
+        (
+            b"aag 2 2 0 0 0\n2\n4\ni0 a\ni1 a\n",
+            "`a` names both input 0 and input 1",
+        ),
+    ];
+    for (src, message) in &cases {
+        let errs = read_aiger(src, &mut SymbolTable::new()).unwrap_err();
+        let messages: Vec<&str> = errs.iter().map(|r| &r.message[..]).collect();
+        assert_eq!(
+            messages,
+            [*message],
+            "reading {:?}",
+            String::from_utf8_lossy(src)
+        );
+    }
+
+    let src = "aag 2 1 0 1 1\n2\n4\n4 2 7\n";
+    let errs = read_aiger(src.as_bytes(), &mut SymbolTable::new()).unwrap_err();
+    assert_eq!(
+        SourceFile::new("bad.aag", src).render(&errs[0]),
+        "error: literal 6 is out of range
+ --> bad.aag:4:1
+  |
+4 | 4 2 7
+  | ^
+"
+    );
+}
+
+#[test]
+fn input_names_are_one_to_one() {
+    let src = "aag 3 2 0 1 1\n2\n4\n6\n6 2 5\ni0 a\ni1 a\n";
+    let errs = read_aiger(src.as_bytes(), &mut SymbolTable::new()).unwrap_err();
+    assert_eq!(
+        SourceFile::new("names.aag", src).render(&errs[0]),
+        "error: `a` names both input 0 and input 1
+ --> names.aag:7:4
+  |
+6 | i0 a
+  |    - input 0
+7 | i1 a
+  |    ^
+"
+    );
+
+    // An unnamed input's default name steers clear of explicit ones.
+    let src = "aag 3 2 0 1 1\n2\n4\n6\n6 2 5\ni1 i0\n";
+    let mut symbols = SymbolTable::new();
+    let circuit = read_aiger(src.as_bytes(), &mut symbols).unwrap();
+    let roots: Vec<Edge> = circuit.outputs.iter().map(|(_, e)| *e).collect();
+    let m = values(&mut symbols, &[("i0_1", true), ("i0", false)]);
+    assert_eq!(circuit.aig.evaluate(&roots, &m), Some(vec![true]));
+    let m = values(&mut symbols, &[("i0_1", true), ("i0", true)]);
+    assert_eq!(circuit.aig.evaluate(&roots, &m), Some(vec![false]));
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::{assignments, random_expr, Rng};
+use keen4_flyweight::ast::solvability::Diagnostic;
+use keen4_flyweight::ast::symbols::{Names, SymbolTable, VarId};
+use keen4_flyweight::bl::anf::{Anf, XorSystem};
+use keen4_flyweight::bl::astToLogic;
+use keen4_flyweight::keen4::ExprParser;
+use keen4_flyweight::parser::parse_constraints;
+use std::collections::HashMap;
+
+fn polynomial(symbols: &mut SymbolTable, src: &str) -> Anf {
+    let mut errors = vec![];
+    let e = ExprParser::new().parse(&mut errors, symbols, src).unwrap();
+    Anf::new(&astToLogic(&e)).unwrap()
+}
+
+#[test]
+fn polynomials_print_as_xors_of_ands() {
+    let cases = [
+        ("(a + b) + (~b & ~a)", "(𝕋 + (a & b))"),
+        ("a | b", "(a + (a & b) + b)"),
+        ("a -> b", "(𝕋 + a + (a & b))"),
+        ("a <-> b", "(𝕋 + a + b)"),
+        ("~a + b + ~c", "(a + b + c)"),
+        ("a & (b | ~b)", "a"),
+        ("a & ~a", "𝔽"),
+    ];
+    for (src, expected) in &cases {
+        let mut symbols = SymbolTable::new();
+        for v in &["a", "b", "c"] {
+            symbols.intern(v);
+        }
+        let p = polynomial(&mut symbols, src);
+        assert_eq!(
+            p.named(&symbols).to_string(),
+            *expected,
+            "converting {}",
+            src
+        );
+    }
+}
+
+#[test]
+fn polynomials_keep_the_truth_table_and_are_unique() {
+    let mut symbols = SymbolTable::new();
+    let vars: Vec<VarId> = ["w", "x", "y", "z"]
+        .iter()
+        .map(|n| symbols.intern(n))
+        .collect();
+    let mut rng = Rng::new(0x1234_5678_9abc_def1);
+    let mut seen: HashMap<Vec<bool>, Anf> = HashMap::new();
+    for _ in 0..2000 {
+        let depth = 1 + rng.below(5) as u32;
+        let e = random_expr(&mut rng, &vars, depth);
+        let p = Anf::new(&astToLogic(&e)).unwrap();
+        let table: Vec<bool> = assignments(&vars)
+            .map(|m| {
+                let value = e.evaluate(&m).is_true();
+                assert_eq!(p.evaluate(&m), Some(value), "{}", e.named(&symbols));
+                assert_eq!(p.expr().evaluate(&m), Some(value));
+                value
+            })
+            .collect();
+        // Equivalent expressions have the same polynomial.
+        assert_eq!(seen.entry(table).or_insert_with(|| p.clone()), &p);
+    }
+}
+
+#[test]
+fn elimination_solves_in_terms_of_the_rest() {
+    let mut symbols = SymbolTable::new();
+    symbols.intern("a");
+    let vars: Vec<VarId> = ["x", "y", "z"].iter().map(|n| symbols.intern(n)).collect();
+    let equations = ["x + y", "y + z + a", "x + z + ~a"];
+    let mut system = XorSystem::new(&vars);
+    for e in &equations {
+        assert!(system.assert(&polynomial(&mut symbols, e)));
+    }
+    assert!(!system.assert(&polynomial(&mut symbols, "x & y")));
+    assert!(!system.assert(&polynomial(&mut symbols, "x & a")));
+    let solved = system.eliminate();
+    let determined: Vec<(&str, String)> = solved
+        .determined
+        .iter()
+        .map(|(v, p)| (symbols.name(*v), p.named(&symbols).to_string()))
+        .collect();
+    assert_eq!(
+        determined,
+        [
+            ("x", "(a + z)".to_string()),
+            ("y", "(𝕋 + a + z)".to_string())
+        ]
+    );
+    assert_eq!(solved.unconstrained, [vars[2]]);
+    assert!(solved.conditions.is_empty());
+
+    let mut system = XorSystem::new(&vars[..2]);
+    for e in &["x + y", "x + y + a"] {
+        assert!(system.assert(&polynomial(&mut symbols, e)));
+    }
+    let solved = system.eliminate();
+    assert_eq!(solved.conditions.len(), 1);
+    assert_eq!(solved.conditions[0].named(&symbols).to_string(), "a");
+}
+
+#[test]
+fn linear_blocks_are_solved_directly() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(
+        "constraints(free=A_occ, B_occ; bound=SA_stop, SB_stop, SB_appr) {
+  SB_stop <-> B_occ
+  SA_stop + SB_stop + A_occ
+  SB_appr <-> ~A_occ & ~B_occ
+  (SB_appr + SB_stop) + (~SB_stop & ~SB_appr)
+}",
+        &mut symbols,
+    )
+    .unwrap();
+    let solution: Vec<(&str, String)> = c
+        .linear_solution()
+        .unwrap()
+        .iter()
+        .map(|(var, p)| (symbols.name(var.id), p.named(&symbols).to_string()))
+        .collect();
+    assert_eq!(
+        solution,
+        [
+            ("SA_stop", "(𝕋 + A_occ + B_occ)".to_string()),
+            ("SB_stop", "B_occ".to_string()),
+            (
+                "SB_appr",
+                "(𝕋 + A_occ + (A_occ & B_occ) + B_occ)".to_string()
+            ),
+        ]
+    );
+    assert_eq!(c.assert_bound_used(), Ok(()));
+
+    // The nonlinear clause fails when only B_occ is set, so the solver
+    // has to find that.
+    let c = parse_constraints(
+        "constraints(free=A_occ, B_occ; bound=SB_stop, SB_appr) {
+  SB_stop <-> B_occ
+  SB_appr <-> ~A_occ
+  (SB_appr + SB_stop) + (~SB_stop & ~SB_appr)
+}",
+        &mut symbols,
+    )
+    .unwrap();
+    assert_eq!(c.linear_solution(), None);
+    let errs = c.assert_bound_used().unwrap_err();
+    assert!(matches!(errs[..], [Diagnostic::NoSolution { .. }]));
+
+    let c = parse_constraints(
+        "constraints(free=A_occ; bound=SA_stop, SB_stop) {
+  SA_stop + A_occ + ~A_occ
+  SB_stop <-> A_occ
+}",
+        &mut symbols,
+    )
+    .unwrap();
+    assert!(c.linear_solution().is_some());
+    let errs = c.assert_bound_used().unwrap_err();
+    assert!(matches!(
+        errs[..],
+        [Diagnostic::StuckAt { value: false, .. }]
+    ));
+}
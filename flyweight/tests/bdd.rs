@@ -0,0 +1,146 @@
+use keen4_flyweight::ast::symbols::{SymbolTable, VarId};
+use keen4_flyweight::bl::bdd::{self, Bdd, NodeId};
+use keen4_flyweight::keen4::ExprParser;
+use keen4_flyweight::parser::parse_constraints;
+
+fn vars(symbols: &mut SymbolTable, names: &[&str]) -> Vec<VarId> {
+    names.iter().map(|n| symbols.intern(n)).collect()
+}
+
+fn expr(bdd: &mut Bdd, symbols: &mut SymbolTable, src: &str) -> NodeId {
+    let e = ExprParser::new().parse(&mut vec![], symbols, src).unwrap();
+    bdd.expr(&e)
+}
+
+#[test]
+fn equal_functions_are_equal_nodes() {
+    let mut symbols = SymbolTable::new();
+    let mut bdd = Bdd::new();
+    let a = expr(&mut bdd, &mut symbols, "a -> b");
+    let b = expr(&mut bdd, &mut symbols, "~a | b");
+    let c = expr(&mut bdd, &mut symbols, "~(a & ~b)");
+    assert_eq!(a, b);
+    assert_eq!(b, c);
+    assert_eq!(expr(&mut bdd, &mut symbols, "a + a"), NodeId::FALSE);
+    assert_eq!(expr(&mut bdd, &mut symbols, "a <-> a"), NodeId::TRUE);
+    assert_eq!(
+        expr(&mut bdd, &mut symbols, "(a + b) + c"),
+        expr(&mut bdd, &mut symbols, "a <-> (b <-> c)")
+    );
+}
+
+#[test]
+fn restrict_and_quantify() {
+    let mut symbols = SymbolTable::new();
+    let mut bdd = Bdd::new();
+    let f = expr(&mut bdd, &mut symbols, "(a & x) | (~a & ~x)");
+    let v = vars(&mut symbols, &["a", "x"]);
+    let x = bdd.var(v[1]);
+    assert_eq!(bdd.restrict(f, v[0], true), x);
+    assert_eq!(bdd.exists(f, &[v[1]]), NodeId::TRUE);
+    assert_eq!(bdd.forall(f, &[v[1]]), NodeId::FALSE);
+    assert_eq!(bdd.support(f), v);
+    assert_eq!(bdd.any_sat(f), Some(vec![(v[0], false), (v[1], false)]));
+}
+
+#[test]
+fn sat_count_counts_over_the_given_variables() {
+    let mut symbols = SymbolTable::new();
+    let mut bdd = Bdd::new();
+    let f = expr(&mut bdd, &mut symbols, "a | b");
+    let v = vars(&mut symbols, &["a", "b", "c"]);
+    assert_eq!(bdd.sat_count(f, &v[..2]), 3.0);
+    assert_eq!(bdd.sat_count(f, &v), 6.0);
+    assert_eq!(bdd.sat_count(NodeId::TRUE, &v), 8.0);
+    assert_eq!(bdd.sat_count(NodeId::FALSE, &v), 0.0);
+}
+
+/// `x0 + x1 + ... + xn`, and each bound `yi` set to the parity so far.
+fn parity_chain(n: usize) -> String {
+    let free: Vec<String> = (0..n).map(|i| format!("x{}", i)).collect();
+    let bound: Vec<String> = (0..n).map(|i| format!("y{}", i)).collect();
+    let mut src = format!(
+        "constraints(free={}; bound={}) {{\n  y0 <-> x0\n",
+        free.join(","),
+        bound.join(",")
+    );
+    for i in 1..n {
+        src += &format!("  y{} <-> (y{} + x{})\n", i, i - 1, i);
+    }
+    src + "}"
+}
+
+#[test]
+fn hundreds_of_variables_stay_small() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&parity_chain(200), &mut symbols).unwrap();
+    let (mut bdd, f) = bdd::conjunction(&c.exprs);
+    // Linear in the chain; the free-first order would be exponential.
+    assert!(bdd.node_count(f) < 8 * 200, "{}", bdd.node_count(f));
+
+    // One solution for each of the 2^200 inputs, and one for every input.
+    let free: Vec<VarId> = c.free.iter().map(|i| i.id).collect();
+    let bound: Vec<VarId> = c.bound.iter().map(|i| i.id).collect();
+    let all: Vec<VarId> = free.iter().chain(&bound).copied().collect();
+    assert_eq!(bdd.sat_count(f, &all), 2f64.powi(200));
+    assert_eq!(bdd.exists(f, &bound), NodeId::TRUE);
+    let solution = bdd.any_sat(f).unwrap();
+    assert_eq!(bdd.restrict_all(f, &solution), NodeId::TRUE);
+
+    // Drop the last definition: y199 is then unconstrained.
+    let mut c = c;
+    c.exprs.pop();
+    let (mut bdd, f) = bdd::conjunction(&c.exprs);
+    assert_eq!(bdd.sat_count(f, &all), 2f64.powi(201));
+    assert!(!bdd.support(f).contains(&bound[199]));
+}
+
+#[test]
+fn ordering_heuristics_keep_the_smallest_diagram() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&parity_chain(12), &mut symbols).unwrap();
+    let (bdd, f) = bdd::conjunction(&c.exprs);
+    let interleaved = bdd::first_use_order(&c.exprs);
+    let mut worst = Bdd::with_order(
+        interleaved
+            .iter()
+            .filter(|v| symbols.name(**v).starts_with('x'))
+            .chain(
+                interleaved
+                    .iter()
+                    .filter(|v| symbols.name(**v).starts_with('y')),
+            )
+            .copied(),
+    );
+    let g = c.exprs.iter().fold(NodeId::TRUE, |g, e| {
+        let e = worst.expr(e);
+        worst.and(g, e)
+    });
+    assert!(bdd.node_count(f) <= worst.node_count(g));
+}
+
+#[test]
+fn truth_tables_enumerate_what_clauses_depend_on() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(
+        "constraints(free=x, y; bound=z) {\n  (x | ~x) & (z <-> y)\n}",
+        &mut symbols,
+    )
+    .unwrap();
+    let rows = c.free_truth_table(&c.exprs[0]).unwrap();
+    let y = symbols.get("y").unwrap();
+    let keys: Vec<Vec<(VarId, bool)>> = rows.iter().map(|r| r.free_map.assignment()).collect();
+    assert_eq!(keys, [vec![(y, false)], vec![(y, true)]]);
+    assert_eq!(c.bound_truth_table(&c.exprs[0]).unwrap().len(), 2);
+
+    // Far too many rows to list, but no overflow.
+    let free: Vec<String> = (0..40).map(|i| format!("x{}", i)).collect();
+    let src = format!(
+        "constraints(free={}; bound=z) {{\n  z <-> {}\n}}",
+        free.join(", "),
+        free.join(" + ")
+    );
+    let c = parse_constraints(&src, &mut symbols).unwrap();
+    assert_eq!(c.free_truth_table(&c.exprs[0]), None);
+    assert_eq!(c.bound_truth_table(&c.exprs[0]).unwrap().len(), 2);
+}
@@ -0,0 +1,95 @@
+use keen4_flyweight::ast::behavior::Behavior;
+use keen4_flyweight::ast::solvability::Diagnostic;
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::diagnostics::SourceFile;
+use keen4_flyweight::parser::parse_constraints;
+
+const SIGNALS: &str = "constraints(free=A_occ, B_occ; bound=SA_stop, SA_clear, SB_stop, SB_appr) {
+  SA_stop <-> A_occ
+  SA_clear <-> ~SA_stop
+  SB_stop <-> A_occ | B_occ
+  SB_appr <-> SB_stop
+}";
+
+#[test]
+fn copies_are_named() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(SIGNALS, &mut symbols).unwrap();
+    let id = |n: &str| symbols.get(n).unwrap();
+    let found: Vec<(&str, Behavior)> = c
+        .bound_behavior()
+        .iter()
+        .map(|b| (symbols.name(b.var.id), b.behavior))
+        .collect();
+    assert_eq!(
+        found[0],
+        (
+            "SA_stop",
+            Behavior::Input {
+                input: id("A_occ"),
+                negated: false
+            }
+        )
+    );
+    assert!(matches!(
+        found[1],
+        ("SA_clear", Behavior::Equal { other, negated: true }) if other.id == id("SA_stop")
+    ));
+    assert_eq!(found[2], ("SB_stop", Behavior::Varies));
+    assert!(matches!(
+        found[3],
+        ("SB_appr", Behavior::Equal { other, negated: false }) if other.id == id("SB_stop")
+    ));
+    // Copies are fine as far as solving goes.
+    assert_eq!(c.assert_bound_used(), Ok(()));
+}
+
+#[test]
+fn copies_are_warnings() {
+    let src = SourceFile::new("signals.fw", SIGNALS);
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let warnings: Vec<String> = c
+        .bound_behavior()
+        .iter()
+        .filter_map(|b| b.warning(&symbols))
+        .map(|r| src.render(&r))
+        .collect();
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(
+        warnings[1],
+        "warning: bound variable `SA_clear` always has the opposite value of `SA_stop`
+ --> signals.fw:1:47
+  |
+1 | constraints(free=A_occ, B_occ; bound=SA_stop, SA_clear, SB_stop, SB_appr) {
+  |                                      -------
+  |                                               ^^^^^^^^
+  = note: it can be replaced by `~SA_stop`
+"
+    );
+}
+
+#[test]
+fn stuck_variables_are_errors() {
+    let src = SourceFile::new(
+        "stuck.fw",
+        "constraints(free=A_occ; bound=SA_stop) {\n  A_occ -> SA_stop\n  ~A_occ -> SA_stop\n}",
+    );
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let errs = c.assert_bound_used().unwrap_err();
+    assert!(matches!(
+        errs.as_slice(),
+        [Diagnostic::StuckAt { value: true, .. }]
+    ));
+    assert_eq!(
+        src.render(&errs[0].report(&symbols)),
+        "error: bound variable `SA_stop` is always true
+ --> stuck.fw:1:31
+  |
+1 | constraints(free=A_occ; bound=SA_stop) {
+  |                               ^^^^^^^
+  = note: no assignment of the free variables lets it be false
+"
+    );
+}
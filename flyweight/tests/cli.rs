@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const OK: &str = "x := false
+y := false
+z := false
+
+constraints(free=x, y; bound=z) {
+  z <-> x & y
+}
+";
+
+/// Writes `text` to a file of its own for the binary to read.
+fn source(name: &str, text: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, text).unwrap();
+    path
+}
+
+fn keen4(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_keen4"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn run(command: &str, path: &Path) -> (Option<i32>, String, String) {
+    let out = keen4(&[command, path.to_str().unwrap()]);
+    (
+        out.status.code(),
+        String::from_utf8(out.stdout).unwrap(),
+        String::from_utf8(out.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn subcommands_print_to_stdout() {
+    let path = source("cli_ok.fw", OK);
+    let location = format!("{}:5:1", path.display());
+
+    let (code, stdout, stderr) = run("check", &path);
+    assert_eq!((code, &stdout[..], &stderr[..]), (Some(0), "", ""));
+
+    let (code, stdout, _) = run("cnf", &path);
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout,
+        format!("{}\n  ~z | x\n  ~z | y\n  z | ~x | ~y\n", location)
+    );
+
+    let empty = source("cli_empty.fw", "constraints() {\n}\n");
+    let (code, stdout, _) = run("cnf", &empty);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, format!("{}:1:1\n  true\n", empty.display()));
+
+    let (code, stdout, _) = run("truth-table", &path);
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout,
+        format!(
+            "{}
+  (z <-> (x & y))
+    x = false, y = false  =>  ~z
+    x = true, y = false  =>  ~z
+    x = false, y = true  =>  ~z
+    x = true, y = true  =>  z
+",
+            location
+        )
+    );
+
+    let (code, stdout, _) = run("simplify", &path);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, format!("{}\n  (z <-> (x & y))\n", location));
+
+    let (code, stdout, _) = run("fmt", &path);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, OK.replace(":=", "<-"));
+}
+
+#[test]
+fn errors_exit_with_1() {
+    let path = source("cli_syntax.fw", "constraints(bound=z) {\n  z &\n}\n");
+    for command in &["check", "cnf", "fmt"] {
+        let (code, stdout, stderr) = run(command, &path);
+        assert_eq!(code, Some(1), "keen4 {}", command);
+        assert_eq!(stdout, "");
+        assert!(stderr.starts_with("error: unexpected end of line\n"));
+    }
+
+    // Undeclared names only fail `check`.
+    let path = source("cli_names.fw", "constraints(bound=z) {\n  z & x\n}\n");
+    let (code, _, stderr) = run("check", &path);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("error: `x` is not declared\n"));
+    assert!(stderr.ends_with(": 2 error(s)\n"));
+
+    let path = source(
+        "cli_unsat.fw",
+        "x := false
+z := false
+constraints(free=x; bound=z) {
+  z <-> x
+  z <-> ~x
+}
+",
+    );
+    let (code, stdout, stderr) = run("check", &path);
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, "");
+    assert!(stderr.starts_with("error: constraints have no solution"));
+
+    let free: Vec<String> = (0..20).map(|i| format!("x{}", i)).collect();
+    let path = source(
+        "cli_wide.fw",
+        &format!(
+            "constraints(bound=z) {{\n  z <-> {}\n}}\n",
+            free.join(" + ")
+        ),
+    );
+    let (code, stdout, stderr) = run("truth-table", &path);
+    assert_eq!(code, Some(1));
+    assert!(stdout.ends_with("    (too many free variables)\n"));
+    assert!(stderr.starts_with("error: too many free variables to tabulate\n"));
+}
+
+#[test]
+fn usage_and_read_errors_exit_with_2() {
+    for args in &[&[][..], &["check"], &["check", "a.fw", "b.fw"]] {
+        let out = keen4(args);
+        assert_eq!(out.status.code(), Some(2), "keen4 {:?}", args);
+        assert!(String::from_utf8(out.stderr)
+            .unwrap()
+            .starts_with("usage: keen4 <command> <file>"));
+    }
+
+    let path = source("cli_unknown.fw", OK);
+    let (code, stdout, stderr) = run("frobnicate", &path);
+    assert_eq!(code, Some(2));
+    assert_eq!(stdout, "");
+    assert!(stderr.starts_with("usage: "));
+
+    let missing = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli_missing.fw");
+    let (code, _, stderr) = run("check", &missing);
+    assert_eq!(code, Some(2));
+    assert!(stderr.starts_with("error: could not read "));
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use common::{assignments, random_expr, Rng};
+use keen4_flyweight::ast::symbols::{Names, SymbolTable, VarId};
+use keen4_flyweight::bl::astToLogic;
+use keen4_flyweight::bl::cnf::{Cnf, CnfVar, Encoder};
+use keen4_flyweight::bl::logic::Expr;
+use keen4_flyweight::keen4::ExprParser;
+use std::collections::HashMap;
+
+fn encoded(symbols: &mut SymbolTable, inputs: &[&str], src: &str) -> Cnf {
+    let inputs: Vec<VarId> = inputs.iter().map(|n| symbols.intern(n)).collect();
+    let mut errors = vec![];
+    let e = ExprParser::new().parse(&mut errors, symbols, src).unwrap();
+    let mut encoder = Encoder::new(&inputs);
+    encoder.assert(&astToLogic(&e));
+    encoder.finish()
+}
+
+#[test]
+fn dimacs_names_inputs_and_gates() {
+    let mut symbols = SymbolTable::new();
+    let cnf = encoded(
+        &mut symbols,
+        &["a", "b", "SB_stop", "SB_appr", "SA_stop"],
+        "(SB_stop + SB_appr + SA_stop) & (a | ~b)",
+    );
+    assert_eq!(
+        cnf.dimacs(&symbols),
+        "c var 1 = a
+c var 2 = b
+c var 3 = SB_stop
+c var 4 = SB_appr
+c var 5 = SA_stop
+c aux 6 = ((SB_stop + SB_appr) + SA_stop)
+c aux 7 = (SB_stop + SB_appr)
+p cnf 7 8
+1 -2 0
+-7 3 4 0
+-7 -3 -4 0
+7 -3 4 0
+7 3 -4 0
+-6 7 5 0
+-6 -7 -5 0
+6 0
+"
+    );
+}
+
+#[test]
+fn xor_chains_grow_linearly() {
+    let n = 64;
+    let mut symbols = SymbolTable::new();
+    let e = Expr::Xor(
+        (0..n)
+            .map(|i| Box::new(Expr::Term(symbols.intern(&format!("x{}", i)))))
+            .collect(),
+    );
+    let mut encoder = Encoder::new(&[]);
+    encoder.assert(&e);
+    let cnf = encoder.finish();
+    assert_eq!(cnf.num_vars(), n + n - 1);
+    // Two clauses for each of the n - 1 gates used only positively at the
+    // top and four for the others, and the unit clause.
+    assert_eq!(cnf.clauses.len(), 4 * (n - 1) - 2 + 1);
+}
+
+#[test]
+fn xors_of_one_or_no_operands() {
+    let mut symbols = SymbolTable::new();
+    let a = symbols.intern("a");
+    let not = |e| Expr::Not(Box::new(e));
+    let xor = |es: Vec<Expr>| Expr::Xor(es.into_iter().map(Box::new).collect());
+    let cases = [
+        (xor(vec![Expr::Term(a)]), vec![true]),
+        (not(xor(vec![Expr::Term(a)])), vec![false]),
+        (xor(vec![]), vec![]),
+        (not(xor(vec![])), vec![false, true]),
+    ];
+    for (e, models) in &cases {
+        let mut encoder = Encoder::new(&[a]);
+        encoder.assert(e);
+        let cnf = encoder.finish();
+        let (mut solver, lits) = cnf.solver();
+        let found: Vec<bool> = [false, true]
+            .iter()
+            .copied()
+            .filter(|v| solver.solve_with(&[lits[0].var().lit(*v)]))
+            .collect();
+        assert_eq!(&found, models, "{:?}", e);
+    }
+}
+
+#[test]
+fn encoding_is_equisatisfiable() {
+    let mut symbols = SymbolTable::new();
+    let vars: Vec<VarId> = ["w", "x", "y", "z"]
+        .iter()
+        .map(|n| symbols.intern(n))
+        .collect();
+    let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..1000 {
+        let depth = 1 + rng.below(6) as u32;
+        let e = *astToLogic(&random_expr(&mut rng, &vars, depth));
+        let mut encoder = Encoder::new(&vars);
+        encoder.assert(&e);
+        let cnf = encoder.finish();
+
+        let (mut solver, lits) = cnf.solver();
+        let satisfiable = assignments(&vars).any(|m| e.evaluate(&m) == Some(true));
+        assert_eq!(solver.solve(), satisfiable);
+        if satisfiable {
+            let found: HashMap<VarId, bool> = cnf.vars[..vars.len()]
+                .iter()
+                .zip(&lits)
+                .map(|(v, l)| match v {
+                    CnfVar::Input(id) => (*id, solver.model_value(*l)),
+                    CnfVar::Aux(_) => panic!("inputs come first"),
+                })
+                .collect();
+            assert_eq!(e.evaluate(&found), Some(true));
+        }
+        let expr = cnf.expr();
+        for m in assignments(&vars) {
+            assert_eq!(expr.evaluate(&m), e.evaluate(&m));
+        }
+    }
+}
+
+fn clauses(symbols: &mut SymbolTable, src: &str) -> Vec<Vec<(String, bool)>> {
+    let mut errors = vec![];
+    let e = ExprParser::new().parse(&mut errors, symbols, src).unwrap();
+    e.cnf()
+        .iter()
+        .map(|c| {
+            c.iter()
+                .map(|(id, positive)| (symbols.name(*id).to_string(), *positive))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn distribution_drops_duplicates_and_tautologies() {
+    let mut symbols = SymbolTable::new();
+    let lit = |n: &str, positive| (n.to_string(), positive);
+    assert_eq!(clauses(&mut symbols, "true"), Vec::<Vec<_>>::new());
+    assert_eq!(clauses(&mut symbols, "false"), [vec![]]);
+    assert_eq!(clauses(&mut symbols, "a | ~a"), Vec::<Vec<_>>::new());
+    assert_eq!(clauses(&mut symbols, "a & a"), [[lit("a", true)]]);
+    assert_eq!(
+        clauses(&mut symbols, "a -> b"),
+        [[lit("a", false), lit("b", true)]]
+    );
+    assert_eq!(
+        clauses(&mut symbols, "a + b"),
+        [
+            [lit("a", false), lit("b", false)],
+            [lit("a", true), lit("b", true)]
+        ]
+    );
+}
+
+#[test]
+fn distribution_keeps_the_truth_table() {
+    let mut symbols = SymbolTable::new();
+    let vars: Vec<VarId> = ["w", "x", "y", "z"]
+        .iter()
+        .map(|n| symbols.intern(n))
+        .collect();
+    let mut rng = Rng::new(0x6a09_e667_f3bc_c908);
+    for _ in 0..1000 {
+        let depth = 1 + rng.below(5) as u32;
+        let e = random_expr(&mut rng, &vars, depth);
+        let cnf = e.cnf();
+        for m in assignments(&vars) {
+            let holds = cnf.iter().all(|c| c.iter().any(|(id, p)| m[id] == *p));
+            assert_eq!(holds, e.evaluate(&m).is_true(), "{}", e.named(&symbols));
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Helpers shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use keen4_flyweight::ast::nodes::{Expr, ExprKind, VarMap};
+use keen4_flyweight::ast::span::Span;
+use keen4_flyweight::ast::symbols::{SymbolTable, VarId};
+use std::collections::HashMap;
+
+/// An xorshift generator, so randomized tests see the same cases every run.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+/// An expression `depth` operators deep, over the leaves `vars`, `true`
+/// and `false`.
+pub fn random_expr(rng: &mut Rng, vars: &[VarId], depth: u32) -> Expr {
+    let sub = |rng: &mut Rng| Box::new(random_expr(rng, vars, depth - 1));
+    let kind = match if depth == 0 {
+        rng.below(4)
+    } else {
+        4 + rng.below(6)
+    } {
+        0 => ExprKind::True,
+        1 => ExprKind::False,
+        2 | 3 => ExprKind::Term(vars[rng.below(vars.len() as u64) as usize]),
+        4 => ExprKind::Not(sub(rng)),
+        5 => ExprKind::And(sub(rng), sub(rng)),
+        6 => ExprKind::Or(sub(rng), sub(rng)),
+        7 => ExprKind::Implication(sub(rng), sub(rng)),
+        8 => ExprKind::Biconditional(sub(rng), sub(rng)),
+        _ => ExprKind::Xor(sub(rng), sub(rng)),
+    };
+    Expr::new(kind, Span::new(0, 0))
+}
+
+/// Every assignment to `vars`, counting up in binary with `vars[0]` the
+/// lowest bit.
+pub fn assignments(vars: &[VarId]) -> impl Iterator<Item = HashMap<VarId, bool>> + '_ {
+    (0..1usize << vars.len()).map(move |n| {
+        vars.iter()
+            .enumerate()
+            .map(|(i, v)| (*v, n >> i & 1 == 1))
+            .collect()
+    })
+}
+
+/// Fully parenthesized rendering, so the tests pin down grouping without
+/// depending on how `Expr` is displayed.
+pub fn group(symbols: &SymbolTable, e: &Expr) -> String {
+    let group = |e| group(symbols, e);
+    match &e.kind {
+        ExprKind::Term(x) => symbols.name(*x).to_string(),
+        ExprKind::True => "true".to_string(),
+        ExprKind::False => "false".to_string(),
+        ExprKind::Not(a) => format!("~{}", group(a)),
+        ExprKind::And(a, b) => format!("({} & {})", group(a), group(b)),
+        ExprKind::Xor(a, b) => format!("({} + {})", group(a), group(b)),
+        ExprKind::Or(a, b) => format!("({} | {})", group(a), group(b)),
+        ExprKind::Implication(a, b) => format!("({} -> {})", group(a), group(b)),
+        ExprKind::Biconditional(a, b) => format!("({} <-> {})", group(a), group(b)),
+    }
+}
+
+/// The variables named in `values`, which must already be interned, with
+/// their values.
+pub fn var_map(symbols: &SymbolTable, values: &[(&str, bool)]) -> VarMap {
+    values
+        .iter()
+        .map(|(n, v)| (symbols.get(n).unwrap(), *v))
+        .collect::<HashMap<_, _>>()
+        .into()
+}
+
+/// Signal `si` shows stop when block `bi` or any block before it is
+/// occupied.
+pub fn signal_chain(n: usize) -> String {
+    let free: Vec<String> = (0..n).map(|i| format!("b{}", i)).collect();
+    let bound: Vec<String> = (0..n).map(|i| format!("s{}", i)).collect();
+    let mut src = format!(
+        "constraints(free={}; bound={}) {{\n  s0 <-> b0\n",
+        free.join(","),
+        bound.join(",")
+    );
+    for i in 1..n {
+        src += &format!("  s{} <-> (b{} | s{})\n", i, i, i - 1);
+    }
+    src + "}"
+}
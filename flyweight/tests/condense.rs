@@ -0,0 +1,95 @@
+use keen4_flyweight::ast::solvability::Diagnostic;
+use keen4_flyweight::ast::symbols::{Names, SymbolTable};
+use keen4_flyweight::parser::parse_constraints;
+
+const SIGNALS: &str = "constraints(free=A_occ, B_occ, C_occ, D_occ; bound=SA_stop, SB_stop) {
+  (A_occ | B_occ) -> SA_stop
+  ~(A_occ | B_occ) -> ~SA_stop
+  SB_stop <-> (C_occ & D_occ) | SA_stop
+}";
+
+#[test]
+fn free_only_subtrees_become_inputs() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(SIGNALS, &mut symbols).unwrap();
+    let condensed = c.condense(&mut symbols);
+
+    let inputs: Vec<(String, String)> = condensed
+        .inputs
+        .iter()
+        .map(|i| {
+            (
+                symbols.name(i.var.id).to_string(),
+                i.expr.named(&symbols).to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        inputs,
+        vec![
+            ("$0".to_string(), "(A_occ | B_occ)".to_string()),
+            ("$1".to_string(), "(C_occ & D_occ)".to_string()),
+        ]
+    );
+    let exprs: Vec<String> = condensed
+        .constraints
+        .exprs
+        .iter()
+        .map(|e| e.named(&symbols).to_string())
+        .collect();
+    // `~(A_occ | B_occ)` shares `$0` with the first clause.
+    assert_eq!(
+        exprs,
+        vec![
+            "($0 -> SA_stop)",
+            "(~$0 -> ~SA_stop)",
+            "(SB_stop <-> ($1 | SA_stop))"
+        ]
+    );
+    assert_eq!(condensed.constraints.free_vars().len(), 2);
+    assert!(condensed.exact());
+    for (e, condensed_e) in c.exprs.iter().zip(&condensed.constraints.exprs) {
+        assert_eq!(&condensed.expand(condensed_e), e);
+    }
+    assert_eq!(c.assert_bound_used_condensed(&mut symbols), Ok(()));
+}
+
+#[test]
+fn witnesses_are_given_in_the_original_inputs() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(
+        "constraints(free=A_occ, B_occ; bound=SA_stop) {\n  (A_occ | B_occ) -> SA_stop\n  (A_occ | B_occ) -> ~SA_stop\n}",
+        &mut symbols,
+    )
+    .unwrap();
+    let errs = c.assert_bound_used_condensed(&mut symbols).unwrap_err();
+    match &errs[0] {
+        Diagnostic::NoSolution { free, clauses, .. } => {
+            let a = symbols.get("A_occ").unwrap();
+            let b = symbols.get("B_occ").unwrap();
+            assert!(free.get(a).unwrap() || free.get(b).unwrap());
+            assert_eq!(clauses.len(), 2);
+            assert!(clauses.iter().all(|c| c.reduced.variables().len() == 1));
+        }
+        d => panic!("expected no solution, got {:?}", d),
+    }
+    let report = errs[0].report(&symbols);
+    assert!(!report.message.contains('$'));
+}
+
+#[test]
+fn overlapping_inputs_are_checked_uncondensed() {
+    let mut symbols = SymbolTable::new();
+    // `A_occ & B_occ` and `A_occ | B_occ` can't be false and true together,
+    // so as inputs of their own they'd make a case that can't happen.
+    let c = parse_constraints(
+        "constraints(free=A_occ, B_occ; bound=SA_stop) {\n  (A_occ & B_occ) -> SA_stop\n  ~(A_occ | B_occ) -> ~SA_stop\n  SA_stop -> A_occ | B_occ\n}",
+        &mut symbols,
+    )
+    .unwrap();
+    assert!(!c.condense(&mut symbols).exact());
+    assert_eq!(
+        c.assert_bound_used_condensed(&mut symbols),
+        c.assert_bound_used()
+    );
+}
@@ -0,0 +1,98 @@
+use keen4_flyweight::ast::span::Span;
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::diagnostics::SourceFile;
+use keen4_flyweight::parser::{parse_constraints, parse_program, parse_program_partial};
+
+#[test]
+fn parse_errors_point_at_the_offending_token() {
+    let src = SourceFile::new(
+        "bad.fw",
+        "A_occ <- false\n\nconstraints(bound=SA_stop) {\n  A_occ -> )\n}\n",
+    );
+    let report = parse_program(&src.text, &mut SymbolTable::new())
+        .unwrap_err()
+        .remove(0);
+    assert_eq!(
+        src.render(&report),
+        "error: unexpected `)`
+ --> bad.fw:4:12
+  |
+4 |   A_occ -> )
+  |            ^ expected `(`, `false`, `true`, `~` or an identifier
+"
+    );
+}
+
+#[test]
+fn unexpected_end_of_file() {
+    let src = SourceFile::new("eof.fw", "constraints(bound=x) {\n  x");
+    let report = parse_program(&src.text, &mut SymbolTable::new())
+        .unwrap_err()
+        .remove(0);
+    assert_eq!(report.span(), Some(Span::new(26, 26)));
+    assert!(src
+        .render(&report)
+        .starts_with("error: unexpected end of file\n --> eof.fw:2:4\n"));
+}
+
+#[test]
+fn semantic_errors_label_the_source() {
+    let src = SourceFile::new("unused.fw", "constraints(bound=x, y) {\n  a -> x\n}");
+    let mut symbols = SymbolTable::new();
+    let constraints = parse_constraints(&src.text, &mut symbols).unwrap();
+    let errs = constraints.assert_bound_used().unwrap_err();
+    let rendered = errs
+        .iter()
+        .map(|e| src.render(&e.report(&symbols)))
+        .find(|r| r.starts_with("error: bound variables are never used in any constraint\n"))
+        .unwrap();
+    assert_eq!(
+        rendered,
+        "error: bound variables are never used in any constraint
+ --> unused.fw:1:22
+  |
+1 | constraints(bound=x, y) {
+  |                      ^ `y` is never used
+"
+    );
+}
+
+#[test]
+fn spans_cover_their_source() {
+    let text = "constraints(bound=SA_stop) {\n  (B_occ & ~SA_stop) -> SA_appr\n}";
+    let constraints = parse_constraints(text, &mut SymbolTable::new()).unwrap();
+    let span = constraints.exprs[0].span;
+    assert_eq!(&text[span.start..span.end], "(B_occ & ~SA_stop) -> SA_appr");
+    assert_eq!(constraints.span, Span::new(0, text.len()));
+    let bound = constraints.bound[0].span;
+    assert_eq!(&text[bound.start..bound.end], "SA_stop");
+}
+
+#[test]
+fn every_bad_line_is_reported() {
+    let src = "x <- false
+y <- false
+constraints(bound=y) {
+  x & -> y
+  x -> y
+  (x | y
+}
+run {
+  x, y <- IN
+  OUT <- y
+}
+";
+    let mut symbols = SymbolTable::new();
+    let (program, errs) = parse_program_partial(src, &mut symbols);
+    let lines: Vec<usize> = errs
+        .iter()
+        .map(|e| SourceFile::new("", src).line_col(e.span().unwrap().start).0)
+        .collect();
+    assert_eq!(lines, vec![4, 6, 9]);
+
+    let program = program.unwrap();
+    let constraints: Vec<_> = program.constraints().collect();
+    assert_eq!(constraints[0].exprs.len(), 1);
+    assert_eq!(program.runs().next().unwrap().statements.len(), 2);
+    assert!(program.check_names_partial(&symbols).is_ok());
+}
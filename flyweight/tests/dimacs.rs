@@ -0,0 +1,152 @@
+use keen4_flyweight::ast::symbols::{Names, SymbolTable};
+use keen4_flyweight::bl::astToLogic;
+use keen4_flyweight::bl::cnf::{parse_dimacs, CnfVar, Encoder};
+use keen4_flyweight::diagnostics::SourceFile;
+use keen4_flyweight::keen4::ExprParser;
+
+#[test]
+fn comments_name_variables() {
+    let mut symbols = SymbolTable::new();
+    let cnf = parse_dimacs(
+        "c a regression case
+c var 1 = A_occ
+c var 3 = B_occ
+p cnf 3 3
+1 -3 0
+-1 2
+  3 0
+-2 0
+",
+        &mut symbols,
+    )
+    .unwrap();
+    assert_eq!(cnf.clauses, vec![vec![1, -3], vec![-1, 2, 3], vec![-2]]);
+    let names: Vec<&str> = cnf
+        .vars
+        .iter()
+        .map(|v| match v {
+            CnfVar::Input(id) => symbols.name(*id),
+            CnfVar::Aux(_) => panic!("imported variables are inputs"),
+        })
+        .collect();
+    assert_eq!(names, ["A_occ", "x2", "B_occ"]);
+    assert_eq!(
+        cnf.expr().named(&symbols).to_string(),
+        "(~x2 & (A_occ | ~B_occ) & (~A_occ | x2 | B_occ))"
+    );
+    let (mut solver, lits) = cnf.solver();
+    assert!(solver.solve());
+    assert!(!solver.model_value(lits[1]));
+    assert_eq!(solver.model_value(lits[0]), solver.model_value(lits[2]));
+}
+
+#[test]
+fn exports_read_back() {
+    let mut symbols = SymbolTable::new();
+    let mut errors = vec![];
+    let e = ExprParser::new()
+        .parse(
+            &mut errors,
+            &mut symbols,
+            "(SB_stop + SB_appr + SA_stop) & ~(B_occ <-> SB_stop) & SA_stop",
+        )
+        .unwrap();
+    let mut encoder = Encoder::new(&[]);
+    encoder.assert(&astToLogic(&e));
+    let exported = encoder.finish();
+    let text = exported.dimacs(&symbols);
+
+    let imported = parse_dimacs(&text, &mut symbols).unwrap();
+    assert_eq!(imported.clauses, exported.clauses);
+    assert_eq!(
+        imported.dimacs(&symbols).lines().last(),
+        text.lines().last()
+    );
+    for (a, b) in imported.vars.iter().zip(&exported.vars) {
+        match (a, b) {
+            (CnfVar::Input(a), CnfVar::Input(b)) => assert_eq!(a, b),
+            (CnfVar::Input(a), CnfVar::Aux(_)) => assert!(symbols.name(*a).starts_with('x')),
+            _ => panic!("imported variables are inputs"),
+        }
+    }
+    assert_eq!(imported.solver().0.solve(), exported.solver().0.solve());
+}
+
+#[test]
+fn malformed_files_are_reported() {
+    let cases = [
+        ("1 2 0\n", "clauses before the problem line"),
+        ("p cnf 2\n1 2 0\n", "malformed problem line"),
+        ("p cnf 2 1\n1 3 0\n", "variable 3 is out of range"),
+        ("p cnf 2 1\n1 a 0\n", "expected a literal"),
+        (
+            "p cnf 2 2\n1 2 0\n",
+            "the problem has 2 clauses but the file has 1",
+        ),
+        ("c var 4 = a\np cnf 2 0\n", "variable 4 is out of range"),
+        (
+            "c var 1 = a\nc var 1 = b\np cnf 2 0\n",
+            "variable 1 is named twice",
+        ),
+        (
+            "c var 1 = a\nc var 2 = a\np cnf 2 0\n",
+            "`a` names both variable 1 and variable 2",
+        ),
+        ("c nothing\n", "missing the problem line"),
+    ];
+    for (src, message) in &cases {
+        let errs = parse_dimacs(src, &mut SymbolTable::new()).unwrap_err();
+        let messages: Vec<&str> = errs.iter().map(|r| &r.message[..]).collect();
+        assert_eq!(messages, [*message], "reading {:?}", src);
+    }
+
+    let src = "p cnf 2 1\n1 -3 0\n";
+    let errs = parse_dimacs(src, &mut SymbolTable::new()).unwrap_err();
+    assert_eq!(
+        SourceFile::new("bad.cnf", src).render(&errs[0]),
+        "error: variable 3 is out of range
+ --> bad.cnf:2:3
+  |
+1 | p cnf 2 1
+  |       - the problem has 2 variables
+2 | 1 -3 0
+  |   ^^
+"
+    );
+}
+
+#[test]
+fn satlib_trailers_end_the_problem() {
+    let cnf = parse_dimacs("p cnf 1 1\n 1 0\n%\n0\n\n", &mut SymbolTable::new()).unwrap();
+    assert_eq!(cnf.clauses, vec![vec![1]]);
+}
+
+#[test]
+fn names_are_one_to_one() {
+    let src = "c var 1 = A_occ\nc var 2 = A_occ\np cnf 2 0\n";
+    let errs = parse_dimacs(src, &mut SymbolTable::new()).unwrap_err();
+    assert_eq!(
+        SourceFile::new("names.cnf", src).render(&errs[0]),
+        "error: `A_occ` names both variable 1 and variable 2
+ --> names.cnf:2:11
+  |
+1 | c var 1 = A_occ
+  |           ----- variable 1
+2 | c var 2 = A_occ
+  |           ^^^^^
+"
+    );
+
+    // An unnamed variable's default name steers clear of explicit ones.
+    let mut symbols = SymbolTable::new();
+    let cnf = parse_dimacs("c var 1 = x2\np cnf 3 1\n1 -2 3 0\n", &mut symbols).unwrap();
+    let names: Vec<&str> = cnf
+        .vars
+        .iter()
+        .map(|v| match v {
+            CnfVar::Input(id) => symbols.name(*id),
+            CnfVar::Aux(_) => panic!("imported variables are inputs"),
+        })
+        .collect();
+    assert_eq!(names, ["x2", "x2_1", "x3"]);
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use common::group;
+use keen4_flyweight::ast::nodes::{Ident, Item, Program, Statement, StatementKind, Value};
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::format::{format_expr, format_program};
+use keen4_flyweight::keen4::ExprParser;
+use keen4_flyweight::parser::parse_program;
+
+const MESSY: &str = "# header comment
+import \"stations/a#b.fw\"   # trailing on import
+
+
+x := false
+   y <- true # y trailing
+z <- false
+constraints( bound = z ; free = x,y ) {   # header trailing
+
+    # leading in block
+  x + (y + z)
+
+
+      (x -> y) -> z   # expr trailing
+  x <-> (y <-> z)
+  ~(x & y) | ~~z
+  # before close
+}
+run {
+  z <- transaction {
+   x <- IN
+      # inner
+  y <- IN
+  }  # after transaction
+  OUT <- z
+}
+# final
+";
+
+const TIDY: &str = "# header comment
+import \"stations/a#b.fw\" # trailing on import
+
+x <- false
+y <- true # y trailing
+z <- false
+constraints(free=x, y; bound=z) { # header trailing
+  # leading in block
+  x + (y + z)
+
+  (x -> y) -> z # expr trailing
+  x <-> (y <-> z)
+  ~(x & y) | ~~z
+  # before close
+}
+run {
+  z <- transaction {
+    x <- IN
+    # inner
+    y <- IN
+  } # after transaction
+  OUT <- z
+}
+# final
+";
+
+/// Everything in a program but its spans, so two parses can be compared.
+fn shape(symbols: &SymbolTable, program: &Program) -> Vec<String> {
+    let names = |idents: &[Ident]| {
+        idents
+            .iter()
+            .map(|i| symbols.name(i.id))
+            .collect::<Vec<&str>>()
+            .join(",")
+    };
+    let mut out = vec![];
+    for item in &program.items {
+        match item {
+            Item::Import(i) => out.push(format!("import {}", i.path)),
+            Item::Declaration(d) => out.push(format!("{} = {}", names(&[d.name]), d.value)),
+            Item::Constraints(c) => {
+                out.push(format!("free {} bound {}", names(&c.free), names(&c.bound)));
+                out.extend(c.exprs.iter().map(|e| group(symbols, e)));
+            }
+            Item::Run(r) => statements(symbols, &r.statements, &mut out),
+        }
+    }
+    out
+}
+
+fn statements(symbols: &SymbolTable, body: &[Statement], out: &mut Vec<String>) {
+    for s in body {
+        match &s.kind {
+            StatementKind::Assign { targets, value } => {
+                let targets: Vec<&str> = targets.iter().map(|t| symbols.name(t.id)).collect();
+                match value {
+                    Value::Input(_) => out.push(format!("{:?} <- IN", targets)),
+                    Value::Expr(e) => out.push(format!("{:?} <- {}", targets, group(symbols, e))),
+                    Value::Transaction(body, _) => {
+                        out.push(format!("{:?} <- transaction", targets));
+                        statements(symbols, body, out);
+                        out.push("end".to_string());
+                    }
+                }
+            }
+            StatementKind::Output(e) => out.push(format!("OUT <- {}", group(symbols, e))),
+        }
+    }
+}
+
+/// Formats `src`, checks the result parses back to the same program and is
+/// already formatted, and returns it.
+fn round_trip(src: &str) -> String {
+    let mut symbols = SymbolTable::new();
+    let program = parse_program(src, &mut symbols).unwrap();
+    let formatted = format_program(&program, src, &symbols);
+
+    let reparsed = parse_program(&formatted, &mut symbols)
+        .unwrap_or_else(|e| panic!("formatted output doesn't parse: {:?}\n{}", e, formatted));
+    assert_eq!(shape(&symbols, &reparsed), shape(&symbols, &program));
+    assert_eq!(format_program(&reparsed, &formatted, &symbols), formatted);
+    formatted
+}
+
+#[test]
+fn sample_round_trips() {
+    let sample = include_str!("../../sample.flyweightkeen4");
+    let formatted = round_trip(sample);
+    // Only the redundant parentheses and the list spacing change.
+    assert_eq!(
+        formatted,
+        sample
+            .replace("SA_stop,SA_appr", "SA_stop, SA_appr")
+            .replace("(B_occ & ~SA_stop) ->", "B_occ & ~SA_stop ->")
+            .replace("(~SA_appr & ~SA_stop) ->", "~SA_appr & ~SA_stop ->")
+    );
+}
+
+#[test]
+fn layout_is_normalized_and_comments_kept() {
+    assert_eq!(round_trip(MESSY), TIDY);
+    assert_eq!(round_trip(TIDY), TIDY);
+}
+
+#[test]
+fn parentheses_are_minimal() {
+    let cases = [
+        ("(a & b) | c", "a & b | c"),
+        ("a & (b | c)", "a & (b | c)"),
+        ("(a -> b) -> c", "(a -> b) -> c"),
+        ("a -> (b -> c)", "a -> b -> c"),
+        ("(a <-> b) <-> c", "a <-> b <-> c"),
+        ("a <-> (b <-> c)", "a <-> (b <-> c)"),
+        ("~(a + b) + ~~(c)", "~(a + b) + ~~c"),
+        ("((true)) | (false & a)", "true | false & a"),
+    ];
+    for (src, expected) in &cases {
+        let formatted = round_trip(&format!("constraints() {{\n  {}\n}}\n", src));
+        assert_eq!(formatted, format!("constraints() {{\n  {}\n}}\n", expected));
+        let mut symbols = SymbolTable::new();
+        let e = ExprParser::new()
+            .parse(&mut vec![], &mut symbols, expected)
+            .unwrap();
+        assert_eq!(format_expr(&e, &symbols), *expected);
+    }
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use common::group;
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::keen4::ExprParser;
+use keen4_flyweight::parser::parse_program;
+
+/// Binary operators from tightest to loosest binding.
+const LADDER: [&str; 5] = ["&", "+", "|", "->", "<->"];
+
+fn assert_parses_as(src: &str, expected: &str) {
+    let mut errors = vec![];
+    let mut symbols = SymbolTable::new();
+    let e = ExprParser::new()
+        .parse(&mut errors, &mut symbols, src)
+        .unwrap_or_else(|e| panic!("{:?} failed to parse: {:?}", src, e));
+    assert!(errors.is_empty(), "{:?} failed to parse: {:?}", src, errors);
+    assert_eq!(group(&symbols, &e), expected, "while parsing {:?}", src);
+}
+
+#[test]
+fn every_operator_pair_groups_by_precedence() {
+    for (i, tight) in LADDER.iter().enumerate() {
+        for loose in &LADDER[i + 1..] {
+            assert_parses_as(
+                &format!("a {} b {} c", tight, loose),
+                &format!("((a {} b) {} c)", tight, loose),
+            );
+            assert_parses_as(
+                &format!("a {} b {} c", loose, tight),
+                &format!("(a {} (b {} c))", loose, tight),
+            );
+        }
+    }
+}
+
+#[test]
+fn operators_associate() {
+    assert_parses_as("a & b & c", "((a & b) & c)");
+    assert_parses_as("a + b + c", "((a + b) + c)");
+    assert_parses_as("a | b | c", "((a | b) | c)");
+    assert_parses_as("a -> b -> c", "(a -> (b -> c))");
+    assert_parses_as("a <-> b <-> c", "((a <-> b) <-> c)");
+}
+
+#[test]
+fn not_binds_tightest() {
+    for op in &LADDER {
+        assert_parses_as(&format!("~a {} ~b", op), &format!("(~a {} ~b)", op));
+    }
+    assert_parses_as("~~a", "~~a");
+    assert_parses_as("~(a & b)", "~(a & b)");
+}
+
+#[test]
+fn parentheses_override_precedence() {
+    assert_parses_as("(a | b) & c", "((a | b) & c)");
+    assert_parses_as("(a -> b) -> c", "((a -> b) -> c)");
+    assert_parses_as("a & (b <-> c)", "(a & (b <-> c))");
+}
+
+#[test]
+fn mixed_expressions() {
+    assert_parses_as("a | b & c -> d", "((a | (b & c)) -> d)");
+    assert_parses_as(
+        "(B_occ & ~SA_stop) -> SA_appr",
+        "((B_occ & ~SA_stop) -> SA_appr)",
+    );
+    assert_parses_as(
+        "SB_stop + SB_appr + SB_clear",
+        "((SB_stop + SB_appr) + SB_clear)",
+    );
+}
+
+#[test]
+fn fat_arrow_is_implication() {
+    assert_parses_as("a => b", "(a -> b)");
+    assert_parses_as("a => b -> c", "(a -> (b -> c))");
+    assert_parses_as("a | b => c", "((a | b) -> c)");
+}
+
+#[test]
+fn boolean_literals() {
+    assert_parses_as("true", "true");
+    assert_parses_as("false", "false");
+    assert_parses_as("a & true | ~false", "((a & true) | ~false)");
+}
+
+#[test]
+fn sample_programs_parse() {
+    let mut symbols = SymbolTable::new();
+    assert!(parse_program(include_str!("../../sample.flyweightkeen4"), &mut symbols).is_ok());
+    assert!(parse_program(
+        "x := false
+y := false
+z := false
+
+constraints(free=x,y; bound=z) {
+ x + y + z
+}
+
+run {
+  z <- transaction {
+    x <- IN
+    y <- IN
+  }
+  OUT <- z
+}
+",
+        &mut symbols
+    )
+    .is_ok());
+}
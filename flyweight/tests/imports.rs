@@ -0,0 +1,150 @@
+use keen4_flyweight::ast::nodes::Program;
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::diagnostics::{Report, SourceMap};
+use keen4_flyweight::loader::load_program;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+const STATION_A: &str = "B_occ <- false
+SA_stop <- true
+
+constraints(bound=SA_stop) {
+  B_occ -> SA_stop
+}
+";
+
+const STATION_B: &str = "import \"stationA.fw\"
+SB_stop <- true
+
+constraints(bound=SB_stop) {
+  stationA::B_occ -> SB_stop
+}
+";
+
+/// Loads `main.fw` from `files`, an in-memory file system.
+fn load(files: &[(&str, &str)]) -> (SourceMap, SymbolTable, Option<Program>, Vec<Report>) {
+    let files: HashMap<&str, &str> = files.iter().copied().collect();
+    let mut sources = SourceMap::new();
+    let mut symbols = SymbolTable::new();
+    let (program, errs) = load_program(
+        Path::new("main.fw"),
+        files["main.fw"].to_string(),
+        &mut sources,
+        &mut symbols,
+        |p| {
+            files
+                .get(p.to_str().unwrap())
+                .map(|t| t.to_string())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file"))
+        },
+    );
+    (sources, symbols, program, errs)
+}
+
+#[test]
+fn imported_names_are_qualified() {
+    let (sources, symbols, program, errs) = load(&[
+        (
+            "main.fw",
+            "import \"stations/stationA.fw\"
+import \"stations/stationB.fw\"
+run {
+  stationA::B_occ <- IN
+  OUT <- stationB::SB_stop
+}
+",
+        ),
+        ("stations/stationA.fw", STATION_A),
+        ("stations/stationB.fw", STATION_B),
+    ]);
+    assert_eq!(errs, vec![]);
+    let program = program.unwrap();
+    assert_eq!(program.check_names(&symbols), Ok(()));
+
+    // stationA is imported twice but only loaded once.
+    assert_eq!(sources.files().count(), 3);
+    let declared: Vec<&str> = program
+        .declarations()
+        .map(|d| symbols.name(d.name.id))
+        .collect();
+    assert_eq!(
+        declared,
+        vec!["stationA::B_occ", "stationA::SA_stop", "stationB::SB_stop"]
+    );
+    let c = program.constraints().nth(1).unwrap();
+    assert_eq!(sources.location(c.span.start), "stations/stationB.fw:4:1");
+}
+
+#[test]
+fn conflicting_definitions_show_both_locations() {
+    let (sources, symbols, program, errs) = load(&[
+        (
+            "main.fw",
+            "import \"a/station.fw\"
+import \"b/station.fw\"
+",
+        ),
+        ("a/station.fw", "x <- false\n"),
+        ("b/station.fw", "y <- false\nx <- true\n"),
+    ]);
+    assert_eq!(errs, vec![]);
+    let errs = program.unwrap().check_names(&symbols).unwrap_err();
+    let rendered = errs
+        .iter()
+        .map(|e| sources.render(e))
+        .find(|r| r.contains("more than once"))
+        .unwrap();
+    assert_eq!(
+        rendered,
+        "error: `station::x` is declared more than once
+ --> b/station.fw:2:1
+  |
+2 | x <- true
+  | ^ declared again here
+ ::: a/station.fw:1:1
+  |
+1 | x <- false
+  | - first declared here
+"
+    );
+}
+
+#[test]
+fn a_variable_is_bound_by_one_block() {
+    let (sources, symbols, program, _) = load(&[
+        (
+            "main.fw",
+            "import \"stationA.fw\"
+constraints(bound=stationA::SA_stop) {
+  ~stationA::SA_stop
+}
+",
+        ),
+        ("stationA.fw", STATION_A),
+    ]);
+    let errs = program.unwrap().check_names(&symbols).unwrap_err();
+    assert_eq!(errs.len(), 1);
+    let rendered = sources.render(&errs[0]);
+    assert!(rendered.starts_with(
+        "error: `stationA::SA_stop` is bound by more than one constraint block
+ --> main.fw:2:19
+"
+    ));
+    assert!(rendered.contains(" ::: stationA.fw:4:19\n"));
+}
+
+#[test]
+fn missing_imports_are_reported() {
+    let (sources, _, program, errs) = load(&[("main.fw", "import \"nowhere.fw\"\nx <- false\n")]);
+    assert!(program.is_some());
+    assert_eq!(
+        sources.render(&errs[0]),
+        "error: could not read `nowhere.fw`
+ --> main.fw:1:1
+  |
+1 | import \"nowhere.fw\"
+  | ^^^^^^^^^^^^^^^^^^^ no such file
+"
+    );
+}
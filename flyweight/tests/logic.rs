@@ -0,0 +1,89 @@
+mod common;
+
+use common::{assignments, random_expr, Rng};
+use keen4_flyweight::ast::symbols::{Names, SymbolTable, VarId};
+use keen4_flyweight::bl::astToLogic;
+use keen4_flyweight::bl::logic::Expr;
+use keen4_flyweight::keen4::ExprParser;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+fn lowered(symbols: &mut SymbolTable, src: &str) -> Expr {
+    let mut errors = vec![];
+    let e = ExprParser::new().parse(&mut errors, symbols, src).unwrap();
+    *astToLogic(&e)
+}
+
+fn hash(e: &Expr) -> u64 {
+    let mut h = DefaultHasher::new();
+    e.hash(&mut h);
+    h.finish()
+}
+
+#[test]
+fn canonical_forms_print_flat_and_sorted() {
+    let cases = [
+        ("c & (a & b) & a", "(a & b & c)"),
+        ("b | a -> c", "(c | (~a & ~b))"),
+        ("~(a & ~b)", "(~a | b)"),
+        ("a <-> b", "~(a + b)"),
+        ("~a + b + ~c", "(a + b + c)"),
+        ("a + b + a", "b"),
+        ("a & ~a & b", "𝔽"),
+        ("(a | ~a) & b", "b"),
+        ("a | false", "a"),
+        ("~~a", "a"),
+        ("a + true", "~a"),
+    ];
+    for (src, expected) in &cases {
+        let mut symbols = SymbolTable::new();
+        for v in &["a", "b", "c"] {
+            symbols.intern(v);
+        }
+        let e = lowered(&mut symbols, src);
+        assert_eq!(e.named(&symbols).to_string(), *expected, "lowering {}", src);
+    }
+}
+
+#[test]
+fn equivalent_spellings_compare_and_hash_equal() {
+    let pairs = [
+        ("a & (b & c)", "(c & a) & b"),
+        ("a -> b", "~a | b"),
+        ("~(a | b)", "~b & ~a"),
+        ("a <-> b", "~a + b"),
+        ("~(a <-> b)", "b + a"),
+        (
+            "SA_stop -> SB_stop | C_occ",
+            "~SA_stop | C_occ | SB_stop | SB_stop",
+        ),
+    ];
+    for (a, b) in &pairs {
+        let mut symbols = SymbolTable::new();
+        let (a, b) = (lowered(&mut symbols, a), lowered(&mut symbols, b));
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+    }
+}
+
+#[test]
+fn lowering_keeps_the_truth_table() {
+    let mut symbols = SymbolTable::new();
+    let vars: Vec<VarId> = ["x", "y", "z"].iter().map(|n| symbols.intern(n)).collect();
+    let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+    for _ in 0..2000 {
+        let depth = 1 + rng.below(5) as u32;
+        let e = random_expr(&mut rng, &vars, depth);
+        let l = *astToLogic(&e);
+        for m in assignments(&vars) {
+            assert_eq!(
+                l.evaluate(&m),
+                Some(e.evaluate(&m).is_true()),
+                "{} lowered to {}",
+                e.named(&symbols),
+                l.named(&symbols)
+            );
+        }
+        assert_eq!(l.clone().canonical(), l);
+    }
+}
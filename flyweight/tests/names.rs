@@ -0,0 +1,115 @@
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::parser::parse_program;
+
+fn name_errors(src: &str) -> Vec<String> {
+    let mut symbols = SymbolTable::new();
+    let program = parse_program(src, &mut symbols).unwrap();
+    match program.check_names(&symbols) {
+        Ok(()) => vec![],
+        Err(errs) => errs.into_iter().map(|e| e.message).collect(),
+    }
+}
+
+#[test]
+fn declared_and_used_names_pass() {
+    let src = "x := false
+y := false
+z := false
+
+constraints(free=x,y; bound=z) {
+ x + y + z
+}
+
+run {
+  z <- transaction {
+    x <- IN
+    y <- IN
+  }
+  OUT <- z
+}
+";
+    assert_eq!(name_errors(src), Vec::<String>::new());
+}
+
+#[test]
+fn undeclared_names_are_errors() {
+    let src = "C_occ <- false
+constraints(bound=SC_stop) {
+  C_ocs -> SC_stop
+}
+";
+    assert_eq!(
+        name_errors(src),
+        vec![
+            "`C_occ` is declared but never used",
+            "`SC_stop` is not declared",
+            "`C_ocs` is not declared",
+        ]
+    );
+}
+
+#[test]
+fn free_and_bound_lists_are_checked() {
+    let src = "x <- false
+y <- false
+z <- false
+constraints(free=x,z; bound=z) {
+  x | y -> z
+}
+";
+    assert_eq!(
+        name_errors(src),
+        vec![
+            "`z` is listed as both free and bound",
+            "`y` is neither free nor bound here",
+        ]
+    );
+}
+
+#[test]
+fn duplicate_declarations_are_errors() {
+    let src = "x <- false
+x <- true
+constraints(bound=x) {
+  x
+}
+";
+    assert_eq!(name_errors(src), vec!["`x` is declared more than once"]);
+}
+
+#[test]
+fn names_repeated_in_one_list_are_errors() {
+    let src = "x <- false
+s <- false
+t <- false
+constraints(free=x; bound=s, t, s) {
+  x -> s & t
+}
+";
+    assert_eq!(name_errors(src), vec!["`s` is listed twice in `bound=`"]);
+
+    let src = "x <- false
+s <- false
+constraints(free=x, x; bound=s) {
+  x -> s
+}
+";
+    assert_eq!(name_errors(src), vec!["`x` is listed twice in `free=`"]);
+
+    let src = "x <- false
+s <- false
+constraints(free=x; bound=s) {
+  x -> s
+}
+constraints(free=x; bound=s, s) {
+  x -> s
+}
+";
+    assert_eq!(
+        name_errors(src),
+        vec![
+            "`s` is bound by more than one constraint block",
+            "`s` is listed twice in `bound=`",
+        ]
+    );
+}
@@ -0,0 +1,187 @@
+use keen4_flyweight::ast::nodes::{Item, StatementKind, Value};
+use keen4_flyweight::ast::symbols::{Names, SymbolTable};
+use keen4_flyweight::parser::parse_program;
+
+#[test]
+fn sample_program_parses() {
+    let mut symbols = SymbolTable::new();
+    let program = parse_program(include_str!("../../sample.flyweightkeen4"), &mut symbols).unwrap();
+    let kinds: Vec<&str> = program
+        .items
+        .iter()
+        .map(|i| match i {
+            Item::Import(_) => "import",
+            Item::Declaration(_) => "declaration",
+            Item::Constraints(_) => "constraints",
+            Item::Run(_) => "run",
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "declaration",
+            "declaration",
+            "declaration",
+            "declaration",
+            "declaration",
+            "constraints",
+            "run"
+        ]
+    );
+
+    let declarations: Vec<(&str, bool)> = program
+        .declarations()
+        .map(|d| (symbols.name(d.name.id), d.value))
+        .collect();
+    assert_eq!(
+        declarations,
+        [
+            ("A_occ", false),
+            ("B_occ", false),
+            ("SA_stop", true),
+            ("SA_appr", false),
+            ("SB_stop", true)
+        ]
+    );
+
+    let c = program.constraints().next().unwrap();
+    assert!(c.free.is_empty());
+    let bound: Vec<&str> = c.bound.iter().map(|i| symbols.name(i.id)).collect();
+    assert_eq!(bound, ["SA_stop", "SA_appr", "SB_stop", "SB_appr"]);
+    let clauses: Vec<String> = c
+        .exprs
+        .iter()
+        .map(|e| e.named(&symbols).to_string())
+        .collect();
+    assert_eq!(
+        clauses,
+        [
+            "(A_occ -> SA_stop)",
+            "(B_occ -> SB_stop)",
+            "(C_occ -> SC_stop)",
+            "((B_occ & ~SA_stop) -> SA_appr)",
+            "((~SA_appr & ~SA_stop) -> ~A_occ)",
+            "(~SB_stop -> ~B_occ)",
+            "(~SA_appr | ~SA_stop)"
+        ]
+    );
+
+    let run = program.runs().next().unwrap();
+    assert_eq!(run.statements.len(), 4);
+    match &run.statements[0].kind {
+        StatementKind::Assign {
+            targets,
+            value: Value::Transaction(body, _),
+        } => {
+            let targets: Vec<&str> = targets.iter().map(|i| symbols.name(i.id)).collect();
+            assert_eq!(targets, ["SA_stop", "SA_appr", "SB_stop"]);
+            assert_eq!(body.len(), 2);
+            for s in body {
+                assert!(matches!(
+                    s.kind,
+                    StatementKind::Assign {
+                        value: Value::Input(_),
+                        ..
+                    }
+                ));
+            }
+        }
+        kind => panic!("expected a transaction, got {:?}", kind),
+    }
+    let outputs: Vec<String> = run.statements[1..]
+        .iter()
+        .map(|s| match &s.kind {
+            StatementKind::Output(e) => e.named(&symbols).to_string(),
+            kind => panic!("expected an output, got {:?}", kind),
+        })
+        .collect();
+    assert_eq!(outputs, ["SA_stop", "SA_appr", "SB_stop"]);
+}
+
+#[test]
+fn comments_and_blank_lines_are_skipped() {
+    let mut symbols = SymbolTable::new();
+    let program = parse_program(
+        "# leading comment
+
+a := true   # trailing comment
+b <- false
+
+
+constraints(free=a; bound=b) {
+
+  # a comment line inside the block
+  a -> b  # and after a clause
+
+  b -> a
+}
+# trailing
+",
+        &mut symbols,
+    )
+    .unwrap();
+    assert_eq!(program.declarations().count(), 2);
+    let c = program.constraints().next().unwrap();
+    assert_eq!(c.exprs.len(), 2);
+}
+
+#[test]
+fn clauses_are_one_per_line() {
+    let mut symbols = SymbolTable::new();
+    let program = parse_program("constraints(bound=b) {\n  a\n  b\n}", &mut symbols).unwrap();
+    assert_eq!(program.constraints().next().unwrap().exprs.len(), 2);
+
+    let errs = parse_program("constraints(bound=b) {\n  a b\n}", &mut symbols).unwrap_err();
+    assert_eq!(errs.len(), 1);
+    assert!(
+        errs[0].message.starts_with("unexpected"),
+        "{}",
+        errs[0].message
+    );
+}
+
+#[test]
+fn run_blocks_assign_inputs_expressions_and_outputs() {
+    let mut symbols = SymbolTable::new();
+    let program = parse_program(
+        "run {
+  x <- IN
+  y <- x & ~x
+  OUT <- y
+}
+run {}
+",
+        &mut symbols,
+    )
+    .unwrap();
+    let runs: Vec<_> = program.runs().collect();
+    assert_eq!(runs.len(), 2);
+    assert!(runs[1].statements.is_empty());
+    let kinds: Vec<&str> = runs[0]
+        .statements
+        .iter()
+        .map(|s| match &s.kind {
+            StatementKind::Assign {
+                value: Value::Input(_),
+                ..
+            } => "input",
+            StatementKind::Assign {
+                value: Value::Expr(_),
+                ..
+            } => "expr",
+            StatementKind::Assign {
+                value: Value::Transaction(..),
+                ..
+            } => "transaction",
+            StatementKind::Output(_) => "output",
+        })
+        .collect();
+    assert_eq!(kinds, ["input", "expr", "output"]);
+
+    // Only a transaction may assign several variables at once.
+    let errs = parse_program("run {\n  x, y <- IN\n}", &mut symbols).unwrap_err();
+    assert_eq!(
+        errs[0].message,
+        "only a transaction may assign more than one variable"
+    );
+}
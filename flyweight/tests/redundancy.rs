@@ -0,0 +1,91 @@
+use keen4_flyweight::ast::redundancy::Redundancy;
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::diagnostics::SourceFile;
+use keen4_flyweight::parser::parse_constraints;
+
+const SIGNALS: &str = "constraints(free=A_occ, B_occ; bound=SA_stop, SB_stop) {
+  A_occ -> SA_stop
+  SA_stop -> SB_stop
+  A_occ -> SB_stop
+  SB_stop -> SA_stop | B_occ
+  ~(SB_stop & ~(B_occ | SA_stop))
+}";
+
+#[test]
+fn implied_clauses_name_what_implies_them() {
+    let src = SourceFile::new("signals.fw", SIGNALS);
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let found = c.redundancies();
+
+    let implied: Vec<(usize, Vec<usize>)> = found
+        .iter()
+        .filter_map(|r| match r {
+            Redundancy::Implied { clause, by } => {
+                Some((clause.index, by.iter().map(|c| c.index).collect()))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(implied, vec![(2, vec![0, 1])]);
+    assert_eq!(
+        src.render(&found[0].report(&symbols)),
+        "warning: constraint is implied by the others
+ --> signals.fw:4:3
+  |
+2 |   A_occ -> SA_stop
+  |   ----------------
+3 |   SA_stop -> SB_stop
+  |   ------------------
+4 |   A_occ -> SB_stop
+  |   ^^^^^^^^^^^^^^^^ redundant
+  = note: it follows from `A_occ -> SA_stop` and `SA_stop -> SB_stop`
+"
+    );
+}
+
+#[test]
+fn equivalent_clauses_are_paired() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(SIGNALS, &mut symbols).unwrap();
+    let pairs: Vec<(usize, usize)> = c
+        .redundancies()
+        .iter()
+        .filter_map(|r| match r {
+            Redundancy::Equivalent { first, second } => Some((first.index, second.index)),
+            _ => None,
+        })
+        .collect();
+    // `~(SB_stop & ~(B_occ | SA_stop))` is `SB_stop -> SA_stop | B_occ`.
+    assert_eq!(pairs, vec![(3, 4)]);
+
+    let c = parse_constraints(
+        "constraints(bound=x, y) {\n  x -> y\n  ~y -> ~x\n  y | ~x\n  x | true\n}",
+        &mut symbols,
+    )
+    .unwrap();
+    let found = c.redundancies();
+    let pairs: Vec<(usize, usize)> = found
+        .iter()
+        .filter_map(|r| match r {
+            Redundancy::Equivalent { first, second } => Some((first.index, second.index)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(pairs, vec![(0, 1), (0, 2)]);
+    assert!(found.iter().any(|r| matches!(
+        r,
+        Redundancy::Implied { clause, by } if clause.index == 3 && by.is_empty()
+    )));
+}
+
+#[test]
+fn load_bearing_clauses_are_not_reported() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(
+        "constraints(free=A_occ; bound=SA_stop) {\n  A_occ -> SA_stop\n  SA_stop -> A_occ\n}",
+        &mut symbols,
+    )
+    .unwrap();
+    assert_eq!(c.redundancies(), vec![]);
+}
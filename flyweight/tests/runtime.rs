@@ -0,0 +1,105 @@
+mod common;
+
+use common::{signal_chain, var_map, Rng};
+use keen4_flyweight::ast::solvability::Diagnostic;
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::diagnostics::SourceFile;
+use keen4_flyweight::parser::parse_constraints;
+use keen4_flyweight::runtime::{SolveError, Solver};
+
+#[test]
+fn solves_every_cycle() {
+    let n = 40;
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&signal_chain(n), &mut symbols).unwrap();
+    let mut solver = Solver::checked(&c).unwrap();
+    let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..500 {
+        let bits = rng.next_u64();
+        // Mostly clear blocks, so the signals don't all show stop.
+        let occupied: Vec<bool> = (0..n).map(|i| bits >> i & 31 == 0).collect();
+        let names: Vec<String> = (0..n).map(|i| format!("b{}", i)).collect();
+        let values: Vec<(&str, bool)> = names
+            .iter()
+            .map(|b| b.as_str())
+            .zip(occupied.iter().copied())
+            .collect();
+        let outputs = solver.solve(&var_map(&symbols, &values)).unwrap();
+        for i in 0..n {
+            let s = symbols.get(&format!("s{}", i)).unwrap();
+            assert_eq!(outputs.get(s), Some(occupied[..=i].contains(&true)));
+        }
+    }
+}
+
+#[test]
+fn missing_inputs_are_named() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(
+        "constraints(free=A_occ, B_occ; bound=SA_stop) {\n  SA_stop <-> A_occ | B_occ\n}",
+        &mut symbols,
+    )
+    .unwrap();
+    let mut solver = Solver::new(&c);
+    assert_eq!(
+        solver.solve(&var_map(&symbols, &[("A_occ", true)])),
+        Err(SolveError::MissingInput(symbols.get("B_occ").unwrap()))
+    );
+}
+
+#[test]
+fn violated_clauses_are_named() {
+    let src = SourceFile::new(
+        "bad.fw",
+        "constraints(free=A_occ, B_occ; bound=SA_stop) {\n  A_occ -> SA_stop\n  B_occ -> SA_stop\n  A_occ -> ~SA_stop\n}",
+    );
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    assert!(matches!(
+        Solver::checked(&c).err().unwrap().as_slice(),
+        [Diagnostic::NoSolution { .. }, ..]
+    ));
+
+    let mut solver = Solver::new(&c);
+    let ok = solver.solve(&var_map(&symbols, &[("A_occ", false), ("B_occ", true)]));
+    assert_eq!(ok, Ok(var_map(&symbols, &[("SA_stop", true)])));
+    let err = solver
+        .solve(&var_map(&symbols, &[("A_occ", true), ("B_occ", true)]))
+        .unwrap_err();
+    assert_eq!(
+        src.render(&err.report(&symbols)),
+        "error: constraints can't all hold when A_occ = true
+ --> bad.fw:2:3
+  |
+2 |   A_occ -> SA_stop
+  |   ^^^^^^^^^^^^^^^^ reduces to SA_stop
+...
+4 |   A_occ -> ~SA_stop
+  |   ^^^^^^^^^^^^^^^^^ reduces to ~SA_stop
+"
+    );
+}
+
+#[test]
+fn conflicts_without_inputs_name_none() {
+    let src = SourceFile::new(
+        "bad.fw",
+        "constraints(free=A_occ; bound=SA_stop) {\n  SA_stop <-> A_occ\n  SA_stop <-> ~A_occ\n}",
+    );
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let err = Solver::new(&c)
+        .solve(&var_map(&symbols, &[("A_occ", true)]))
+        .unwrap_err();
+    assert_eq!(
+        src.render(&err.report(&symbols)),
+        "error: constraints can't all hold
+ --> bad.fw:2:3
+  |
+2 |   SA_stop <-> A_occ
+  |   ^^^^^^^^^^^^^^^^^ reduces to (SA_stop <-> A_occ)
+3 |   SA_stop <-> ~A_occ
+  |   ^^^^^^^^^^^^^^^^^^ reduces to (SA_stop <-> ~A_occ)
+"
+    );
+}
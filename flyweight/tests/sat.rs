@@ -0,0 +1,284 @@
+mod common;
+
+use common::{signal_chain, var_map, Rng};
+use keen4_flyweight::ast::solvability::Diagnostic;
+use keen4_flyweight::ast::symbols::SymbolTable;
+use keen4_flyweight::bl::sat::{Lit, Solver, Var};
+use keen4_flyweight::diagnostics::{Severity, SourceFile};
+use keen4_flyweight::parser::parse_constraints;
+
+fn vars(solver: &mut Solver, n: usize) -> Vec<Var> {
+    (0..n).map(|_| solver.new_var()).collect()
+}
+
+fn satisfied(solver: &Solver, clauses: &[Vec<Lit>]) -> bool {
+    clauses
+        .iter()
+        .all(|c| c.iter().any(|l| solver.model_value(*l)))
+}
+
+#[test]
+fn pigeons_do_not_fit_in_fewer_holes() {
+    let mut solver = Solver::new();
+    let (pigeons, holes) = (6, 5);
+    // `p[i][h]`: pigeon `i` is in hole `h`.
+    let p: Vec<Vec<Var>> = (0..pigeons).map(|_| vars(&mut solver, holes)).collect();
+    for row in &p {
+        let lits: Vec<Lit> = row.iter().map(|v| v.lit(true)).collect();
+        solver.add_clause(&lits);
+    }
+    // No two pigeons share a hole.
+    for (i, a) in p.iter().enumerate() {
+        for b in &p[i + 1..] {
+            for (x, y) in a.iter().zip(b) {
+                solver.add_clause(&[x.lit(false), y.lit(false)]);
+            }
+        }
+    }
+    assert!(!solver.solve());
+    assert!(solver.conflicts() > 0);
+}
+
+/// Random 3-SAT near the threshold, checked against every assignment.
+#[test]
+fn agrees_with_brute_force() {
+    let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+    for _ in 0..200 {
+        let n = 10;
+        let mut solver = Solver::new();
+        let vs = vars(&mut solver, n);
+        let clauses: Vec<Vec<Lit>> = (0..43)
+            .map(|_| {
+                (0..3)
+                    .map(|_| vs[rng.below(n as u64) as usize].lit(rng.below(2) == 0))
+                    .collect()
+            })
+            .collect();
+        for c in &clauses {
+            solver.add_clause(c);
+        }
+        let expected = (0..1 << n).any(|m: u32| {
+            clauses.iter().all(|c| {
+                c.iter()
+                    .any(|l| (m >> l.var().index() & 1 == 1) == l.is_positive())
+            })
+        });
+        assert_eq!(solver.solve(), expected);
+        if expected {
+            assert!(satisfied(&solver, &clauses));
+        }
+    }
+}
+
+#[test]
+fn assumptions_hold_for_one_call() {
+    let mut solver = Solver::new();
+    let v = vars(&mut solver, 4);
+    let (a, b, c, d) = (
+        v[0].lit(true),
+        v[1].lit(true),
+        v[2].lit(true),
+        v[3].lit(true),
+    );
+    // a -> b, b -> c
+    solver.add_clause(&[!a, b]);
+    solver.add_clause(&[!b, c]);
+
+    assert!(!solver.solve_with(&[d, a, !c]));
+    let mut core = solver.core().to_vec();
+    core.sort();
+    // `d` had nothing to do with it.
+    assert_eq!(core, vec![a, !c]);
+
+    assert!(solver.solve_with(&[a]));
+    assert!(solver.model_value(c));
+    assert!(solver.solve_with(&[!c]));
+    assert!(!solver.model_value(a));
+    assert!(solver.solve());
+}
+
+#[test]
+fn blocking_clauses_enumerate_every_model() {
+    let mut solver = Solver::new();
+    let v = vars(&mut solver, 3);
+    let lits: Vec<Lit> = v.iter().map(|v| v.lit(true)).collect();
+    solver.add_clause(&lits);
+    let mut models = 0;
+    while solver.solve() {
+        models += 1;
+        let block: Vec<Lit> = lits
+            .iter()
+            .map(|l| if solver.model_value(*l) { !*l } else { *l })
+            .collect();
+        solver.add_clause(&block);
+    }
+    assert_eq!(models, 7);
+}
+
+#[test]
+fn multiple_solutions_show_two_of_them() {
+    let src = SourceFile::new(
+        "signals.fw",
+        "constraints(free=A_occ; bound=SA_stop, SA_appr) {\n  A_occ -> SA_stop\n  SA_appr -> SA_stop\n}",
+    );
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let errs = c.assert_bound_used().unwrap_err();
+    assert_eq!(errs.len(), 1);
+    match &errs[0] {
+        Diagnostic::MultipleSolutions {
+            free, solutions, ..
+        } => {
+            assert_ne!(solutions[0], solutions[1]);
+            for s in solutions {
+                let mut values = free.values().clone();
+                values.extend(s.values());
+                assert!(c.conjunction().evaluate(&values).is_true());
+            }
+        }
+        d => panic!("expected multiple solutions, got {:?}", d),
+    }
+    let rendered = src.render(&errs[0].report(&symbols));
+    assert!(rendered.starts_with("error: constraints have multiple solutions when A_occ = "));
+    assert_eq!(
+        rendered
+            .matches("= note: one solution is SA_appr = ")
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn constant_output_notes_what_is_unconstrained() {
+    let src = SourceFile::new(
+        "signals.fw",
+        "constraints(free=A_occ; bound=SA_stop, SA_appr) {\n  A_occ | (SA_stop <-> SA_appr)\n}",
+    );
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let errs = c.assert_bound_used().unwrap_err();
+    let severities: Vec<Severity> = errs.iter().map(|d| d.severity()).collect();
+    assert_eq!(severities, [Severity::Error, Severity::Note]);
+    assert_eq!(
+        src.render(&errs[1].report(&symbols)),
+        "note: when A_occ = true the constraints always hold, leaving `SA_stop`, `SA_appr` unconstrained
+ --> signals.fw:1:1
+  |
+1 | constraints(free=A_occ; bound=SA_stop, SA_appr) {
+  | ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+  = note: the constraints are (A_occ | (SA_stop <-> SA_appr))
+"
+    );
+}
+
+#[test]
+fn unsatisfiable_clauses_are_pointed_at() {
+    let src = SourceFile::new(
+        "bad.fw",
+        "constraints(free=A_occ; bound=SA_stop) {\n  A_occ -> SA_stop\n  A_occ -> ~SA_stop\n}",
+    );
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let errs = c.assert_bound_used().unwrap_err();
+    assert_eq!(
+        errs[0].witness(),
+        Some(&var_map(&symbols, &[("A_occ", true)]))
+    );
+    assert_eq!(
+        src.render(&errs[0].report(&symbols)),
+        "error: constraints have no solution when A_occ = true
+ --> bad.fw:2:3
+  |
+2 |   A_occ -> SA_stop
+  |   ^^^^^^^^^^^^^^^^ reduces to SA_stop
+3 |   A_occ -> ~SA_stop
+  |   ^^^^^^^^^^^^^^^^^ reduces to ~SA_stop
+"
+    );
+}
+
+#[test]
+fn conflicts_without_inputs_name_none() {
+    let src = SourceFile::new(
+        "bad.fw",
+        "constraints(free=A_occ; bound=SA_stop) {\n  SA_stop <-> A_occ\n  SA_stop <-> ~A_occ\n}",
+    );
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let errs = c.assert_bound_used().unwrap_err();
+    assert_eq!(
+        src.render(&errs[0].report(&symbols)),
+        "error: constraints have no solution
+ --> bad.fw:2:3
+  |
+2 |   SA_stop <-> A_occ
+  |   ^^^^^^^^^^^^^^^^^ reduces to (SA_stop <-> A_occ)
+3 |   SA_stop <-> ~A_occ
+  |   ^^^^^^^^^^^^^^^^^^ reduces to (SA_stop <-> ~A_occ)
+  = note: regardless of A_occ
+"
+    );
+}
+
+#[test]
+fn unsatisfiable_blocks_report_only_that() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints("constraints(bound=x, y) {\n  x & y\n  ~x\n}", &mut symbols).unwrap();
+    let errs = c.assert_bound_used().unwrap_err();
+    assert_eq!(errs.len(), 1, "{:?}", errs);
+    assert!(matches!(errs[0], Diagnostic::NoSolution { .. }));
+}
+
+#[test]
+fn conflicts_name_only_the_clauses_and_inputs_involved() {
+    let src = SourceFile::new(
+        "conflict.fw",
+        "constraints(free=A_occ, B_occ, C_occ; bound=SA_stop, SB_stop) {
+  B_occ -> SA_stop
+  A_occ -> ~SB_stop
+  SA_stop -> SB_stop | C_occ
+  ~SB_stop
+}",
+    );
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&src.text, &mut symbols).unwrap();
+    let errs = c.assert_bound_used().unwrap_err();
+    let d = errs
+        .iter()
+        .find(|d| matches!(d, Diagnostic::NoSolution { .. }))
+        .unwrap();
+    assert_eq!(
+        src.render(&d.report(&symbols)),
+        "error: constraints have no solution when B_occ = true, C_occ = false
+ --> conflict.fw:2:3
+  |
+2 |   B_occ -> SA_stop
+  |   ^^^^^^^^^^^^^^^^ reduces to SA_stop
+...
+4 |   SA_stop -> SB_stop | C_occ
+  |   ^^^^^^^^^^^^^^^^^^^^^^^^^^ reduces to (SA_stop -> SB_stop)
+5 |   ~SB_stop
+  |   ^^^^^^^^ reduces to ~SB_stop
+  = note: regardless of A_occ
+"
+    );
+}
+
+#[test]
+fn hundreds_of_variables_check() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(&signal_chain(300), &mut symbols).unwrap();
+    assert_eq!(c.assert_bound_used(), Ok(()));
+
+    // Drop the last definition: s299 is then unconstrained.
+    let mut c = c;
+    c.exprs.pop();
+    let errs = c.assert_bound_used().unwrap_err();
+    let last = symbols.get("s299").unwrap();
+    assert!(errs.iter().any(
+        |d| matches!(d, Diagnostic::UnusedBound { vars } if vars.len() == 1 && vars[0].id == last)
+    ));
+    assert!(errs
+        .iter()
+        .any(|d| matches!(d, Diagnostic::MultipleSolutions { .. })));
+}
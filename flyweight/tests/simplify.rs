@@ -0,0 +1,84 @@
+mod common;
+
+use common::{assignments, random_expr, Rng};
+use keen4_flyweight::ast::nodes::Expr;
+use keen4_flyweight::ast::symbols::{Names, SymbolTable, VarId};
+use keen4_flyweight::keen4::ExprParser;
+
+fn simplified(src: &str) -> String {
+    let mut errors = vec![];
+    let mut symbols = SymbolTable::new();
+    let e = ExprParser::new()
+        .parse(&mut errors, &mut symbols, src)
+        .unwrap();
+    e.simplify().named(&symbols).to_string()
+}
+
+#[test]
+fn each_law_applies() {
+    let cases = [
+        // identity and annulment
+        ("x & true", "x"),
+        ("false | x", "x"),
+        ("x & false", "𝔽"),
+        ("true | x", "𝕋"),
+        ("true -> x", "x"),
+        ("x -> false", "~x"),
+        ("false <-> x", "~x"),
+        ("x + true", "~x"),
+        ("x + false", "x"),
+        // complement and idempotence
+        ("x & ~x", "𝔽"),
+        ("~x | x", "𝕋"),
+        ("x -> x", "𝕋"),
+        ("x + x", "𝔽"),
+        ("x <-> ~x", "𝔽"),
+        ("(x | y) & (x | y)", "(x | y)"),
+        // absorption
+        ("x & (x | y)", "x"),
+        ("(y & x) | x", "x"),
+        ("x & (~x | y)", "(x & y)"),
+        // De Morgan and negated operands
+        ("~x & ~y", "~(x | y)"),
+        ("~(~x | y)", "(x & ~y)"),
+        ("~~x", "x"),
+        ("~x -> ~y", "(y -> x)"),
+        ("~x + y", "(x <-> y)"),
+        ("~(x <-> y)", "(x + y)"),
+    ];
+    for (src, expected) in &cases {
+        assert_eq!(simplified(src), *expected, "simplifying {}", src);
+    }
+}
+
+fn truth_table(e: &Expr, vars: &[VarId]) -> Vec<bool> {
+    assignments(vars)
+        .map(|m| {
+            let value = e.evaluate(&m);
+            assert!(value.is_true() || value.is_false());
+            value.is_true()
+        })
+        .collect()
+}
+
+#[test]
+fn random_expressions_keep_their_truth_table() {
+    let mut symbols = SymbolTable::new();
+    let vars: Vec<VarId> = ["x", "y", "z"].iter().map(|n| symbols.intern(n)).collect();
+    let mut rng = Rng::new(0x853c_49e6_748f_ea9b);
+    for _ in 0..2000 {
+        let depth = 1 + rng.below(5) as u32;
+        let e = random_expr(&mut rng, &vars, depth);
+        let s = e.clone().simplify();
+        assert_eq!(
+            truth_table(&e, &vars),
+            truth_table(&s, &vars),
+            "{} simplified to {}",
+            e.named(&symbols),
+            s.named(&symbols)
+        );
+        assert!(s.size() <= e.size());
+        // A fixed point: nothing left to rewrite.
+        assert_eq!(s.clone().simplify(), s);
+    }
+}
@@ -0,0 +1,78 @@
+use keen4_flyweight::ast::skolem::SkolemError;
+use keen4_flyweight::ast::symbols::{Names, SymbolTable, VarId};
+use keen4_flyweight::parser::parse_constraints;
+use keen4_flyweight::runtime::Solver;
+use std::collections::HashMap;
+
+const SIGNALS: &str =
+    "constraints(free=A_occ, B_occ, C_occ; bound=SA_stop, SB_stop, SB_appr, SC_stop) {
+  SA_stop <-> A_occ | B_occ & ~A_occ
+  SB_stop <-> B_occ
+  SB_appr <-> ~SB_stop & (C_occ <-> ~SA_stop | SB_stop)
+  SC_stop <-> C_occ & (A_occ | ~A_occ)
+}";
+
+#[test]
+fn functions_are_minimized() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(SIGNALS, &mut symbols).unwrap();
+    let found: Vec<(String, String)> = c
+        .skolem_functions()
+        .unwrap()
+        .iter()
+        .map(|f| {
+            (
+                symbols.name(f.var.id).to_string(),
+                f.function.named(&symbols).to_string(),
+            )
+        })
+        .collect();
+    let expected = [
+        ("SA_stop", "(A_occ | B_occ)"),
+        ("SB_stop", "B_occ"),
+        (
+            "SB_appr",
+            "((A_occ & ~B_occ & ~C_occ) | (~A_occ & ~B_occ & C_occ))",
+        ),
+        ("SC_stop", "C_occ"),
+    ];
+    let found: Vec<(&str, &str)> = found.iter().map(|(a, b)| (&a[..], &b[..])).collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn functions_agree_with_the_solver() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(SIGNALS, &mut symbols).unwrap();
+    let functions = c.skolem_functions().unwrap();
+    let mut solver = Solver::checked(&c).unwrap();
+    let free = c.free_vars();
+    for n in 0..1 << free.len() {
+        let inputs: HashMap<VarId, bool> = free
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (*v, n >> i & 1 == 1))
+            .collect();
+        let outputs = solver.solve(&inputs.clone().into()).unwrap();
+        for f in &functions {
+            assert!(f.inputs.iter().all(|v| free.contains(v)));
+            assert_eq!(f.function.evaluate(&inputs), outputs.get(f.var.id));
+        }
+    }
+    let appr = &functions[2];
+    assert_eq!(appr.inputs.len(), 3);
+}
+
+#[test]
+fn unsolvable_blocks_have_no_functions() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(
+        "constraints(free=A_occ; bound=SA_stop) {\n  A_occ -> SA_stop\n}",
+        &mut symbols,
+    )
+    .unwrap();
+    assert!(matches!(
+        c.skolem_functions(),
+        Err(SkolemError::Unsolvable(_))
+    ));
+}
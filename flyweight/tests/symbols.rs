@@ -0,0 +1,32 @@
+use keen4_flyweight::ast::symbols::{Names, SymbolTable};
+use keen4_flyweight::parser::parse_constraints;
+
+#[test]
+fn names_intern_to_one_id() {
+    let mut symbols = SymbolTable::new();
+    let a = symbols.intern("A_occ");
+    let b = symbols.intern("B_occ");
+    assert_ne!(a, b);
+    assert_eq!(symbols.intern("A_occ"), a);
+    assert_eq!(symbols.get("B_occ"), Some(b));
+    assert_eq!(symbols.get("C_occ"), None);
+    assert_eq!(symbols.name(b), "B_occ");
+    assert_eq!(symbols.len(), 2);
+}
+
+#[test]
+fn parsed_names_print_back() {
+    let mut symbols = SymbolTable::new();
+    let c = parse_constraints(
+        "constraints(bound=SA_stop) {\n  (B_occ & ~SA_stop) -> SA_appr\n}",
+        &mut symbols,
+    )
+    .unwrap();
+    assert_eq!(c.bound[0].id, symbols.get("SA_stop").unwrap());
+    assert_eq!(
+        c.exprs[0].named(&symbols).to_string(),
+        "((B_occ & ~SA_stop) -> SA_appr)"
+    );
+    // Without the table only the IDs are known.
+    assert_eq!(format!("{:?}", c.exprs[0]), "((#1 & ~#0) -> #2)");
+}
@@ -0,0 +1,5 @@
+#![feature(box_patterns)]
+
+pub mod ast;
+pub mod mccluskey;
+pub mod sast;
@@ -1,10 +1,5 @@
-#![feature(box_patterns)]
-
-pub mod ast;
-pub mod mccluskey;
-pub mod sast;
-
-use ast::{distribute, var};
+use mckluskey::ast::{distribute, var};
+use mckluskey::{mccluskey, sast};
 
 fn main() -> Result<(), String> {
     // let e = var("d") * !(var("e") + !var("b") + !var("d")) * (var("e") + var("c"))
@@ -1,7 +1,7 @@
 use crate::sast::Expr;
 use itertools::Itertools;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
 pub enum BoolOrDontCare {
     One,
     Zero,
@@ -15,7 +15,7 @@ pub fn number(a: &Vec<BoolOrDontCare>) -> usize {
         .sum()
 }
 
-pub fn diff(a: &Vec<BoolOrDontCare>, b: &Vec<BoolOrDontCare>) -> Vec<BoolOrDontCare> {
+pub fn diff(a: &[BoolOrDontCare], b: &[BoolOrDontCare]) -> Vec<BoolOrDontCare> {
     a.iter()
         .zip(b)
         .map(|(i, j)| {
@@ -122,3 +122,108 @@ pub fn minterms(r: &Expr) -> Result<Vec<(usize, Vec<Vec<BoolOrDontCare>>)>, Stri
         .map(|k| (k.0, k.1.collect()))
         .collect())
 }
+
+/// `a` and `b` merged into one implicant, if they differ in exactly one
+/// variable that neither leaves out.
+fn combine(a: &[BoolOrDontCare], b: &[BoolOrDontCare]) -> Option<Vec<BoolOrDontCare>> {
+    let mut differ = a.iter().zip(b).filter(|(i, j)| i != j);
+    match (differ.next(), differ.next()) {
+        (Some((i, j)), None)
+            if *i != BoolOrDontCare::DontCare && *j != BoolOrDontCare::DontCare =>
+        {
+            Some(diff(a, b))
+        }
+        _ => None,
+    }
+}
+
+/// The implicant of `n` variables that's true only for minterm `m`, bit
+/// `i` of `m` being variable `i`.
+pub fn implicant(n: usize, m: usize) -> Vec<BoolOrDontCare> {
+    (0..n)
+        .map(|i| match m >> i & 1 {
+            1 => BoolOrDontCare::One,
+            _ => BoolOrDontCare::Zero,
+        })
+        .collect()
+}
+
+pub fn covers(implicant: &[BoolOrDontCare], m: usize) -> bool {
+    implicant.iter().enumerate().all(|(i, v)| match v {
+        BoolOrDontCare::One => m >> i & 1 == 1,
+        BoolOrDontCare::Zero => m >> i & 1 == 0,
+        BoolOrDontCare::DontCare => true,
+    })
+}
+
+/// The prime implicants of the function of `n` variables that's true for
+/// the minterms `ones` and may be either for `dont_cares`.
+pub fn prime_implicants(
+    n: usize,
+    ones: &[usize],
+    dont_cares: &[usize],
+) -> Vec<Vec<BoolOrDontCare>> {
+    let mut current: Vec<Vec<BoolOrDontCare>> = ones
+        .iter()
+        .chain(dont_cares)
+        .map(|m| implicant(n, *m))
+        .unique()
+        .collect();
+    let mut primes = vec![];
+    while !current.is_empty() {
+        // Only implicants whose counts of ones differ by one can merge.
+        current.sort_by_key(count_ones);
+        let mut merged = vec![false; current.len()];
+        let mut next = vec![];
+        for i in 0..current.len() {
+            for j in i + 1..current.len() {
+                if count_ones(&current[j]) > count_ones(&current[i]) + 1 {
+                    break;
+                }
+                if let Some(c) = combine(&current[i], &current[j]) {
+                    merged[i] = true;
+                    merged[j] = true;
+                    next.push(c);
+                }
+            }
+        }
+        primes.extend(
+            current
+                .into_iter()
+                .zip(merged)
+                .filter(|(_, merged)| !merged)
+                .map(|(a, _)| a),
+        );
+        current = next.into_iter().unique().collect();
+    }
+    primes
+}
+
+/// A cover of `ones` by prime implicants: the essential ones, then
+/// whichever covers the most of what's left until nothing is. That's not
+/// always the smallest cover, which can take exponential time to find.
+pub fn minimize(n: usize, ones: &[usize], dont_cares: &[usize]) -> Vec<Vec<BoolOrDontCare>> {
+    let primes = prime_implicants(n, ones, dont_cares);
+    let mut left: Vec<usize> = ones.to_vec();
+    let mut cover: Vec<Vec<BoolOrDontCare>> = vec![];
+    let mut take = |p: &Vec<BoolOrDontCare>, left: &mut Vec<usize>| {
+        if !cover.contains(p) {
+            left.retain(|m| !covers(p, *m));
+            cover.push(p.clone());
+        }
+    };
+    for m in ones {
+        let mut covering = primes.iter().filter(|p| covers(p, *m));
+        if let (Some(p), None) = (covering.next(), covering.next()) {
+            take(p, &mut left);
+        }
+    }
+    while let Some(p) = primes
+        .iter()
+        .max_by_key(|p| left.iter().filter(|m| covers(p, **m)).count())
+        .filter(|_| !left.is_empty())
+    {
+        take(p, &mut left);
+    }
+    cover
+}